@@ -0,0 +1,104 @@
+/*
+ * C11 view of the shm crate synchronization primitives.
+ *
+ * The Rust types are #[repr(C)] and their field offsets are asserted at
+ * compile time in src/{mutex,condvar,rwlock}.rs. The structs below declare
+ * the same layout; embed them in a C struct exactly where the Rust struct
+ * places the corresponding field. The protected data of a Mutex or RwLock
+ * follows the lock words using ordinary C struct layout rules, e.g.
+ *
+ *     Rust: Mutex<u64>          C: struct { shm_mutex lock; uint64_t data; }
+ *
+ * All futex calls omit FUTEX_PRIVATE_FLAG since the words live in memory
+ * shared between processes.
+ *
+ * The layouts assume the crate's `metrics` feature is disabled, as it appends
+ * contention counters to every Mutex and RwLock.
+ *
+ * Only the Mutex protocol is implemented here. Condvar and RwLock are
+ * declared so C code can size and place them, but must only be operated on
+ * from Rust.
+ */
+
+#ifndef SHM_SYNC_H
+#define SHM_SYNC_H
+
+/* syscall(2) is only declared with _GNU_SOURCE; include this header first. */
+#ifndef _GNU_SOURCE
+#define _GNU_SOURCE
+#endif
+
+#include <linux/futex.h>
+#include <stdatomic.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <sys/syscall.h>
+#include <unistd.h>
+
+/* 0: unlocked, 1: locked, 2: locked with waiters (contended) */
+typedef struct {
+    _Atomic uint32_t state;
+} shm_mutex;
+
+typedef struct {
+    _Atomic uint32_t counter;
+    _Atomic size_t num_waiters;
+    _Atomic ptrdiff_t mutex_offset;
+} shm_condvar;
+
+typedef struct {
+    _Atomic uint32_t state;
+    _Atomic uint32_t writer_wake_counter;
+} shm_rwlock;
+
+_Static_assert(sizeof(_Atomic uint32_t) == 4, "futex word must be 32 bits");
+_Static_assert(offsetof(shm_mutex, state) == 0, "shm_mutex layout");
+_Static_assert(offsetof(shm_condvar, counter) == 0, "shm_condvar layout");
+_Static_assert(offsetof(shm_condvar, num_waiters) == sizeof(size_t), "shm_condvar layout");
+_Static_assert(offsetof(shm_condvar, mutex_offset) == 2 * sizeof(size_t), "shm_condvar layout");
+_Static_assert(offsetof(shm_rwlock, state) == 0, "shm_rwlock layout");
+_Static_assert(offsetof(shm_rwlock, writer_wake_counter) == 4, "shm_rwlock layout");
+
+static inline void shm_futex_wait(_Atomic uint32_t *word, uint32_t expected)
+{
+    syscall(SYS_futex, word, FUTEX_WAIT_BITSET, expected, NULL, NULL, FUTEX_BITSET_MATCH_ANY);
+}
+
+static inline void shm_futex_wake_one(_Atomic uint32_t *word)
+{
+    syscall(SYS_futex, word, FUTEX_WAKE, 1);
+}
+
+static inline int shm_mutex_try_lock(shm_mutex *m)
+{
+    uint32_t expected = 0;
+    return atomic_compare_exchange_strong_explicit(
+        &m->state, &expected, 1, memory_order_acquire, memory_order_relaxed);
+}
+
+static inline void shm_mutex_lock(shm_mutex *m)
+{
+    if (shm_mutex_try_lock(m)) {
+        return;
+    }
+
+    for (int spin = 100; spin > 0 && atomic_load_explicit(&m->state, memory_order_relaxed) == 1; --spin) {
+    }
+
+    if (shm_mutex_try_lock(m)) {
+        return;
+    }
+
+    while (atomic_exchange_explicit(&m->state, 2, memory_order_acquire) != 0) {
+        shm_futex_wait(&m->state, 2);
+    }
+}
+
+static inline void shm_mutex_unlock(shm_mutex *m)
+{
+    if (atomic_exchange_explicit(&m->state, 0, memory_order_release) == 2) {
+        shm_futex_wake_one(&m->state);
+    }
+}
+
+#endif /* SHM_SYNC_H */
@@ -0,0 +1,55 @@
+// Registers a shared region as pinned (page-locked) host memory so a device can DMA to and from it
+// without staging copies.
+//
+// mlock stands in for the pinned-memory API here; with CUDA replace `register`/`unregister` with
+// cudaHostRegister(ptr, len, cudaHostRegisterPortable) and cudaHostUnregister(ptr).
+
+use {
+    shm::{page_size, Shareable, Shared},
+    std::{ffi::CString, io, sync::atomic::AtomicU32},
+};
+
+// Keeping whole pages per frame avoids sharing a pinned page with unrelated data.
+#[repr(C, align(4096))]
+struct Frames {
+    ready: AtomicU32,
+    pixels: [[u8; 4096]; 16],
+}
+
+impl Default for Frames {
+    fn default() -> Self {
+        Self {
+            ready: AtomicU32::new(0),
+            pixels: [[0; 4096]; 16],
+        }
+    }
+}
+
+unsafe impl Shareable for Frames {}
+
+fn register(ptr: *mut u8, len: usize) -> io::Result<()> {
+    match unsafe { libc::mlock(ptr.cast(), len) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn unregister(ptr: *mut u8, len: usize) {
+    unsafe { libc::munlock(ptr.cast(), len) };
+}
+
+fn main() {
+    let shm_name = CString::new("/pinned_frames").unwrap();
+    let frames: Shared<Frames> = unsafe { Shared::create(&shm_name).unwrap() };
+
+    let (ptr, len) = (frames.as_ptr(), frames.mapped_len());
+    assert_eq!(ptr as usize % page_size(), 0);
+    match register(ptr, len) {
+        Ok(()) => {
+            println!("pinned {len} bytes at {ptr:?}");
+            // ... hand `ptr` to the device for DMA ...
+            unregister(ptr, len);
+        }
+        Err(e) => println!("unable to pin {len} bytes: {e}"),
+    }
+}
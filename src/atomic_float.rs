@@ -0,0 +1,129 @@
+use {
+    crate::Shareable,
+    core::{
+        fmt,
+        sync::atomic::{AtomicU32, AtomicU64, Ordering},
+    },
+};
+
+macro_rules! atomic_float {
+    ($name:ident, $float:ty, $atomic:ty) => {
+        /// A floating point value stored as its bit pattern in an atomic integer.
+        ///
+        /// Read-modify-write operations are compare-and-swap loops.
+        #[repr(transparent)]
+        #[derive(Default)]
+        pub struct $name($atomic);
+
+        unsafe impl Shareable for $name {}
+
+        impl fmt::Debug for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.load(Ordering::Relaxed), f)
+            }
+        }
+
+        impl $name {
+            pub const fn new(value: $float) -> Self {
+                Self(<$atomic>::new(value.to_bits()))
+            }
+
+            pub fn load(&self, order: Ordering) -> $float {
+                <$float>::from_bits(self.0.load(order))
+            }
+
+            pub fn store(&self, value: $float, order: Ordering) {
+                self.0.store(value.to_bits(), order)
+            }
+
+            pub fn swap(&self, value: $float, order: Ordering) -> $float {
+                <$float>::from_bits(self.0.swap(value.to_bits(), order))
+            }
+
+            /// Compares bit patterns, so `-0.0` differs from `0.0` and NaN can match itself.
+            pub fn compare_exchange(
+                &self,
+                current: $float,
+                new: $float,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$float, $float> {
+                self.0
+                    .compare_exchange(current.to_bits(), new.to_bits(), success, failure)
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            /// Applies `f` until it succeeds or returns None, returning the previous value.
+            pub fn fetch_update(
+                &self,
+                set_order: Ordering,
+                fetch_order: Ordering,
+                mut f: impl FnMut($float) -> Option<$float>,
+            ) -> Result<$float, $float> {
+                self.0
+                    .fetch_update(set_order, fetch_order, |bits| {
+                        f(<$float>::from_bits(bits)).map(<$float>::to_bits)
+                    })
+                    .map(<$float>::from_bits)
+                    .map_err(<$float>::from_bits)
+            }
+
+            pub fn fetch_add(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_rmw(order, |v| v + value)
+            }
+
+            pub fn fetch_sub(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_rmw(order, |v| v - value)
+            }
+
+            pub fn fetch_min(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_rmw(order, |v| v.min(value))
+            }
+
+            pub fn fetch_max(&self, value: $float, order: Ordering) -> $float {
+                self.fetch_rmw(order, |v| v.max(value))
+            }
+
+            fn fetch_rmw(&self, order: Ordering, f: impl Fn($float) -> $float) -> $float {
+                let fetch_order = match order {
+                    Ordering::Release => Ordering::Relaxed,
+                    Ordering::AcqRel => Ordering::Acquire,
+                    order => order,
+                };
+                match self.fetch_update(order, fetch_order, |v| Some(f(v))) {
+                    Ok(v) | Err(v) => v,
+                }
+            }
+        }
+    };
+}
+
+atomic_float!(AtomicF32, f32, AtomicU32);
+atomic_float!(AtomicF64, f64, AtomicU64);
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{sync::atomic::Ordering::Relaxed, thread},
+    };
+
+    #[test]
+    fn atomic_float() {
+        let f = AtomicF64::new(1.5);
+        assert_eq!(f.fetch_add(1.0, Relaxed), 1.5);
+        assert_eq!(f.fetch_max(0.5, Relaxed), 2.5);
+        assert_eq!(f.fetch_min(0.5, Relaxed), 2.5);
+        assert_eq!(f.load(Relaxed), 0.5);
+        assert_eq!(f.compare_exchange(1.0, 2.0, Relaxed, Relaxed), Err(0.5));
+
+        let acc = AtomicF32::default();
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| (0..1000).for_each(|_| _ = acc.fetch_add(1.0, Relaxed)));
+            }
+        });
+        assert_eq!(acc.load(Relaxed), 4000.0);
+    }
+}
@@ -0,0 +1,178 @@
+use {
+    crate::ordering::{Acquire, Relaxed, Release},
+    core::{cell::UnsafeCell, fmt, sync::atomic::AtomicU32},
+};
+
+/// A double-width atomic integer usable in Shareable structs.
+///
+/// On x86_64 CPUs supporting `cmpxchg16b` (detected at runtime) all operations are lock-free,
+/// otherwise they are serialized by a small lock stored alongside the value. The detection result
+/// is identical for every process on a host, so processes never mix the two strategies.
+///
+/// With `cmpxchg16b` all operations are sequentially consistent (the instruction is a full
+/// barrier). The lock of the fallback, which is the only strategy on other architectures
+/// (including aarch64), is taken with Acquire and released with Release ordering: operations on
+/// one cell remain linearizable, but aren't ordered as SeqCst against other atomics. A process
+/// killed while holding that lock also leaves it held, so every other process's next operation
+/// spins forever.
+///
+/// Every operation writes the cell, including [`load`](Self::load): `cmpxchg16b` stores even
+/// when the comparison fails, and the fallback takes the lock. The region must therefore be
+/// mapped writable, so an AtomicU128 can't be read through a [`crate::SharedRead`] (the store
+/// faults with SIGSEGV).
+#[repr(C, align(16))]
+pub struct AtomicU128 {
+    value: UnsafeCell<u128>,
+    lock: AtomicU32,
+}
+
+unsafe impl Sync for AtomicU128 {}
+
+unsafe impl crate::Shareable for AtomicU128 {}
+
+impl Default for AtomicU128 {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl fmt::Debug for AtomicU128 {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.load(), f)
+    }
+}
+
+impl AtomicU128 {
+    pub const fn new(value: u128) -> Self {
+        Self {
+            value: UnsafeCell::new(value),
+            lock: AtomicU32::new(0),
+        }
+    }
+
+    /// Reads the value, which writes the cell (see the type's documentation).
+    pub fn load(&self) -> u128 {
+        match self.compare_exchange(0, 0) {
+            Ok(v) | Err(v) => v,
+        }
+    }
+
+    pub fn store(&self, value: u128) {
+        self.swap(value);
+    }
+
+    pub fn swap(&self, value: u128) -> u128 {
+        self.fetch_update(|_| Some(value)).unwrap()
+    }
+
+    /// Stores `new` if the value equals `current`, returning the previous value.
+    pub fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+        #[cfg(target_arch = "x86_64")]
+        if std::is_x86_feature_detected!("cmpxchg16b") {
+            // [SAFETY]: The cell is 16-byte aligned and the instruction is supported.
+            return unsafe { cmpxchg16b(self.value.get(), current, new) };
+        }
+        self.locked_compare_exchange(current, new)
+    }
+
+    /// Applies `f` until it succeeds or returns None, returning the previous value.
+    pub fn fetch_update(&self, mut f: impl FnMut(u128) -> Option<u128>) -> Result<u128, u128> {
+        let mut prev = self.load();
+        while let Some(next) = f(prev) {
+            match self.compare_exchange(prev, next) {
+                Ok(v) => return Ok(v),
+                Err(v) => prev = v,
+            }
+        }
+        Err(prev)
+    }
+
+    fn locked_compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+        while self
+            .lock
+            .compare_exchange_weak(0, 1, Acquire, Relaxed)
+            .is_err()
+        {
+            std::thread::yield_now();
+        }
+        // [SAFETY]: The lock guarantees exclusive access to the value.
+        let value = unsafe { &mut *self.value.get() };
+        let prev = *value;
+        if prev == current {
+            *value = new;
+        }
+        self.lock.store(0, Release);
+        if prev == current {
+            Ok(prev)
+        } else {
+            Err(prev)
+        }
+    }
+}
+
+#[cfg(target_arch = "x86_64")]
+unsafe fn cmpxchg16b(dst: *mut u128, current: u128, new: u128) -> Result<u128, u128> {
+    let (prev_lo, prev_hi): (u64, u64);
+    let ok: u8;
+    // rbx is reserved by LLVM, so the low half of `new` is swapped in and out around the exchange.
+    core::arch::asm!(
+        "xchg {new_lo}, rbx",
+        "lock cmpxchg16b xmmword ptr [{dst}]",
+        "sete {ok}",
+        "mov rbx, {new_lo}",
+        dst = in(reg) dst,
+        new_lo = inout(reg) new as u64 => _,
+        ok = out(reg_byte) ok,
+        in("rcx") (new >> 64) as u64,
+        inout("rax") current as u64 => prev_lo,
+        inout("rdx") (current >> 64) as u64 => prev_hi,
+        options(nostack),
+    );
+    let prev = u128::from(prev_hi) << 64 | u128::from(prev_lo);
+    if ok != 0 {
+        Ok(prev)
+    } else {
+        Err(prev)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn atomic_u128() {
+        let a = AtomicU128::new(1 << 64);
+        assert_eq!(a.compare_exchange(1, 2), Err(1 << 64));
+        assert_eq!(a.compare_exchange(1 << 64, u128::MAX), Ok(1 << 64));
+        assert_eq!(a.swap(3), u128::MAX);
+        assert_eq!(a.load(), 3);
+
+        // Exercise the selected strategy and, on a separate cell, the locked fallback
+        let locked = AtomicU128::new(0);
+        a.store(0);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        let _ = a.fetch_update(|v| Some(v + (1 << 64) + 1));
+                        let mut v = 0;
+                        while let Err(e) = locked.locked_compare_exchange(v, v + (1 << 64) + 1) {
+                            v = e;
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(a.load(), (4000 << 64) + 4000);
+        assert_eq!(locked.load(), (4000 << 64) + 4000);
+    }
+
+    #[test]
+    fn shared() {
+        let shared = crate::Shared::<AtomicU128>::create_anon().unwrap();
+        assert_eq!(shared.load(), 0);
+        shared.store(u128::MAX);
+        assert_eq!(shared.swap(1), u128::MAX);
+    }
+}
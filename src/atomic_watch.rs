@@ -0,0 +1,66 @@
+use {
+    crate::ordering::{Acquire, Release},
+    core::sync::atomic::AtomicU32,
+};
+
+/// Futex based waiting on an arbitrary shared AtomicU32.
+#[derive(Clone, Copy)]
+pub struct AtomicWatch<'a>(&'a AtomicU32);
+
+impl<'a> AtomicWatch<'a> {
+    pub fn new(atomic: &'a AtomicU32) -> Self {
+        Self(atomic)
+    }
+
+    pub fn load(&self) -> u32 {
+        self.0.load(Acquire)
+    }
+
+    /// Blocks until the value satisfies `pred`, returning the matching value.
+    pub fn wait_until(&self, mut pred: impl FnMut(u32) -> bool) -> u32 {
+        loop {
+            let v = self.0.load(Acquire);
+            if pred(v) {
+                return v;
+            }
+            crate::futex::wait(self.0, v);
+        }
+    }
+
+    /// Blocks until the value differs from `seen`, returning the new value.
+    pub fn wait_for_change(&self, seen: u32) -> u32 {
+        self.wait_until(|v| v != seen)
+    }
+
+    /// Stores `value` and wakes all waiters.
+    pub fn store_and_wake(&self, value: u32) {
+        self.0.store(value, Release);
+        crate::futex::wake_all(self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{thread, time::Duration},
+    };
+
+    #[test]
+    fn atomic_watch() {
+        let a = AtomicU32::new(0);
+        let watch = AtomicWatch::new(&a);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                for v in 1..=3 {
+                    thread::sleep(Duration::from_millis(10));
+                    watch.store_and_wake(v);
+                }
+            });
+
+            assert_ne!(watch.wait_for_change(0), 0);
+            assert_eq!(watch.wait_until(|v| v == 3), 3);
+        });
+    }
+}
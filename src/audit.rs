@@ -0,0 +1,149 @@
+//! Process-local callbacks for auditing access to shared regions.
+//!
+//! The hook installed with [`set_hook`] is called whenever this process creates, opens or detaches
+//! from a region, and whenever it acquires or releases a Mutex/RwLock. Lock events name the region
+//! containing the lock, so deployments can log every access to sensitive regions.
+
+use std::{
+    cell::Cell,
+    ffi::CStr,
+    ops::Range,
+    sync::{Arc, Mutex, RwLock},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Access {
+    Create,
+    Open,
+    Detach,
+    Lock,
+    LockShared,
+    Unlock,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Event<'a> {
+    pub access: Access,
+    /// None for anonymous regions (and locks outside any region mapped by this crate)
+    pub region: Option<&'a CStr>,
+    /// The address of the mapping, or of the lock, in this process
+    pub addr: usize,
+}
+
+type Hook = Box<dyn Fn(&Event) + Send + Sync>;
+/// A region mapped by this process as (name, address range)
+type Mapping = (Option<Arc<CStr>>, Range<usize>);
+
+static HOOK: RwLock<Option<Hook>> = RwLock::new(None);
+static REGIONS: Mutex<Vec<Mapping>> = Mutex::new(Vec::new());
+
+thread_local! {
+    /// Set while the hook runs, so accesses made by the hook itself aren't reported.
+    static IN_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Installs the hook, replacing any previous one.
+pub fn set_hook(hook: impl Fn(&Event) + Send + Sync + 'static) {
+    *HOOK.write().unwrap_or_else(|e| e.into_inner()) = Some(Box::new(hook));
+}
+
+pub fn clear_hook() {
+    *HOOK.write().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+fn call(event: Event) {
+    if IN_HOOK.get() {
+        return;
+    }
+    let hook = HOOK.read().unwrap_or_else(|e| e.into_inner());
+    if let Some(hook) = &*hook {
+        IN_HOOK.set(true);
+        hook(&event);
+        IN_HOOK.set(false);
+    }
+}
+
+pub(crate) fn attached(name: Option<&CStr>, start: *const u8, len: usize, created: bool) {
+    let addr = start as usize;
+    REGIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push((name.map(Arc::from), addr..addr.saturating_add(len)));
+    let access = match created {
+        true => Access::Create,
+        false => Access::Open,
+    };
+    call(Event {
+        access,
+        region: name,
+        addr,
+    });
+}
+
+pub(crate) fn detaching(start: *const u8) {
+    let addr = start as usize;
+    let mut regions = REGIONS.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(index) = regions.iter().position(|(_, range)| range.start == addr) else {
+        return;
+    };
+    let (name, _) = regions.swap_remove(index);
+    drop(regions);
+    call(Event {
+        access: Access::Detach,
+        region: name.as_deref(),
+        addr,
+    });
+}
+
+pub(crate) fn lock<T: ?Sized>(lock: *const T, access: Access) {
+    let addr = lock.cast::<()>() as usize;
+    let name = REGIONS
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|(_, range)| range.contains(&addr))
+        .and_then(|(name, _)| name.clone());
+    call(Event {
+        access,
+        region: name.as_deref(),
+        addr,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Mutex as ShmMutex, Shareable, Shared},
+        std::ffi::CString,
+    };
+
+    #[derive(Default)]
+    struct Region {
+        mutex: ShmMutex<u32>,
+    }
+
+    unsafe impl Shareable for Region {}
+
+    #[test]
+    fn hook() {
+        let name = CString::new("/audit").unwrap();
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let events = log.clone();
+        set_hook(move |e| {
+            if e.region == Some(c"/audit") {
+                events.lock().unwrap().push(e.access);
+            }
+        });
+
+        let region: Shared<Region> = unsafe { Shared::create(&name).unwrap() };
+        *region.mutex.lock() += 1;
+        drop(region);
+        clear_hook();
+
+        assert_eq!(
+            *log.lock().unwrap(),
+            [Access::Create, Access::Lock, Access::Unlock, Access::Detach]
+        );
+    }
+}
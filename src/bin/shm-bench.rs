@@ -0,0 +1,296 @@
+//! Runs an inter-process scenario and prints latency percentiles and throughput as JSON.
+//!
+//! ```text
+//! shm-bench lock [--kind mutex|read|write] [--processes N] [--iterations N]
+//! shm-bench channel [--kind spsc|log] [--processes N] [--iterations N] [--size BYTES]
+//!                   [--wait futex|spin]
+//! ```
+//!
+//! Lock scenarios time acquiring the lock in each of `processes` processes. Channel scenarios time
+//! message delivery from `processes - 1` producers (exactly one for spsc) to a consumer.
+
+use {
+    shm::{monotonic_now, spsc::Ring, ByteLog, Event, Mutex, RwLock, Shareable, Shared},
+    std::{
+        process::exit,
+        sync::atomic::{AtomicU64, Ordering::Relaxed},
+        time::Instant,
+    },
+};
+
+const CAPACITY: usize = 1 << 16;
+
+/// Sub-buckets per power of two (a relative error of at most 1/8)
+const SUB_BITS: u32 = 3;
+
+/// A log-linear latency histogram in nanoseconds, shared by all processes.
+struct Histogram {
+    counts: [AtomicU64; 64 << SUB_BITS],
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            counts: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Histogram {
+    fn record(&self, ns: u64) {
+        let index = match ns {
+            0..8 => ns as usize,
+            _ => {
+                let exp = 63 - ns.leading_zeros();
+                let sub = (ns >> (exp - SUB_BITS)) & ((1 << SUB_BITS) - 1);
+                (((exp - SUB_BITS + 1) << SUB_BITS) as u64 + sub) as usize
+            }
+        };
+        self.counts[index].fetch_add(1, Relaxed);
+    }
+
+    /// The lower bound of the bucket at `index`.
+    fn value(index: usize) -> u64 {
+        match index {
+            0..8 => index as u64,
+            _ => {
+                let exp = (index >> SUB_BITS) as u32 + SUB_BITS - 1;
+                ((1 << SUB_BITS) + (index as u64 & ((1 << SUB_BITS) - 1))) << (exp - SUB_BITS)
+            }
+        }
+    }
+
+    fn total(&self) -> u64 {
+        self.counts.iter().map(|c| c.load(Relaxed)).sum()
+    }
+
+    /// The latency at or below which `q` of the samples fall.
+    fn quantile(&self, q: f64) -> u64 {
+        let rank = ((self.total() as f64 * q).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (index, count) in self.counts.iter().enumerate() {
+            seen += count.load(Relaxed);
+            if seen >= rank {
+                return Self::value(index);
+            }
+        }
+        0
+    }
+
+    fn json(&self) -> String {
+        let quantiles = [("min", 0.0), ("p50", 0.5), ("p90", 0.9), ("p99", 0.99)]
+            .into_iter()
+            .chain([("p999", 0.999), ("max", 1.0)]);
+        let fields: Vec<_> = quantiles
+            .map(|(name, q)| format!("\"{name}\":{}", self.quantile(q)))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+#[derive(Default)]
+struct Region {
+    start: Event,
+    mutex: Mutex<u64>,
+    rwlock: RwLock<u64>,
+    ring: Ring<CAPACITY>,
+    log: ByteLog<CAPACITY>,
+    latency: Histogram,
+}
+
+unsafe impl Shareable for Region {}
+
+struct Config {
+    scenario: String,
+    kind: String,
+    processes: usize,
+    iterations: u64,
+    size: usize,
+    spin: bool,
+}
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: shm-bench lock|channel [--kind KIND] [--processes N] [--iterations N] \
+         [--size BYTES] [--wait futex|spin]"
+    );
+    exit(2)
+}
+
+fn parse() -> Config {
+    let mut args = std::env::args().skip(1);
+    let scenario = args.next().unwrap_or_else(|| usage());
+    let mut config = Config {
+        kind: match scenario.as_str() {
+            "lock" => "mutex",
+            "channel" => "spsc",
+            _ => usage(),
+        }
+        .into(),
+        scenario,
+        processes: 2,
+        iterations: 100_000,
+        size: 64,
+        spin: false,
+    };
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage());
+        let number = || value.parse().unwrap_or_else(|_| usage());
+        match flag.as_str() {
+            "--kind" => config.kind = value.clone(),
+            "--processes" => config.processes = number(),
+            "--iterations" => config.iterations = number() as u64,
+            "--size" => config.size = number(),
+            "--wait" => config.spin = value == "spin",
+            _ => usage(),
+        }
+    }
+    config
+}
+
+/// Forks `n` processes running `f` (given the process index), returning their pids.
+fn spawn(n: usize, f: impl Fn(usize)) -> Vec<libc::pid_t> {
+    (0..n)
+        .map(|i| match unsafe { libc::fork() } {
+            0 => {
+                f(i);
+                unsafe { libc::_exit(0) }
+            }
+            pid if pid > 0 => pid,
+            _ => panic!("fork failed: {}", std::io::Error::last_os_error()),
+        })
+        .collect()
+}
+
+fn join(pids: Vec<libc::pid_t>) {
+    for pid in pids {
+        let mut status = 0;
+        unsafe { libc::waitpid(pid, &mut status, 0) };
+        assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+    }
+}
+
+fn now_ns() -> u64 {
+    monotonic_now().as_nanos() as u64
+}
+
+fn lock(region: &Region, config: &Config) -> u64 {
+    let acquire: fn(&Region) = match config.kind.as_str() {
+        "mutex" => |r| *r.mutex.lock() += 1,
+        "read" => |r| drop(r.rwlock.read()),
+        "write" => |r| *r.rwlock.write() += 1,
+        _ => usage(),
+    };
+    let pids = spawn(config.processes, |_| {
+        region.start.wait();
+        for _ in 0..config.iterations {
+            let start = now_ns();
+            acquire(region);
+            region.latency.record(now_ns() - start);
+        }
+    });
+    region.start.set();
+    join(pids);
+    config.iterations * config.processes as u64
+}
+
+fn channel(region: &Region, config: &Config) -> u64 {
+    let size = config.size.max(8);
+    let producers = config.processes.saturating_sub(1).max(1);
+    let messages = config.iterations * producers as u64;
+    let mut message = vec![0; size];
+
+    match config.kind.as_str() {
+        "spsc" => {
+            if producers != 1 {
+                usage();
+            }
+            let pids = spawn(1, |_| {
+                let mut producer = region.ring.producer().unwrap();
+                let mut message = vec![0; size];
+                region.start.wait();
+                for _ in 0..config.iterations {
+                    message[..8].copy_from_slice(&now_ns().to_ne_bytes());
+                    if config.spin {
+                        let mut bytes = &message[..];
+                        while !bytes.is_empty() {
+                            bytes = &bytes[producer.try_push(bytes)..];
+                        }
+                    } else {
+                        producer.push(&message);
+                    }
+                }
+            });
+            let mut consumer = region.ring.consumer().unwrap();
+            region.start.set();
+            for _ in 0..messages {
+                let mut read = 0;
+                while read < size {
+                    read += match config.spin {
+                        true => consumer.try_pop(&mut message[read..]),
+                        false => consumer.pop(&mut message[read..]),
+                    };
+                }
+                let sent = u64::from_ne_bytes(message[..8].try_into().unwrap());
+                region.latency.record(now_ns() - sent);
+            }
+            join(pids);
+        }
+        "log" => {
+            let pids = spawn(producers, |_| {
+                let mut message = vec![0; size];
+                region.start.wait();
+                for _ in 0..config.iterations {
+                    message[..8].copy_from_slice(&now_ns().to_ne_bytes());
+                    if config.spin {
+                        while region.log.try_append(&message).is_none() {}
+                    } else {
+                        region.log.append(&message);
+                    }
+                }
+            });
+            let mut collector = region.log.collector().unwrap();
+            region.start.set();
+            for _ in 0..messages {
+                let record = match config.spin {
+                    true => loop {
+                        if let Some(record) = collector.try_read() {
+                            break record;
+                        }
+                    },
+                    false => collector.read(),
+                };
+                let sent = u64::from_ne_bytes(record.data[..8].try_into().unwrap());
+                region.latency.record(now_ns() - sent);
+            }
+            join(pids);
+        }
+        _ => usage(),
+    }
+    messages
+}
+
+fn main() {
+    let config = parse();
+    let region: Shared<Region> = Shared::create_anon().expect("unable to create region");
+
+    let start = Instant::now();
+    let ops = match config.scenario.as_str() {
+        "lock" => lock(&region, &config),
+        _ => channel(&region, &config),
+    };
+    let seconds = start.elapsed().as_secs_f64();
+
+    println!(
+        "{{\"scenario\":\"{}\",\"kind\":\"{}\",\"processes\":{},\"iterations\":{},\"size\":{},\
+         \"wait\":\"{}\",\"ops\":{ops},\"seconds\":{seconds},\"ops_per_sec\":{},\"latency_ns\":{}}}",
+        config.scenario,
+        config.kind,
+        config.processes,
+        config.iterations,
+        config.size,
+        if config.spin { "spin" } else { "futex" },
+        ops as f64 / seconds,
+        region.latency.json(),
+    );
+}
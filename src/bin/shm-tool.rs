@@ -0,0 +1,262 @@
+//! Inspects and cleans up regions created by this crate (ex: leaked by crashed processes).
+//!
+//! ```text
+//! shm-tool list
+//! shm-tool show NAME
+//! shm-tool dump NAME [--offset BYTES] [--len BYTES]
+//! shm-tool unlink NAME...
+//! shm-tool clean [--dry-run]
+//! shm-tool trace NAME [--offset BYTES]
+//! ```
+//!
+//! Regions are recognized by their header, so regions created by other software are skipped.
+//! A region is stale once its creator has exited and no process remains attached, which `clean`
+//! unlinks. `unlink` removes regions regardless.
+//!
+//! With the `trace` feature, `trace` replays the lock events of the TraceRing at `--offset` into
+//! the payload: each lock's interleaving of critical sections, and any sections which held a lock
+//! at once.
+
+use {
+    shm::{inspect, Error, RegionInfo},
+    std::{ffi::CString, path::Path, process::exit, time::Duration},
+};
+
+/// Where the kernel exposes named regions
+const SHM_DIR: &str = "/dev/shm";
+
+fn usage() -> ! {
+    eprintln!(
+        "usage: shm-tool list\n       shm-tool show NAME\n       \
+         shm-tool dump NAME [--offset BYTES] [--len BYTES]\n       shm-tool unlink NAME...\n       \
+         shm-tool clean [--dry-run]\n       shm-tool trace NAME [--offset BYTES]"
+    );
+    exit(2)
+}
+
+/// The region name, with the leading slash expected by shm_open.
+fn region_name(name: &str) -> CString {
+    let name = format!("/{}", name.trim_start_matches('/'));
+    CString::new(name).unwrap_or_else(|_| usage())
+}
+
+/// The regions created by this crate.
+fn regions() -> Vec<(String, RegionInfo)> {
+    let entries = std::fs::read_dir(SHM_DIR).unwrap_or_else(|e| {
+        eprintln!("unable to list {SHM_DIR}: {e}");
+        exit(1)
+    });
+    let mut regions: Vec<_> = entries
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().into_string().ok()?;
+            let info = inspect(&region_name(&name)).ok()?;
+            Some((name, info))
+        })
+        .collect();
+    regions.sort_by(|a, b| a.0.cmp(&b.0));
+    regions
+}
+
+fn is_stale(info: &RegionInfo) -> bool {
+    info.attached == 0 && !info.creator.is_alive()
+}
+
+/// How long ago the region was created.
+fn age(info: &RegionInfo) -> Duration {
+    let mut now = unsafe { std::mem::zeroed() };
+    unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut now) };
+    let now = Duration::new(now.tv_sec as u64, now.tv_nsec as u32);
+    now.saturating_sub(info.creator.created_since_boot)
+}
+
+fn list() {
+    println!(
+        "{:<24} {:>10} {:>8} {:>8} {:>10}  STATE",
+        "NAME", "SIZE", "CREATOR", "ATTACHED", "AGE(s)"
+    );
+    for (name, info) in regions() {
+        println!(
+            "{name:<24} {:>10} {:>8} {:>8} {:>10}  {}",
+            info.size,
+            info.creator.pid,
+            info.attached,
+            age(&info).as_secs(),
+            if is_stale(&info) { "stale" } else { "live" },
+        );
+    }
+}
+
+fn show(name: &str) {
+    let info = inspect_or_exit(name);
+    println!("name:            {name}");
+    println!("size:            {} bytes", info.size);
+    println!("payload offset:  {}", info.payload_offset);
+    println!("generation:      {}", info.generation);
+    println!("age:             {:.1}s", age(&info).as_secs_f64());
+    println!(
+        "creator:         pid {} ({}), uid {}, shm {}",
+        info.creator.pid,
+        if info.creator.is_alive() {
+            "alive"
+        } else {
+            "exited"
+        },
+        info.creator.uid,
+        info.creator.crate_version,
+    );
+    println!("attached:        {}", info.attached);
+    println!("stale:           {}", is_stale(&info));
+}
+
+fn dump(name: &str, mut args: impl Iterator<Item = String>) {
+    let info = inspect_or_exit(name);
+    let (mut offset, mut len) = (0, info.size);
+    while let Some(flag) = args.next() {
+        let value = args
+            .next()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_else(|| usage());
+        match flag.as_str() {
+            "--offset" => offset = value,
+            "--len" => len = value,
+            _ => usage(),
+        }
+    }
+
+    let bytes = read(name);
+    let payload = &bytes[(info.payload_offset as usize).min(bytes.len())..];
+    let start = (offset as usize).min(payload.len());
+    let end = start.saturating_add(len as usize).min(payload.len());
+    for (i, line) in payload[start..end].chunks(16).enumerate() {
+        let hex: Vec<_> = line.iter().map(|b| format!("{b:02x}")).collect();
+        let ascii: String = line
+            .iter()
+            .map(|&b| match b {
+                0x20..0x7f => b as char,
+                _ => '.',
+            })
+            .collect();
+        println!("{:08x}  {:<47}  |{ascii}|", start + i * 16, hex.join(" "));
+    }
+}
+
+/// A snapshot of the region's contents.
+fn read(name: &str) -> Vec<u8> {
+    let path = Path::new(SHM_DIR).join(name.trim_start_matches('/'));
+    std::fs::read(&path).unwrap_or_else(|e| {
+        eprintln!("unable to read {}: {e}", path.display());
+        exit(1)
+    })
+}
+
+#[cfg(feature = "trace")]
+fn trace(name: &str, mut args: impl Iterator<Item = String>) {
+    use {shm::trace::TraceRing, std::mem::size_of};
+
+    let mut offset = 0;
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--offset" => {
+                offset = args
+                    .next()
+                    .and_then(|v| v.parse::<usize>().ok())
+                    .unwrap_or_else(|| usage())
+            }
+            _ => usage(),
+        }
+    }
+
+    let info = inspect_or_exit(name);
+    let bytes = read(name);
+    let start = info.payload_offset as usize + offset;
+    let Some(ring) = bytes.get(start..start + size_of::<TraceRing>()) else {
+        eprintln!("{name}: no TraceRing at offset {offset}");
+        exit(1)
+    };
+    // Copied to a buffer aligned for the ring's atomics
+    let mut buf = vec![0u64; size_of::<TraceRing>().div_ceil(8)];
+    unsafe { std::ptr::copy_nonoverlapping(ring.as_ptr(), buf.as_mut_ptr().cast(), ring.len()) };
+    // [SAFETY]: The buffer holds a snapshot of a ring, whose every bit pattern is valid.
+    let ring = unsafe { TraceRing::from_raw(buf.as_ptr().cast()) };
+
+    for (lock, events) in ring.by_lock() {
+        println!("lock {lock:+}");
+        for event in events {
+            println!("  {event}");
+        }
+    }
+    println!("sections:");
+    for section in ring.sections() {
+        println!("  {section}");
+    }
+    let overlaps = ring.overlaps();
+    println!("overlapping sections: {}", overlaps.len());
+    for (a, b) in overlaps {
+        println!("  {a}\n    {b}");
+    }
+    println!("dropped events: {}", ring.dropped());
+}
+
+fn unlink(names: &[String]) {
+    if names.is_empty() {
+        usage();
+    }
+    let mut failed = false;
+    for name in names {
+        if let Err(e) = shm::unlink(&region_name(name)) {
+            eprintln!("unable to unlink {name}: {e}");
+            failed = true;
+        }
+    }
+    if failed {
+        exit(1);
+    }
+}
+
+fn clean(dry_run: bool) {
+    for (name, info) in regions().into_iter().filter(|(_, info)| is_stale(info)) {
+        if dry_run {
+            println!("would unlink {name} ({} bytes)", info.size);
+        } else {
+            match shm::unlink(&region_name(&name)) {
+                Ok(()) => println!("unlinked {name} ({} bytes)", info.size),
+                Err(e) => eprintln!("unable to unlink {name}: {e}"),
+            }
+        }
+    }
+}
+
+fn inspect_or_exit(name: &str) -> RegionInfo {
+    inspect(&region_name(name)).unwrap_or_else(|e| {
+        match &e {
+            Error::Open(cause) => eprintln!("{name}: {e}: {cause}"),
+            _ => eprintln!("{name}: {e}"),
+        }
+        exit(1)
+    })
+}
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let command = args.next().unwrap_or_else(|| usage());
+    match command.as_str() {
+        "list" => list(),
+        "show" => show(&args.next().unwrap_or_else(|| usage())),
+        "dump" => {
+            let name = args.next().unwrap_or_else(|| usage());
+            dump(&name, args)
+        }
+        "unlink" => unlink(&args.collect::<Vec<_>>()),
+        #[cfg(feature = "trace")]
+        "trace" => {
+            let name = args.next().unwrap_or_else(|| usage());
+            trace(&name, args)
+        }
+        "clean" => match args.next().as_deref() {
+            None => clean(false),
+            Some("--dry-run") => clean(true),
+            Some(_) => usage(),
+        },
+        _ => usage(),
+    }
+}
@@ -0,0 +1,208 @@
+use {
+    crate::{
+        capabilities, ordering::Relaxed, page_size, Advice, Error, Lifecycle, NumaPolicy, Result,
+        Shareable, Shared, ShmFd, DEFAULT_MODE,
+    },
+    std::{
+        ffi::{CStr, CString},
+        io,
+        marker::PhantomData,
+        sync::atomic::AtomicU8,
+    },
+};
+
+/// Configures how a named region is created or opened (see [`Shared::builder`]).
+///
+/// Mapping options (NUMA policy, huge pages) are applied before the creator first touches the
+/// region's pages.
+pub struct SharedBuilder<T> {
+    name: CString,
+    mode: libc::mode_t,
+    group: Option<libc::gid_t>,
+    populate: bool,
+    huge_pages: bool,
+    lock_memory: bool,
+    numa_policy: Option<NumaPolicy>,
+    lifecycle: Option<Lifecycle>,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<T: Shareable> SharedBuilder<T> {
+    pub(crate) fn new(name: &CStr) -> Self {
+        Self {
+            name: name.into(),
+            mode: DEFAULT_MODE,
+            group: None,
+            populate: false,
+            huge_pages: false,
+            lock_memory: false,
+            numa_policy: None,
+            lifecycle: None,
+            _type: PhantomData,
+        }
+    }
+
+    /// The permission bits of a created region (not masked by the umask).
+    pub fn mode(mut self, mode: libc::mode_t) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// The group of a created region.
+    pub fn group(mut self, gid: libc::gid_t) -> Self {
+        self.group = Some(gid);
+        self
+    }
+
+    /// Faults in the whole mapping up front, so first accesses don't page fault.
+    pub fn populate(mut self, populate: bool) -> Self {
+        self.populate = populate;
+        self
+    }
+
+    /// Requests transparent huge pages for the mapping (see [`Advice::HugePage`]).
+    pub fn huge_pages(mut self, huge_pages: bool) -> Self {
+        self.huge_pages = huge_pages;
+        self
+    }
+
+    /// Locks the mapping in RAM (see [`Shared::lock_memory`]).
+    pub fn lock_memory(mut self, lock_memory: bool) -> Self {
+        self.lock_memory = lock_memory;
+        self
+    }
+
+    pub fn numa_policy(mut self, policy: NumaPolicy) -> Self {
+        self.numa_policy = Some(policy);
+        self
+    }
+
+    /// Keeps the region linked once the handle is dropped (see [`Lifecycle::Manual`]).
+    pub fn persist(self, persist: bool) -> Self {
+        match persist {
+            true => self.lifecycle(Lifecycle::Manual),
+            false => Self {
+                lifecycle: None,
+                ..self
+            },
+        }
+    }
+
+    /// Overrides the default lifecycle (see [`Lifecycle`]).
+    pub fn lifecycle(mut self, lifecycle: Lifecycle) -> Self {
+        self.lifecycle = Some(lifecycle);
+        self
+    }
+
+    /// # Safety
+    ///
+    /// See [`Shared::create`].
+    pub unsafe fn create(self) -> Result<Shared<T>> {
+        let fd = ShmFd::create_with_mode(&self.name, self.mode, self.group).map_err(Error::Open)?;
+        let shared = unsafe { Shared::init_with(fd, |shared| self.configure(shared)) }?;
+        self.finish(shared)
+    }
+
+    /// # Safety
+    ///
+    /// See [`Shared::open`].
+    pub unsafe fn open(self) -> Result<Shared<T>> {
+        let fd = ShmFd::open(&self.name).map_err(Error::Open)?;
+        let shared = unsafe { Shared::map(fd) }?;
+        self.configure(&shared)?;
+        self.finish(shared)
+    }
+
+    fn configure(&self, shared: &Shared<T>) -> Result<()> {
+        if let Some(policy) = self.numa_policy {
+            shared.set_numa_policy(policy).map_err(Error::Configure)?;
+        }
+        if self.huge_pages {
+            shared.advise(Advice::HugePage).map_err(Error::Configure)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&self, mut shared: Shared<T>) -> Result<Shared<T>> {
+        if self.populate && !cfg!(shm_heap) {
+            populate(shared.as_ptr(), shared.mapped_len())?;
+        }
+        if self.lock_memory {
+            shared.lock_memory()?;
+        }
+        if let Some(lifecycle) = self.lifecycle {
+            shared.set_lifecycle(lifecycle);
+        }
+        Ok(shared)
+    }
+}
+
+/// Faults in every page of the mapping for writing. Kernels before 5.14 don't support
+/// MADV_POPULATE_WRITE, so the pages are touched instead, with RMWs which leave their contents
+/// (possibly being written by other processes) unchanged.
+fn populate(ptr: *mut u8, len: usize) -> Result<()> {
+    if capabilities().populate_write {
+        if unsafe { libc::madvise(ptr.cast(), len, libc::MADV_POPULATE_WRITE) } != 0 {
+            return Err(Error::Configure(io::Error::last_os_error()));
+        }
+    } else {
+        touch_pages(ptr, len);
+    }
+    Ok(())
+}
+
+fn touch_pages(ptr: *mut u8, len: usize) {
+    for offset in (0..len).step_by(page_size()) {
+        // [SAFETY]: The offset is within the writable mapping.
+        let byte = unsafe { &*ptr.add(offset).cast::<AtomicU8>() };
+        byte.fetch_add(0, Relaxed);
+    }
+}
+
+// The test checks the permissions of the region in /dev/shm.
+#[cfg(all(test, not(shm_heap)))]
+mod tests {
+    use {
+        super::*,
+        crate::{exists, AtomicF64},
+        std::os::unix::fs::PermissionsExt,
+    };
+
+    #[test]
+    fn builder() {
+        let name = c"/builder";
+        let created: Shared<AtomicF64> = unsafe {
+            Shared::builder(name)
+                .mode(0o640)
+                .populate(true)
+                .persist(true)
+                .create()
+                .unwrap()
+        };
+        drop(created);
+
+        // Persisted past the creator
+        let opened: Shared<AtomicF64> = unsafe {
+            Shared::builder(name)
+                .lifecycle(Lifecycle::UnlinkOnDrop)
+                .open()
+                .unwrap()
+        };
+        let meta = std::fs::metadata("/dev/shm/builder").unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+        drop(opened);
+        assert!(!exists(name));
+    }
+
+    #[test]
+    fn populate() {
+        let shared = Shared::<AtomicF64>::create_anon().unwrap();
+        shared.store(1.5, Relaxed);
+        let (ptr, len) = (shared.as_ptr(), shared.mapped_len());
+        super::populate(ptr, len).unwrap();
+
+        // The fallback for kernels without MADV_POPULATE_WRITE preserves the contents
+        touch_pages(ptr, len);
+        assert_eq!(shared.load(Relaxed), 1.5);
+    }
+}
@@ -0,0 +1,435 @@
+use {
+    crate::{
+        gate::Gate,
+        ordering::{Acquire, Relaxed, Release},
+        spsc::claim,
+        Shareable,
+    },
+    core::{
+        cell::UnsafeCell,
+        sync::atomic::{AtomicU32, AtomicU64},
+        time::Duration,
+    },
+    std::time::Instant,
+};
+
+/// Records start on (and are padded to) this alignment. The header is length, producer, sequence
+/// and topic words, the length being written last to commit the record.
+const HEADER_LEN: usize = 16;
+
+/// Set in the length word of a committed record
+const COMMITTED: u32 = 1 << 31;
+
+#[repr(C, align(16))]
+struct Buf<const N: usize>([u8; N]);
+
+/// A multi-producer single-consumer log of variable-length records, of capacity N bytes which
+/// must be a power of two.
+///
+/// Producers reserve space for a record atomically and commit it once written. The collector
+/// reads records in reservation order, so it waits on a record which is reserved but not yet
+/// committed (a producer which dies in between stalls the log).
+///
+/// Records may be tagged with a bitmask of topics, and the collector filter on some, so it's only
+/// woken for those (futex bitset wakes). The log isn't a broadcast: the other records are
+/// discarded as the collector reads past them, not kept for other readers (which need logs of
+/// their own). It's still woken to drain the log once full, so filtered records can't block
+/// producers.
+///
+/// The log may be closed to new records, and drained of those being appended (see
+/// [`ByteLog::drain`]).
+pub struct ByteLog<const N: usize> {
+    /// Total bytes reserved (wrapping, low half) and records reserved (high half)
+    reserved: AtomicU64,
+    /// Total bytes read (wrapping; also the futex waited on by producers)
+    tail: AtomicU32,
+    /// Incremented whenever a record is committed (the futex waited on by the collector)
+    committed: AtomicU32,
+    /// The id (see [`crate::owner`]) of the process collecting, or 0
+    collector: AtomicU32,
+    /// Counts the appends in progress
+    appenders: Gate,
+    buf: UnsafeCell<Buf<N>>,
+}
+
+unsafe impl<const N: usize> Sync for ByteLog<N> {}
+
+unsafe impl<const N: usize> Shareable for ByteLog<N> {}
+
+impl<const N: usize> Default for ByteLog<N> {
+    fn default() -> Self {
+        const {
+            assert!(
+                N.is_power_of_two() && N >= 2 * HEADER_LEN && N <= 1 << 31,
+                "capacity must be a power of two between 32 and 2^31"
+            )
+        };
+        Self {
+            reserved: AtomicU64::new(0),
+            tail: AtomicU32::new(0),
+            committed: AtomicU32::new(0),
+            collector: AtomicU32::new(0),
+            appenders: Gate::new(),
+            buf: UnsafeCell::new(Buf([0; N])),
+        }
+    }
+}
+
+/// A record read from a [`ByteLog`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Record {
+    /// The pid of the appending process
+    pub producer: u32,
+    /// The position of the record in the log (wrapping)
+    pub seq: u32,
+    /// The bitmask of topics the record was appended with
+    pub topics: u32,
+    pub data: Vec<u8>,
+}
+
+impl<const N: usize> ByteLog<N> {
+    /// The largest record which can be appended.
+    pub const MAX_RECORD_LEN: usize = N - HEADER_LEN;
+
+    /// The topics of untagged records, which pass any filter
+    pub const ALL_TOPICS: u32 = u32::MAX;
+
+    /// Appends a record without blocking, returning its sequence number or None if the log is
+    /// full or closed (or the record exceeds [`Self::MAX_RECORD_LEN`]).
+    pub fn try_append(&self, data: &[u8]) -> Option<u32> {
+        self.try_append_tagged(data, Self::ALL_TOPICS)
+    }
+
+    /// Like [`Self::try_append`], tagging the record with a nonzero bitmask of `topics`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `topics` is 0.
+    pub fn try_append_tagged(&self, data: &[u8], topics: u32) -> Option<u32> {
+        assert_ne!(topics, 0, "records need at least one topic");
+        if data.len() > Self::MAX_RECORD_LEN {
+            return None;
+        }
+        let _appending = self.appenders.enter()?;
+        self.append_entered(data, topics)
+    }
+
+    /// Appends without blocking once counted as in progress, returning None if the log is full.
+    fn append_entered(&self, data: &[u8], topics: u32) -> Option<u32> {
+        let size = (HEADER_LEN + data.len()).next_multiple_of(HEADER_LEN);
+
+        let mut current = self.reserved.load(Relaxed);
+        let (pos, seq) = loop {
+            let (pos, seq) = (current as u32, (current >> 32) as u32);
+            if pos.wrapping_sub(self.tail.load(Acquire)) as usize + size > N {
+                // The collector may be asleep, filtering out every record filling the log
+                crate::futex::wake_all(&self.committed);
+                return None;
+            }
+            let next =
+                u64::from(seq.wrapping_add(1)) << 32 | u64::from(pos.wrapping_add(size as u32));
+            match self
+                .reserved
+                .compare_exchange_weak(current, next, Acquire, Relaxed)
+            {
+                Ok(_) => break (pos, seq),
+                Err(actual) => current = actual,
+            }
+        };
+
+        self.word(pos.wrapping_add(4))
+            .store(std::process::id(), Relaxed);
+        self.word(pos.wrapping_add(8)).store(seq, Relaxed);
+        self.word(pos.wrapping_add(12)).store(topics, Relaxed);
+        self.copy(
+            pos.wrapping_add(HEADER_LEN as u32),
+            data.as_ptr().cast_mut(),
+            data.len(),
+            true,
+        );
+        self.word(pos).store(COMMITTED | data.len() as u32, Release);
+
+        self.committed.fetch_add(1, Release);
+        crate::futex::wake_bitset(&self.committed, topics);
+        Some(seq)
+    }
+
+    /// Appends a record, blocking while the log is full, and returns its sequence number or None
+    /// if the log is closed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the record exceeds [`Self::MAX_RECORD_LEN`].
+    pub fn append(&self, data: &[u8]) -> Option<u32> {
+        self.append_tagged(data, Self::ALL_TOPICS)
+    }
+
+    /// Like [`Self::append`], tagging the record with a nonzero bitmask of `topics`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the record exceeds [`Self::MAX_RECORD_LEN`] or `topics` is 0.
+    pub fn append_tagged(&self, data: &[u8], topics: u32) -> Option<u32> {
+        assert!(
+            data.len() <= Self::MAX_RECORD_LEN,
+            "record exceeds capacity"
+        );
+        assert_ne!(topics, 0, "records need at least one topic");
+        let _appending = self.appenders.enter()?;
+        loop {
+            let tail = self.tail.load(Acquire);
+            match self.append_entered(data, topics) {
+                Some(seq) => return Some(seq),
+                None => crate::futex::wait(&self.tail, tail),
+            }
+        }
+    }
+
+    /// Closes the log to new records. Records already appended may still be read.
+    pub fn close(&self) {
+        self.appenders.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.appenders.is_closed()
+    }
+
+    /// Closes the log and waits until the appends in progress have committed, so only the
+    /// collector changes it (ex: while the region is migrated). Producers blocked on a full log
+    /// finish once the collector makes room.
+    pub fn drain(&self) {
+        self.appenders.drain();
+    }
+
+    /// Claims the collecting end, returning None if another live process holds it.
+    pub fn collector(&self) -> Option<Collector<'_, N>> {
+        claim(&self.collector).then(|| Collector {
+            log: self,
+            topics: Self::ALL_TOPICS,
+        })
+    }
+
+    /// The length word of the record at `pos`, or another header word at an offset from it.
+    fn word(&self, pos: u32) -> &AtomicU32 {
+        let at = pos as usize & (N - 1);
+        // [SAFETY]: Header words are 4-byte aligned within the 16-byte aligned buffer, and never
+        // wrap since records start on a 16 byte boundary.
+        unsafe { &*self.buf.get().cast::<u8>().add(at).cast::<AtomicU32>() }
+    }
+
+    /// Copies `len` bytes at log position `pos` (wrapping) to or from `bytes`.
+    fn copy(&self, pos: u32, bytes: *mut u8, len: usize, write: bool) {
+        let start = pos as usize & (N - 1);
+        let first = len.min(N - start);
+        let buf = self.buf.get().cast::<u8>();
+        for (offset, at, n) in [(0, start, first), (first, 0, len - first)] {
+            // [SAFETY]: A record is only accessed by the producer which reserved it until it is
+            // committed, and then only by the collector.
+            unsafe {
+                let (src, dst) = match write {
+                    true => (bytes.add(offset), buf.add(at)),
+                    false => (buf.add(at), bytes.add(offset)),
+                };
+                core::ptr::copy_nonoverlapping(src, dst, n);
+            }
+        }
+    }
+}
+
+/// The reading end of a [`ByteLog`], released when dropped.
+pub struct Collector<'a, const N: usize> {
+    log: &'a ByteLog<N>,
+    /// The bitmask of topics read, initially [`ByteLog::ALL_TOPICS`]
+    topics: u32,
+}
+
+impl<const N: usize> Collector<'_, N> {
+    /// Reads only records tagged with one of `topics` (a nonzero bitmask), discarding the others
+    /// from the log.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `topics` is 0.
+    pub fn filter(&mut self, topics: u32) {
+        assert_ne!(topics, 0, "filters need at least one topic");
+        self.topics = topics;
+    }
+
+    /// Reads the next record passing the filter unless it has yet to be committed.
+    pub fn try_read(&mut self) -> Option<Record> {
+        loop {
+            let record = self.try_read_any()?;
+            if record.topics & self.topics != 0 {
+                return Some(record);
+            }
+        }
+    }
+
+    fn try_read_any(&mut self) -> Option<Record> {
+        let log = self.log;
+        let tail = log.tail.load(Relaxed);
+        let header = log.word(tail).load(Acquire);
+        if header & COMMITTED == 0 {
+            return None;
+        }
+
+        let len = (header & !COMMITTED) as usize;
+        let mut data = vec![0; len];
+        log.copy(
+            tail.wrapping_add(HEADER_LEN as u32),
+            data.as_mut_ptr(),
+            len,
+            false,
+        );
+        let record = Record {
+            producer: log.word(tail.wrapping_add(4)).load(Relaxed),
+            seq: log.word(tail.wrapping_add(8)).load(Relaxed),
+            topics: log.word(tail.wrapping_add(12)).load(Relaxed),
+            data,
+        };
+
+        // Any position in the record may hold a header once reused.
+        let size = (HEADER_LEN + len).next_multiple_of(HEADER_LEN) as u32;
+        for offset in (0..size).step_by(HEADER_LEN) {
+            log.word(tail.wrapping_add(offset)).store(0, Relaxed);
+        }
+        log.tail.store(tail.wrapping_add(size), Release);
+        crate::futex::wake_all(&log.tail);
+        Some(record)
+    }
+
+    /// Reads the next record passing the filter, blocking until one is committed.
+    pub fn read(&mut self) -> Record {
+        loop {
+            let committed = self.log.committed.load(Acquire);
+            match self.try_read() {
+                Some(record) => return record,
+                None => crate::futex::wait_bitset(&self.log.committed, committed, self.topics),
+            }
+        }
+    }
+
+    /// Reads the next record passing the filter, blocking until one is committed or `timeout`
+    /// elapses.
+    pub fn read_timeout(&mut self, timeout: Duration) -> Option<Record> {
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let committed = self.log.committed.load(Acquire);
+            if let Some(record) = self.try_read() {
+                return Some(record);
+            }
+            if !crate::futex::wait_bitset_until(
+                &self.log.committed,
+                committed,
+                self.topics,
+                deadline,
+            ) {
+                return self.try_read();
+            }
+        }
+    }
+}
+
+impl<const N: usize> Drop for Collector<'_, N> {
+    fn drop(&mut self) {
+        self.log.collector.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn records() {
+        let log = ByteLog::<64>::default();
+        let mut collector = log.collector().unwrap();
+        assert!(log.collector().is_none());
+        assert_eq!(collector.try_read(), None);
+
+        assert_eq!(log.try_append(b"first"), Some(0));
+        assert_eq!(log.try_append(&[7; 10]), Some(1));
+        assert_eq!(log.try_append(b"full"), None);
+        assert_eq!(
+            log.try_append(&[0; ByteLog::<64>::MAX_RECORD_LEN + 1]),
+            None
+        );
+
+        let record = collector.try_read().unwrap();
+        assert_eq!((record.producer, record.seq), (std::process::id(), 0));
+        assert_eq!(record.topics, ByteLog::<64>::ALL_TOPICS);
+        assert_eq!(record.data, b"first");
+        assert_eq!(collector.read().data, [7; 10]);
+
+        // Records from concurrent producers, wrapping the buffer
+        thread::scope(|s| {
+            for producer in 0..4u8 {
+                let log = &log;
+                s.spawn(move || {
+                    for i in 0..100 {
+                        log.append(&[producer; 3][..i % 4]);
+                    }
+                });
+            }
+            let mut counts = [0; 4];
+            for _ in 0..400 {
+                let data = collector.read().data;
+                if let Some(&producer) = data.first() {
+                    assert!(data.iter().all(|&b| b == producer));
+                    counts[usize::from(producer)] += 1;
+                }
+            }
+            assert_eq!(counts, [75; 4]);
+        });
+    }
+
+    #[test]
+    fn topics() {
+        const PRICES: u32 = 1 << 0;
+        const ORDERS: u32 = 1 << 1;
+
+        let log = ByteLog::<64>::default();
+        let mut collector = log.collector().unwrap();
+        collector.filter(ORDERS);
+
+        log.append_tagged(b"price", PRICES);
+        assert_eq!(collector.try_read(), None);
+        log.append_tagged(b"order", ORDERS | PRICES);
+        let record = collector.try_read().unwrap();
+        assert_eq!((record.seq, record.topics), (1, ORDERS | PRICES));
+
+        thread::scope(|s| {
+            let reader = s.spawn(|| collector.read().data);
+            // Filtered records which fill the log wake the collector to drain it
+            for _ in 0..20 {
+                log.append_tagged(b"price", PRICES);
+            }
+            log.append_tagged(b"order", ORDERS);
+            assert_eq!(reader.join().unwrap(), b"order");
+        });
+    }
+
+    #[test]
+    fn drain() {
+        let log = ByteLog::<32>::default();
+        let mut collector = log.collector().unwrap();
+        assert_eq!(log.append(b"first"), Some(0));
+
+        thread::scope(|s| {
+            // Blocked on the full log when it's drained
+            let appending = s.spawn(|| log.append(b"second"));
+            thread::sleep(Duration::from_millis(20));
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                assert_eq!(collector.read().data, b"first");
+            });
+            log.drain();
+            assert_eq!(appending.join().unwrap(), Some(1));
+        });
+        assert!(log.is_closed());
+        assert_eq!(log.try_append(b"third"), None);
+        assert_eq!(log.append(b"fourth"), None);
+        assert_eq!(collector.try_read().unwrap().data, b"second");
+        assert_eq!(collector.try_read(), None);
+    }
+}
@@ -0,0 +1,255 @@
+use {
+    crate::{
+        ordering::{AcqRel, Acquire, Relaxed},
+        Shareable, ShmSlab,
+    },
+    core::{sync::atomic::AtomicU32, time::Duration},
+    std::time::Instant,
+};
+
+const ACTIVE: u32 = 1;
+const CANCELLED: u32 = 2;
+
+const NO_PARENT: u32 = u32::MAX;
+
+#[derive(Default)]
+struct Node {
+    /// ACTIVE or CANCELLED (also the futex waited on)
+    state: AtomicU32,
+    parent: AtomicU32,
+    /// The handles to the token and its children, which keep the node allocated
+    refs: AtomicU32,
+}
+
+/// Fixed capacity storage for a hierarchy of cross-process cancellation tokens.
+///
+/// A node is freed once every handle to its token and its children have been dropped, so at most
+/// `N` tokens exist at once. Handles held by a process which exits are never dropped, leaking
+/// their nodes.
+pub struct CancellationTree<const N: usize> {
+    nodes: ShmSlab<Node, N>,
+}
+
+unsafe impl<const N: usize> Shareable for CancellationTree<N> {}
+
+impl<const N: usize> Default for CancellationTree<N> {
+    fn default() -> Self {
+        Self {
+            nodes: ShmSlab::default(),
+        }
+    }
+}
+
+impl<const N: usize> CancellationTree<N> {
+    /// Creates a new root token, or None if the tree is full.
+    pub fn new_token(&self) -> Option<CancellationToken<'_, N>> {
+        self.allocate(NO_PARENT)
+    }
+
+    /// Attaches to a token created by another process, which must still hold a handle to it (ids
+    /// are reused once every handle has been dropped).
+    pub fn token(&self, id: u32) -> Option<CancellationToken<'_, N>> {
+        let node = self.nodes.get(id as usize)?;
+        node.refs
+            .fetch_update(Relaxed, Relaxed, |refs| (refs > 0).then(|| refs + 1))
+            .ok()?;
+        Some(CancellationToken { tree: self, id })
+    }
+
+    fn allocate(&self, parent: u32) -> Option<CancellationToken<'_, N>> {
+        let id = self.nodes.allocate()? as u32;
+        let node = self.node(id);
+        node.parent.store(parent, Relaxed);
+        node.refs.store(1, Relaxed);
+        if parent != NO_PARENT {
+            // The child keeps its ancestors allocated, so their ids aren't reused
+            self.node(parent).refs.fetch_add(1, Relaxed);
+        }
+        node.state.swap(ACTIVE, AcqRel);
+
+        let token = CancellationToken { tree: self, id };
+        // A concurrent cancellation of an ancestor may have missed this node while it was
+        // allocated. Reading the ancestors with RMWs, as cancel() writes them, ensures either
+        // this sees the cancellation or the cancellation sees this node.
+        if self
+            .ancestors(id)
+            .any(|a| self.node(a).state.fetch_add(0, AcqRel) == CANCELLED)
+        {
+            token.cancel();
+        }
+        Some(token)
+    }
+
+    /// Drops a handle to the token `id`, freeing its node (and then its ancestors') once unused.
+    fn release(&self, mut id: u32) {
+        loop {
+            let node = self.node(id);
+            if node.refs.fetch_sub(1, AcqRel) != 1 {
+                return;
+            }
+            let parent = node.parent.swap(NO_PARENT, Relaxed);
+            node.state.store(0, Relaxed);
+            self.nodes.free(id as usize);
+            match parent {
+                NO_PARENT => return,
+                parent => id = parent,
+            }
+        }
+    }
+
+    fn node(&self, id: u32) -> &Node {
+        // Handles keep their node (and its ancestors) allocated
+        self.nodes.get(id as usize).unwrap()
+    }
+
+    fn ancestors(&self, id: u32) -> impl Iterator<Item = u32> + '_ {
+        core::iter::successors(Some(id), |&id| {
+            match self.nodes.get(id as usize)?.parent.load(Relaxed) {
+                NO_PARENT => None,
+                parent => Some(parent),
+            }
+        })
+        .skip(1)
+    }
+
+    fn is_cancelled(&self, id: u32) -> bool {
+        self.node(id).state.load(Acquire) == CANCELLED
+    }
+}
+
+/// A handle to a node of a [`CancellationTree`], mirroring tokio_util's CancellationToken.
+///
+/// The node is freed once every handle to it (and its children) has been dropped.
+pub struct CancellationToken<'a, const N: usize> {
+    tree: &'a CancellationTree<N>,
+    id: u32,
+}
+
+impl<const N: usize> Clone for CancellationToken<'_, N> {
+    fn clone(&self) -> Self {
+        self.tree.node(self.id).refs.fetch_add(1, Relaxed);
+        Self {
+            tree: self.tree,
+            id: self.id,
+        }
+    }
+}
+
+impl<const N: usize> Drop for CancellationToken<'_, N> {
+    fn drop(&mut self) {
+        self.tree.release(self.id);
+    }
+}
+
+impl<'a, const N: usize> CancellationToken<'a, N> {
+    /// Identifies the token to other processes (see [`CancellationTree::token`]).
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    /// Creates a token which is cancelled along with this one, or None if the tree is full.
+    pub fn child_token(&self) -> Option<CancellationToken<'a, N>> {
+        self.tree.allocate(self.id)
+    }
+
+    /// Cancels this token and all of its descendants.
+    pub fn cancel(&self) {
+        let tree = self.tree;
+        for (id, node) in tree.nodes.iter() {
+            let id = id as u32;
+            if (id == self.id || tree.ancestors(id).any(|a| a == self.id))
+                && node
+                    .state
+                    .compare_exchange(ACTIVE, CANCELLED, AcqRel, Relaxed)
+                    .is_ok()
+            {
+                crate::futex::wake_all(&node.state);
+            }
+        }
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.tree.is_cancelled(self.id)
+    }
+
+    /// Blocks until the token is cancelled.
+    pub fn wait(&self) {
+        let state = &self.tree.node(self.id).state;
+        while state.load(Acquire) != CANCELLED {
+            crate::futex::wait(state, ACTIVE);
+        }
+    }
+
+    /// Blocks until the token is cancelled or the timeout expires, returning false on timeout.
+    pub fn wait_timeout(&self, dur: Duration) -> bool {
+        let state = &self.tree.node(self.id).state;
+        let deadline = Instant::now() + dur;
+        while state.load(Acquire) != CANCELLED {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return false;
+            }
+            crate::futex::wait_timeout(state, ACTIVE, Some(remaining));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn cancellation_hierarchy() {
+        let tree = CancellationTree::<4>::default();
+
+        let root = tree.new_token().unwrap();
+        let stage = root.child_token().unwrap();
+        let sub = stage.child_token().unwrap();
+        let other = root.child_token().unwrap();
+        assert!(root.child_token().is_none());
+
+        // Cancelling a subtree leaves the rest untouched
+        stage.cancel();
+        assert!(stage.is_cancelled() && sub.is_cancelled());
+        assert!(!root.is_cancelled() && !other.is_cancelled());
+        assert!(!other.wait_timeout(Duration::from_millis(10)));
+
+        thread::scope(|s| {
+            s.spawn(|| tree.token(other.id()).unwrap().wait());
+            thread::sleep(Duration::from_millis(10));
+            root.cancel();
+        });
+        assert!(other.is_cancelled());
+    }
+
+    #[test]
+    fn nodes_freed() {
+        let tree = CancellationTree::<2>::default();
+
+        // Far more tokens than the capacity, as each is freed when dropped
+        for _ in 0..10 {
+            let root = tree.new_token().unwrap();
+            let child = root.child_token().unwrap();
+            assert!(tree.new_token().is_none());
+            drop((root, child));
+        }
+
+        // A child keeps its parent allocated, and attached handles keep the token
+        let root = tree.new_token().unwrap();
+        let child = root.child_token().unwrap();
+        let (root_id, child_id) = (root.id(), child.id());
+        drop(root);
+        let attached = tree.token(child_id).unwrap();
+        drop(child);
+        assert!(tree.new_token().is_none());
+        tree.token(root_id).unwrap().cancel();
+        assert!(attached.is_cancelled());
+
+        drop(attached);
+        assert!(tree.token(child_id).is_none());
+        assert!(tree.token(root_id).is_none());
+        let reused = tree.new_token().unwrap();
+        assert!(!reused.is_cancelled());
+    }
+}
@@ -0,0 +1,61 @@
+use std::{
+    os::fd::{AsRawFd, FromRawFd, OwnedFd},
+    sync::OnceLock,
+};
+
+/// Optional kernel features detected at runtime.
+///
+/// The crate only requires the baseline futex and POSIX shared memory interfaces. Optional
+/// features are used only where reported as available and otherwise fall back to the baseline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// memfd_create(2), backing anonymous regions (else an unlinked POSIX region)
+    pub memfd: bool,
+    /// File sealing of memfds, fixing the size of anonymous regions once created
+    pub memfd_seals: bool,
+    /// madvise(MADV_POPULATE_WRITE), Linux 5.14, populating regions (else pages are touched)
+    pub populate_write: bool,
+}
+
+/// Returns the capabilities of the running kernel, detected on first use.
+pub fn capabilities() -> Capabilities {
+    static CAPABILITIES: OnceLock<Capabilities> = OnceLock::new();
+    *CAPABILITIES.get_or_init(detect)
+}
+
+fn detect() -> Capabilities {
+    let (memfd, memfd_seals) = detect_memfd();
+    Capabilities {
+        memfd,
+        memfd_seals,
+        populate_write: advice_exists(libc::MADV_POPULATE_WRITE),
+    }
+}
+
+/// Probes madvise with an empty range, which fails with EINVAL only when the kernel doesn't
+/// recognize the advice (it's validated before the range).
+fn advice_exists(advice: libc::c_int) -> bool {
+    unsafe { libc::madvise(core::ptr::null_mut(), 0, advice) == 0 }
+}
+
+fn detect_memfd() -> (bool, bool) {
+    let fd = unsafe { libc::memfd_create(c"shm-probe".as_ptr(), libc::MFD_ALLOW_SEALING) };
+    if fd < 0 {
+        return (false, false);
+    }
+    let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+    let seals = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_ADD_SEALS, libc::F_SEAL_GROW) } == 0;
+    (true, seals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detection_is_stable() {
+        let caps = capabilities();
+        assert_eq!(caps, capabilities());
+        assert!(!caps.memfd_seals || caps.memfd);
+    }
+}
@@ -0,0 +1,154 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::{ops::Deref, sync::atomic::AtomicU32},
+};
+
+struct Slot<T> {
+    /// 0: free, otherwise the id of the claiming process (see [`crate::owner`])
+    owner: AtomicU32,
+    state: T,
+}
+
+/// A fixed table of per-client state where each attaching process claims a slot.
+///
+/// Slots held by processes which have exited are reclaimed by [`ClientSlots::claim`] and
+/// [`ClientSlots::reclaim_dead`]. A process's liveness is a lock it holds on the region rather
+/// than its pid, so clients in other pid namespaces aren't mistaken for dead ones. The state is
+/// not reset when a slot changes hands, so the new owner should reinitialize it.
+pub struct ClientSlots<T, const N: usize> {
+    slots: [Slot<T>; N],
+}
+
+unsafe impl<T: Shareable, const N: usize> Shareable for ClientSlots<T, N> {}
+
+impl<T: Default, const N: usize> Default for ClientSlots<T, N> {
+    fn default() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| Slot {
+                owner: AtomicU32::new(0),
+                state: T::default(),
+            }),
+        }
+    }
+}
+
+impl<T, const N: usize> ClientSlots<T, N> {
+    /// Claims a free slot for this process, reclaiming one from a dead process if necessary.
+    pub fn claim(&self) -> Option<ClientSlot<'_, T>> {
+        let id = crate::owner::id(self);
+        self.claim_where(id, |owner| owner == 0).or_else(|| {
+            self.claim_where(id, |owner| owner != 0 && !crate::owner::alive(self, owner))
+        })
+    }
+
+    /// Releases slots held by processes which have exited, returning how many were freed.
+    pub fn reclaim_dead(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| {
+                let owner = slot.owner.load(Relaxed);
+                owner != 0
+                    && !crate::owner::alive(self, owner)
+                    && slot
+                        .owner
+                        .compare_exchange(owner, 0, Release, Relaxed)
+                        .is_ok()
+            })
+            .count()
+    }
+
+    /// Iterates over the claimed slots as (index, owner, state), where the owner is an id the
+    /// claiming process took in the region (not its pid).
+    pub fn iter(&self) -> impl Iterator<Item = (usize, u32, &T)> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| match slot.owner.load(Acquire) {
+                0 => None,
+                owner => Some((i, owner, &slot.state)),
+            })
+    }
+
+    fn claim_where(&self, id: u32, pred: impl Fn(u32) -> bool) -> Option<ClientSlot<'_, T>> {
+        self.slots.iter().enumerate().find_map(|(index, slot)| {
+            let owner = slot.owner.load(Relaxed);
+            (pred(owner)
+                && slot
+                    .owner
+                    .compare_exchange(owner, id, Acquire, Relaxed)
+                    .is_ok())
+            .then(|| ClientSlot { slot, index })
+        })
+    }
+}
+
+/// A claimed slot, released when dropped.
+#[must_use = "if unused the slot will immediately be released"]
+pub struct ClientSlot<'a, T> {
+    slot: &'a Slot<T>,
+    index: usize,
+}
+
+impl<T> ClientSlot<'_, T> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T> Deref for ClientSlot<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.slot.state
+    }
+}
+
+impl<T> Drop for ClientSlot<'_, T> {
+    fn drop(&mut self) {
+        self.slot.owner.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{AtomicF64, Shared},
+    };
+
+    #[test]
+    fn claim_release_reclaim() {
+        let slots = Shared::<ClientSlots<AtomicF64, 2>>::create_anon().unwrap();
+
+        let a = slots.claim().unwrap();
+        a.store(7.0, Relaxed);
+        let b = slots.claim().unwrap();
+        assert_ne!(a.index(), b.index());
+        assert!(slots.claim().is_none());
+
+        let id = crate::owner::id(&*slots);
+        assert_eq!(
+            slots
+                .iter()
+                .map(|(i, owner, s)| (i, owner, s.load(Relaxed)))
+                .collect::<Vec<_>>(),
+            [(0, id, 7.0), (1, id, 0.0)]
+        );
+
+        drop(b);
+        assert_eq!(slots.iter().count(), 1);
+
+        // Simulate a crashed client holding the remaining slot (no process holds its id's lock)
+        let dead = i32::MAX as u32;
+        slots.slots[1].owner.store(dead, Relaxed);
+        assert_eq!(slots.reclaim_dead(), 1);
+
+        slots.slots[1].owner.store(dead, Relaxed);
+        let c = slots.claim().unwrap();
+        assert_eq!(c.index(), 1);
+        drop(a);
+    }
+}
@@ -0,0 +1,166 @@
+use {
+    crate::{
+        monotonic_now,
+        mutex::MutexGuard,
+        ordering::{Acquire, Relaxed, Release},
+    },
+    core::{
+        sync::atomic::{AtomicU32, AtomicU64},
+        time::Duration,
+    },
+};
+
+/// The window used by [`CoalescingCondvar::default`]
+const DEFAULT_WINDOW: Duration = Duration::from_millis(1);
+
+/// A condition variable which coalesces rapid notifications, so a high-frequency producer wakes
+/// waiters at most about once per window rather than on every notify. Suited to consumers which
+/// only need the latest state.
+///
+/// The first notification after a quiet window wakes waiters immediately. Later ones within the
+/// window are deferred to its end, when a waiter delivers them.
+pub struct CoalescingCondvar {
+    /// Incremented by each delivered notification
+    seq: AtomicU32,
+    /// The futex word, also incremented to rearm sleeping waiters
+    counter: AtomicU32,
+    num_waiters: AtomicU32,
+    /// 1 while a notification is deferred
+    pending: AtomicU32,
+    window_ns: AtomicU64,
+    /// When waiters were last woken, on the CLOCK_MONOTONIC timeline
+    last_wake_ns: AtomicU64,
+}
+
+impl Default for CoalescingCondvar {
+    fn default() -> Self {
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl CoalescingCondvar {
+    pub const fn new(window: Duration) -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            counter: AtomicU32::new(0),
+            num_waiters: AtomicU32::new(0),
+            pending: AtomicU32::new(0),
+            window_ns: AtomicU64::new(window.as_nanos() as u64),
+            last_wake_ns: AtomicU64::new(0),
+        }
+    }
+
+    pub fn set_window(&self, window: Duration) {
+        self.window_ns.store(window.as_nanos() as u64, Relaxed);
+    }
+
+    pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.num_waiters.fetch_add(1, Relaxed);
+        let seq = self.seq.load(Relaxed);
+
+        let mutex = guard.mutex;
+        drop(guard);
+
+        while self.seq.load(Relaxed) == seq {
+            let counter_value = self.counter.load(Acquire);
+            // Sleeps only until the end of the window while a notification is deferred.
+            let timeout = match self.pending.load(Relaxed) {
+                0 => None,
+                _ => match self.window_end().checked_sub(now_ns()) {
+                    Some(ns) if ns > 0 => Some(Duration::from_nanos(ns)),
+                    _ => {
+                        self.flush();
+                        continue;
+                    }
+                },
+            };
+            crate::futex::wait_timeout(&self.counter, counter_value, timeout);
+        }
+        self.num_waiters.fetch_sub(1, Relaxed);
+
+        mutex.lock()
+    }
+
+    pub fn notify_all(&self) {
+        if self.num_waiters.load(Relaxed) == 0 {
+            return;
+        }
+        let last = self.last_wake_ns.load(Relaxed);
+        let now = now_ns();
+        if now >= self.window_end_from(last)
+            && self
+                .last_wake_ns
+                .compare_exchange(last, now, Relaxed, Relaxed)
+                .is_ok()
+        {
+            self.pending.store(0, Relaxed);
+            self.wake();
+        } else if self.pending.swap(1, Relaxed) == 0 {
+            // Waiters already asleep rearm with a timeout to deliver the deferred notification.
+            self.rearm();
+        }
+    }
+
+    /// Delivers a deferred notification whose window has ended.
+    fn flush(&self) {
+        if self.pending.swap(0, Relaxed) == 1 {
+            self.last_wake_ns.store(now_ns(), Relaxed);
+            self.wake();
+        }
+    }
+
+    fn wake(&self) {
+        self.seq.fetch_add(1, Relaxed);
+        self.rearm();
+    }
+
+    fn rearm(&self) {
+        self.counter.fetch_add(1, Release);
+        crate::futex::wake_all(&self.counter);
+    }
+
+    fn window_end(&self) -> u64 {
+        self.window_end_from(self.last_wake_ns.load(Relaxed))
+    }
+
+    fn window_end_from(&self, last_wake_ns: u64) -> u64 {
+        last_wake_ns.saturating_add(self.window_ns.load(Relaxed))
+    }
+}
+
+fn now_ns() -> u64 {
+    monotonic_now().as_nanos() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::Mutex, std::thread};
+
+    #[test]
+    fn coalescing() {
+        let mutex = Mutex::new(0);
+        let condvar = CoalescingCondvar::new(Duration::from_millis(50));
+
+        let mut wakeups = 0;
+        thread::scope(|s| {
+            s.spawn(|| {
+                while condvar.num_waiters.load(Relaxed) == 0 {
+                    thread::yield_now();
+                }
+                for _ in 0..100 {
+                    *mutex.lock() += 1;
+                    condvar.notify_all();
+                    thread::sleep(Duration::from_millis(1));
+                }
+            });
+
+            // The final notification is delivered despite being deferred.
+            let mut m = mutex.lock();
+            while *m < 100 {
+                m = condvar.wait(m);
+                wakeups += 1;
+            }
+        });
+        assert!(wakeups < 20, "{wakeups}");
+    }
+}
@@ -2,11 +2,15 @@
 // Copyright 2023 Mara Bos, 978-1-098-11944-7."
 
 use {
-    crate::mutex::MutexGuard,
-    core::{
-        sync::atomic::{AtomicU32, AtomicUsize, Ordering::Relaxed},
-        time::Duration,
+    crate::{
+        futex::Interrupted,
+        mutex::MutexGuard,
+        ordering::{Relaxed, SeqCst},
+        sync::{const_fn, AtomicIsize, AtomicU32, AtomicUsize},
+        Doorbell, Mutex,
     },
+    core::time::Duration,
+    std::{io, time::Instant},
 };
 
 pub struct WaitTimeoutResult(bool);
@@ -17,11 +21,40 @@ impl WaitTimeoutResult {
     }
 }
 
+/// The layout is C compatible (see `c/shm_sync.h`).
+///
+/// [`Self::notify_all`] wakes one waiter and moves the rest to wait on the Mutex when every waiter
+/// used [`Self::wait_requeue`] with the same Mutex, otherwise it wakes them all.
+///
+/// `mutex_offset` is stored and loaded SeqCst, as is the `counter` increment of the notifiers: a
+/// waiter which sleeps (the futex checks the counter after a full barrier) has then published its
+/// Mutex before the notifier reads it, so it's never requeued onto another Mutex's state.
+#[repr(C)]
 pub struct Condvar {
     counter: AtomicU32,
     num_waiters: AtomicUsize,
+    /// The byte offset of the waiters' Mutex from the Condvar (the same in every process mapping
+    /// the region), 0 if unknown or [`UNPAIRED`] once any waiter couldn't be requeued
+    mutex_offset: AtomicIsize,
 }
 
+/// Never a valid offset as the Mutex state is aligned to 4 bytes (as is the Condvar).
+const UNPAIRED: isize = 1;
+
+// C11 ABI: `_Atomic uint32_t counter; _Atomic size_t num_waiters; _Atomic ptrdiff_t mutex_offset;`
+#[cfg(not(loom))]
+const _: () = assert!(core::mem::offset_of!(Condvar, counter) == 0);
+#[cfg(not(loom))]
+const _: () = assert!(
+    core::mem::offset_of!(Condvar, num_waiters) == core::mem::size_of::<usize>()
+        && core::mem::size_of::<AtomicUsize>() == core::mem::size_of::<usize>()
+);
+#[cfg(not(loom))]
+const _: () = assert!(
+    core::mem::offset_of!(Condvar, mutex_offset) == 2 * core::mem::size_of::<usize>()
+        && core::mem::size_of::<AtomicIsize>() == core::mem::size_of::<isize>()
+);
+
 impl Default for Condvar {
     fn default() -> Self {
         Condvar::new()
@@ -29,24 +62,99 @@ impl Default for Condvar {
 }
 
 impl Condvar {
-    pub const fn new() -> Self {
-        Self {
-            counter: AtomicU32::new(0),
-            num_waiters: AtomicUsize::new(0),
+    const_fn! {
+        pub fn new() -> Self {
+            Self {
+                counter: AtomicU32::new(0),
+                num_waiters: AtomicUsize::new(0),
+                mutex_offset: AtomicIsize::new(0),
+            }
         }
     }
 
+    /// Initializes a Condvar in memory not managed by this crate.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and aligned for `Self`, and no other thread or process may
+    /// access the memory until this method returns.
+    pub unsafe fn init_in_place(ptr: *mut Self) {
+        unsafe { ptr.write(Self::new()) }
+    }
+
+    /// Borrows an initialized Condvar from memory not managed by this crate.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned and point to an initialized Condvar which remains mapped for `'a`.
+    pub unsafe fn from_raw<'a>(ptr: *const Self) -> &'a Self {
+        unsafe { &*ptr }
+    }
+
     pub fn wait<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.wait_with(guard, false)
+    }
+
+    /// Like [`Self::wait`], but lets [`Self::notify_all`] requeue this waiter onto the Mutex
+    /// rather than waking it, sparing the waiters all contending for the Mutex at once.
+    ///
+    /// # Safety
+    ///
+    /// The guard's Mutex must be at the same offset from the Condvar in every process waiting on
+    /// it (ex: both fields of one Shareable struct, as in a Monitor), and remain so while any
+    /// process may notify the Condvar.
+    pub unsafe fn wait_requeue<'a, T>(&self, guard: MutexGuard<'a, T>) -> MutexGuard<'a, T> {
+        self.wait_with(guard, true)
+    }
+
+    fn wait_with<'a, T>(&self, guard: MutexGuard<'a, T>, requeue: bool) -> MutexGuard<'a, T> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("condvar_wait", condvar = ?(self as *const Self)).entered();
         self.num_waiters.fetch_add(1, Relaxed);
         let counter_value = self.counter.load(Relaxed);
 
-        let mutex = guard.mutex;
-        drop(guard);
+        let mutex = self.waiting_on(guard, requeue);
 
+        #[cfg(feature = "fairness")]
+        let ticket = crate::fairness::arrive(self);
         crate::futex::wait(&self.counter, counter_value);
+        #[cfg(feature = "fairness")]
+        crate::fairness::acquired(self, Some(ticket));
         self.num_waiters.fetch_sub(1, Relaxed);
 
-        mutex.lock()
+        if requeue {
+            mutex.lock_requeued()
+        } else {
+            mutex.lock()
+        }
+    }
+
+    /// Like [`Self::wait`], but reports a signal handler having run while waiting (ex: so a
+    /// shutdown requested by a signal can be observed) rather than waiting again.
+    pub fn wait_interruptible<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+    ) -> (MutexGuard<'a, T>, Result<(), Interrupted>) {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("condvar_wait", condvar = ?(self as *const Self)).entered();
+        self.num_waiters.fetch_add(1, Relaxed);
+        let counter_value = self.counter.load(Relaxed);
+
+        let mutex = self.waiting_on(guard, false);
+
+        #[cfg(feature = "fairness")]
+        let ticket = crate::fairness::arrive(self);
+        let result = crate::futex::wait_interruptible(&self.counter, counter_value, None);
+        #[cfg(feature = "fairness")]
+        match result {
+            Ok(_) => crate::fairness::acquired(self, Some(ticket)),
+            Err(_) => crate::fairness::left(self, ticket),
+        }
+        self.num_waiters.fetch_sub(1, Relaxed);
+
+        (mutex.lock(), result.map(|_| ()))
     }
 
     // TODO: add a test
@@ -55,31 +163,167 @@ impl Condvar {
         guard: MutexGuard<'a, T>,
         dur: Duration,
     ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        self.timed_wait(guard, false, |counter, value| {
+            crate::futex::wait_timeout(counter, value, Some(dur))
+        })
+    }
+
+    /// Like [`Self::wait_timeout`], but requeueable as [`Self::wait_requeue`].
+    ///
+    /// # Safety
+    ///
+    /// As for [`Self::wait_requeue`].
+    pub(crate) unsafe fn wait_timeout_requeue<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        dur: Duration,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        self.timed_wait(guard, true, |counter, value| {
+            crate::futex::wait_timeout(counter, value, Some(dur))
+        })
+    }
+
+    /// Like [`Self::wait_timeout`], but until an absolute deadline so retry loops (ex: waiting
+    /// for a condition across spurious wakeups) don't extend the total wait.
+    pub fn wait_deadline<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        deadline: Instant,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        self.timed_wait(guard, false, |counter, value| {
+            crate::futex::wait_until(counter, value, Some(deadline))
+        })
+    }
+
+    /// Like [`Self::wait_deadline`], but requeueable as [`Self::wait_requeue`].
+    ///
+    /// # Safety
+    ///
+    /// As for [`Self::wait_requeue`].
+    pub(crate) unsafe fn wait_deadline_requeue<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        deadline: Instant,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        self.timed_wait(guard, true, |counter, value| {
+            crate::futex::wait_until(counter, value, Some(deadline))
+        })
+    }
+
+    fn timed_wait<'a, T>(
+        &self,
+        guard: MutexGuard<'a, T>,
+        requeue: bool,
+        wait: impl FnOnce(&AtomicU32, u32) -> bool,
+    ) -> (MutexGuard<'a, T>, WaitTimeoutResult) {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("condvar_wait", condvar = ?(self as *const Self)).entered();
         self.num_waiters.fetch_add(1, Relaxed);
         let counter_value = self.counter.load(Relaxed);
 
-        let mutex = guard.mutex;
-        drop(guard);
+        let mutex = self.waiting_on(guard, requeue);
 
-        let success = crate::futex::wait_timeout(&self.counter, counter_value, Some(dur));
+        #[cfg(feature = "fairness")]
+        let ticket = crate::fairness::arrive(self);
+        let success = wait(&self.counter, counter_value);
+        #[cfg(feature = "fairness")]
+        match success {
+            true => crate::fairness::acquired(self, Some(ticket)),
+            false => crate::fairness::left(self, ticket),
+        }
         self.num_waiters.fetch_sub(1, Relaxed);
 
-        (mutex.lock(), WaitTimeoutResult(!success))
+        let guard = if requeue {
+            mutex.lock_requeued()
+        } else {
+            mutex.lock()
+        };
+        (guard, WaitTimeoutResult(!success))
     }
 
     pub fn notify_one(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(condvar = ?(self as *const Self), "notify_one");
         if self.num_waiters.load(Relaxed) > 0 {
             self.counter.fetch_add(1, Relaxed);
             crate::futex::wake_one(&self.counter);
         }
     }
 
+    /// Wakes up to `n` waiters (ex: one per job enqueued), returning the number woken.
+    pub fn notify_n(&self, n: usize) -> usize {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(condvar = ?(self as *const Self), n, "notify_n");
+        if n == 0 || self.num_waiters.load(Relaxed) == 0 {
+            return 0;
+        }
+        self.counter.fetch_add(1, Relaxed);
+        crate::futex::wake_n(&self.counter, n)
+    }
+
+    /// Wakes every waiter. Only waiters of [`Self::wait_requeue`] (ex: through a
+    /// [`crate::Monitor`]) are requeued onto their Mutex, so once any waiter used [`Self::wait`]
+    /// or another plain wait they all wake at once and contend for the Mutex.
     pub fn notify_all(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::trace!(condvar = ?(self as *const Self), "notify_all");
         if self.num_waiters.load(Relaxed) > 0 {
-            self.counter.fetch_add(1, Relaxed);
-            crate::futex::wake_all(&self.counter);
+            let counter = self.counter.fetch_add(1, SeqCst).wrapping_add(1);
+            // Waking one waiter and requeueing the rest onto the mutex spares them all contending
+            // for it at once. Each unlock then wakes the next.
+            let requeued = self
+                .mutex_state()
+                .and_then(|mutex| crate::futex::requeue(&self.counter, counter, 1, mutex));
+            if requeued.is_none() {
+                crate::futex::wake_all(&self.counter);
+            }
         }
     }
+
+    /// Like [`Self::notify_one`], but also rings `doorbell` to wake waiters polling it.
+    pub fn notify_one_ringing(&self, doorbell: &Doorbell) -> io::Result<()> {
+        self.notify_one();
+        doorbell.ring()
+    }
+
+    /// Like [`Self::notify_all`], but also rings `doorbell` to wake waiters polling it.
+    pub fn notify_all_ringing(&self, doorbell: &Doorbell) -> io::Result<()> {
+        self.notify_all();
+        doorbell.ring()
+    }
+
+    /// Records whether the guard's Mutex can be requeued onto and unlocks it.
+    fn waiting_on<'a, T>(&self, guard: MutexGuard<'a, T>, requeue: bool) -> &'a Mutex<T> {
+        let mutex = guard.mutex;
+        if requeue {
+            let offset = (mutex.state() as *const AtomicU32 as isize)
+                .wrapping_sub(self as *const Self as isize);
+            // Waiters on another Mutex can't share the requeue, so fall back to waking them all.
+            let _ = self
+                .mutex_offset
+                .fetch_update(SeqCst, SeqCst, |current| match current {
+                    0 => Some(offset),
+                    current if current == offset => None,
+                    _ => Some(UNPAIRED),
+                });
+        } else if self.mutex_offset.load(SeqCst) != UNPAIRED {
+            self.mutex_offset.store(UNPAIRED, SeqCst);
+        }
+        drop(guard);
+        mutex
+    }
+
+    fn mutex_state(&self) -> Option<&AtomicU32> {
+        let offset = self.mutex_offset.load(SeqCst);
+        // [SAFETY]: Every waiter so far promised (in `wait_requeue`) the Mutex is at this offset.
+        (offset != 0 && offset != UNPAIRED).then(|| unsafe {
+            &*(self as *const Self)
+                .cast::<u8>()
+                .wrapping_offset(offset)
+                .cast::<AtomicU32>()
+        })
+    }
 }
 
 #[cfg(test)]
@@ -116,4 +360,240 @@ mod tests {
         // while still allowing for a few spurious wake ups.
         assert!(wakeups < 10);
     }
+
+    #[test]
+    fn notify_n() {
+        use {
+            super::*,
+            crate::mutex::Mutex,
+            std::{thread, time::Duration},
+        };
+
+        let mutex = Mutex::new(0);
+        let condvar = Condvar::default();
+        assert_eq!(condvar.notify_n(1), 0);
+
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| {
+                    let mut m = mutex.lock();
+                    while *m == 0 {
+                        m = condvar.wait(m);
+                    }
+                    *m -= 1;
+                });
+            }
+            while condvar.num_waiters.load(Relaxed) < 3 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            // The last waiter to arrive may not have slept yet.
+            thread::sleep(Duration::from_millis(20));
+
+            *mutex.lock() = 2;
+            assert_eq!(condvar.notify_n(2), 2);
+            thread::sleep(Duration::from_millis(20));
+            *mutex.lock() += 1;
+            condvar.notify_all();
+        });
+        assert_eq!(*mutex.lock(), 0);
+    }
+
+    #[test]
+    fn notify_all() {
+        use {
+            super::*,
+            crate::mutex::Mutex,
+            std::{thread, time::Duration},
+        };
+
+        let mutex = Mutex::new(false);
+        let condvar = Condvar::default();
+        let woken = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut m = mutex.lock();
+                    while !*m {
+                        m = condvar.wait(m);
+                    }
+                    woken.fetch_add(1, Relaxed);
+                });
+            }
+            while condvar.num_waiters.load(Relaxed) < 4 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            thread::sleep(Duration::from_millis(20));
+
+            // Waiters woken while the mutex is held block on it until it's unlocked.
+            let mut m = mutex.lock();
+            *m = true;
+            condvar.notify_all();
+            thread::sleep(Duration::from_millis(10));
+            drop(m);
+        });
+        assert_eq!(woken.load(Relaxed), 4);
+    }
+
+    #[test]
+    fn notify_all_requeue() {
+        use {
+            super::*,
+            crate::{mutex::Mutex, Shareable, Shared},
+            std::{thread, time::Duration},
+        };
+
+        #[derive(Default)]
+        struct Pair {
+            mutex: Mutex<bool>,
+            condvar: Condvar,
+        }
+        unsafe impl Shareable for Pair {}
+
+        let pair: Shared<Pair> = Shared::create_anon().unwrap();
+        let woken = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut m = pair.mutex.lock();
+                    while !*m {
+                        m = unsafe { pair.condvar.wait_requeue(m) };
+                    }
+                    woken.fetch_add(1, Relaxed);
+                });
+            }
+            while pair.condvar.num_waiters.load(Relaxed) < 4 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            thread::sleep(Duration::from_millis(20));
+
+            let mut m = pair.mutex.lock();
+            *m = true;
+            pair.condvar.notify_all();
+            assert!(pair.condvar.mutex_state().is_some());
+            // Every waiter was woken or moved to the mutex, whose unlocks wake them in turn.
+            #[cfg(not(shm_heap))]
+            assert_eq!(crate::futex::wake_all(&pair.condvar.counter), 0);
+            thread::sleep(Duration::from_millis(10));
+            assert_eq!(woken.load(Relaxed), 0);
+            drop(m);
+        });
+        assert_eq!(woken.load(Relaxed), 4);
+    }
+
+    #[test]
+    fn notify_all_unpaired() {
+        use {
+            super::*,
+            crate::{mutex::Mutex, Shareable, Shared},
+            std::{thread, time::Duration},
+        };
+
+        #[derive(Default)]
+        struct Region {
+            condvar: Condvar,
+        }
+        unsafe impl Shareable for Region {}
+
+        // The Mutex is outside the Condvar's region, so its waiters can't be requeued.
+        let region: Shared<Region> = Shared::create_anon().unwrap();
+        let mutex = Mutex::new(false);
+        let woken = AtomicUsize::new(0);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    let mut m = mutex.lock();
+                    while !*m {
+                        m = region.condvar.wait(m);
+                    }
+                    woken.fetch_add(1, Relaxed);
+                });
+            }
+            while region.condvar.num_waiters.load(Relaxed) < 4 {
+                thread::sleep(Duration::from_millis(1));
+            }
+            thread::sleep(Duration::from_millis(20));
+
+            *mutex.lock() = true;
+            region.condvar.notify_all();
+            assert!(region.condvar.mutex_state().is_none());
+        });
+        assert_eq!(woken.load(Relaxed), 4);
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn loom_condvar() {
+        use {
+            super::*,
+            crate::mutex::Mutex,
+            loom::{sync::Arc, thread},
+        };
+
+        loom::model(|| {
+            let pair = Arc::new((Mutex::new(false), Condvar::new()));
+            let notifier = thread::spawn({
+                let pair = pair.clone();
+                move || {
+                    *pair.0.lock() = true;
+                    pair.1.notify_one();
+                }
+            });
+            let mut ready = pair.0.lock();
+            while !*ready {
+                ready = pair.1.wait(ready);
+            }
+            drop(ready);
+            notifier.join().unwrap();
+        });
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn loom_notify_all_mixed() {
+        use {
+            super::*,
+            crate::mutex::Mutex,
+            loom::{sync::Arc, thread},
+        };
+
+        struct Region {
+            paired: Mutex<bool>,
+            condvar: Condvar,
+            other: Mutex<bool>,
+        }
+
+        let mut model = loom::model::Builder::new();
+        model.preemption_bound = Some(2);
+        model.check(|| {
+            let region = Arc::new(Region {
+                paired: Mutex::new(false),
+                condvar: Condvar::new(),
+                other: Mutex::new(false),
+            });
+            // An earlier waiter paired the Condvar with a Mutex the plain waiters don't use.
+            drop(region.condvar.waiting_on(region.paired.lock(), true));
+
+            let waiters: Vec<_> = (0..2)
+                .map(|_| {
+                    thread::spawn({
+                        let region = region.clone();
+                        move || {
+                            let mut ready = region.other.lock();
+                            while !*ready {
+                                ready = region.condvar.wait(ready);
+                            }
+                        }
+                    })
+                })
+                .collect();
+            *region.other.lock() = true;
+            region.condvar.notify_all();
+            for waiter in waiters {
+                waiter.join().unwrap();
+            }
+        });
+    }
 }
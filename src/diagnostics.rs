@@ -0,0 +1,225 @@
+//! Detection of lock guards still held when a region is detached or the process exits.
+//!
+//! Every Mutex/RwLock guard acquired by this process is tracked until it's dropped. Guards still
+//! held when a [`crate::Shared`] mapping containing the lock is dropped, or when the process
+//! exits, are reported to stderr as they would deadlock (or stall) the remaining processes.
+//!
+//! A read of an RwLock which blocks while another thread of this process waits for (or holds) the
+//! write lock is also reported. If the reading thread already holds a read guard, it deadlocks.
+
+use std::{
+    sync::{Mutex, Once},
+    thread::{self, ThreadId},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Kind {
+    Exclusive,
+    Shared,
+}
+
+/// A guard which has been acquired but not yet dropped.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HeldGuard {
+    /// The address of the lock in this process
+    pub addr: usize,
+    pub kind: Kind,
+    pub thread: ThreadId,
+    /// The kernel thread id
+    pub tid: libc::pid_t,
+}
+
+/// A blocked read of an RwLock which this process is waiting for or holding the write lock of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReadConflict {
+    /// The address of the lock in this process
+    pub addr: usize,
+    pub pid: u32,
+    /// The kernel thread ids of the reading thread and of the thread waiting for or holding the
+    /// write lock
+    pub reader: libc::pid_t,
+    pub writer: libc::pid_t,
+    /// Set if the reader already holds a read guard (a deadlock)
+    pub recursive: bool,
+}
+
+static HELD: Mutex<Vec<HeldGuard>> = Mutex::new(Vec::new());
+/// Threads waiting for write locks as (lock address, tid)
+static WAITING: Mutex<Vec<(usize, libc::pid_t)>> = Mutex::new(Vec::new());
+static CONFLICTS: Mutex<Vec<ReadConflict>> = Mutex::new(Vec::new());
+
+pub(crate) fn acquired<T: ?Sized>(lock: *const T, kind: Kind) {
+    static AT_EXIT: Once = Once::new();
+    AT_EXIT.call_once(|| {
+        extern "C" fn report() {
+            self::report(&held_guards(), "process exit");
+        }
+        unsafe { libc::atexit(report) };
+    });
+
+    let guard = HeldGuard {
+        addr: lock.cast::<()>() as usize,
+        kind,
+        thread: thread::current().id(),
+        tid: unsafe { libc::gettid() },
+    };
+    let mut waiting = WAITING.lock().unwrap_or_else(|e| e.into_inner());
+    waiting.retain(|&w| w != (guard.addr, guard.tid));
+    drop(waiting);
+    HELD.lock().unwrap_or_else(|e| e.into_inner()).push(guard);
+}
+
+/// Records that the calling thread is about to wait for the write lock.
+pub(crate) fn waiting_to_write<T: ?Sized>(lock: *const T) {
+    let waiter = (lock.cast::<()>() as usize, unsafe { libc::gettid() });
+    let mut waiting = WAITING.lock().unwrap_or_else(|e| e.into_inner());
+    if !waiting.contains(&waiter) {
+        waiting.push(waiter);
+    }
+}
+
+/// Records that the calling thread stopped waiting for the write lock without acquiring it.
+pub(crate) fn gave_up_writing<T: ?Sized>(lock: *const T) {
+    let waiter = (lock.cast::<()>() as usize, unsafe { libc::gettid() });
+    let mut waiting = WAITING.lock().unwrap_or_else(|e| e.into_inner());
+    waiting.retain(|&w| w != waiter);
+}
+
+/// Checks a read of an RwLock which is about to block for conflicts with this process' writers.
+pub(crate) fn read_blocked<T: ?Sized>(lock: *const T) {
+    let (addr, reader) = (lock.cast::<()>() as usize, unsafe { libc::gettid() });
+    let held = held_guards();
+    let writer = WAITING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .iter()
+        .find(|w| w.0 == addr)
+        .map(|w| w.1)
+        .or_else(|| {
+            held.iter()
+                .find(|g| g.addr == addr && g.kind == Kind::Exclusive)
+                .map(|g| g.tid)
+        });
+    let Some(writer) = writer else {
+        return;
+    };
+
+    let conflict = ReadConflict {
+        addr,
+        pid: std::process::id(),
+        reader,
+        writer,
+        recursive: held
+            .iter()
+            .any(|g| g.addr == addr && g.kind == Kind::Shared && g.tid == reader),
+    };
+    let mut conflicts = CONFLICTS.lock().unwrap_or_else(|e| e.into_inner());
+    if !conflicts.contains(&conflict) {
+        eprintln!(
+            "shm: read of lock {addr:#x} by pid {} tid {reader} blocked on write by tid {writer}{}",
+            conflict.pid,
+            if conflict.recursive {
+                " (deadlock: the reader holds a read guard)"
+            } else {
+                ""
+            }
+        );
+        conflicts.push(conflict);
+    }
+}
+
+pub(crate) fn released<T: ?Sized>(lock: *const T) {
+    let addr = lock.cast::<()>() as usize;
+    let thread = thread::current().id();
+    let mut held = HELD.lock().unwrap_or_else(|e| e.into_inner());
+    // Shared guards may be released by a thread other than the one that acquired them.
+    if let Some(i) = held
+        .iter()
+        .rposition(|g| g.addr == addr && g.thread == thread)
+        .or_else(|| held.iter().rposition(|g| g.addr == addr))
+    {
+        held.swap_remove(i);
+    }
+}
+
+/// Returns the guards currently held by this process.
+pub fn held_guards() -> Vec<HeldGuard> {
+    HELD.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Returns the read conflicts detected in this process.
+pub fn read_conflicts() -> Vec<ReadConflict> {
+    CONFLICTS.lock().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Reports guards held on locks within a mapping which is being detached.
+pub(crate) fn detaching(start: *const u8, len: usize) {
+    let range = start as usize..(start as usize).saturating_add(len);
+    let held: Vec<_> = held_guards()
+        .into_iter()
+        .filter(|g| range.contains(&g.addr))
+        .collect();
+    report(&held, "detach");
+}
+
+fn report(held: &[HeldGuard], when: &str) {
+    for guard in held {
+        eprintln!(
+            "shm: {:?} guard on lock {:#x} still held by {:?} at {when}",
+            guard.kind, guard.addr, guard.thread
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Mutex, RwLock},
+        std::{sync::atomic::AtomicI32, sync::atomic::Ordering::Relaxed, time::Duration},
+    };
+
+    #[test]
+    fn held_guard() {
+        let mutex = Mutex::new(0);
+        let addr = &mutex as *const _ as usize;
+        let held = |addr| held_guards().iter().any(|g| g.addr == addr);
+
+        let guard = mutex.lock();
+        assert!(held(addr));
+        drop(guard);
+        assert!(!held(addr));
+    }
+
+    #[test]
+    fn read_conflict() {
+        let rwlock = RwLock::new(0);
+        let addr = &rwlock as *const _ as usize;
+        let (writer, reader) = (AtomicI32::new(0), AtomicI32::new(0));
+
+        let guard = rwlock.read();
+        thread::scope(|s| {
+            s.spawn(|| {
+                writer.store(unsafe { libc::gettid() }, Relaxed);
+                drop(rwlock.write());
+            });
+            thread::sleep(Duration::from_millis(20));
+            // Blocks behind the waiting writer, which waits for `guard`
+            s.spawn(|| {
+                reader.store(unsafe { libc::gettid() }, Relaxed);
+                drop(rwlock.read());
+            });
+            thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        });
+
+        let conflict = read_conflicts()
+            .into_iter()
+            .find(|c| c.addr == addr)
+            .unwrap();
+        assert_eq!(
+            (conflict.reader, conflict.writer, conflict.recursive),
+            (reader.into_inner(), writer.into_inner(), false)
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use {
+    core::time::Duration,
+    std::{
+        io,
+        os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+    },
+};
+
+/// An eventfd which wakes processes through their event loop (ex: epoll, a tokio `AsyncFd` or a C
+/// client's poll), which can't wait on futexes. The server creates it and passes the descriptor
+/// to clients (see [`AsFd`]) like an anonymous region's, then rings it alongside notifying a
+/// [`crate::Condvar`] (see [`crate::Condvar::notify_one_ringing`]).
+///
+/// The descriptor is non-blocking and becomes readable once rung, until drained.
+#[derive(Debug)]
+pub struct Doorbell {
+    fd: OwnedFd,
+}
+
+impl Doorbell {
+    pub fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    /// Wraps a descriptor received from the process which created the Doorbell.
+    pub fn from_fd(fd: OwnedFd) -> Self {
+        Self { fd }
+    }
+
+    /// Wakes the processes polling the descriptor.
+    pub fn ring(&self) -> io::Result<()> {
+        let one = 1u64;
+        let n = unsafe { libc::write(self.fd.as_raw_fd(), (&one as *const u64).cast(), 8) };
+        match n {
+            8 => Ok(()),
+            // The counter is saturated, so the descriptor is readable anyway.
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Resets the Doorbell, returning the number of rings since the last drain.
+    pub fn drain(&self) -> io::Result<u64> {
+        let mut count = 0u64;
+        let n = unsafe { libc::read(self.fd.as_raw_fd(), (&mut count as *mut u64).cast(), 8) };
+        match n {
+            8 => Ok(count),
+            _ if io::Error::last_os_error().kind() == io::ErrorKind::WouldBlock => Ok(0),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Blocks until the Doorbell is rung (without draining it), returning false if `timeout`
+    /// elapses first.
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let timeout = timeout.map_or(-1, |t| t.as_millis().try_into().unwrap_or(i32::MAX));
+        let mut pollfd = libc::pollfd {
+            fd: self.fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            match unsafe { libc::poll(&mut pollfd, 1, timeout) } {
+                n if n >= 0 => return Ok(n > 0),
+                _ if io::Error::last_os_error().kind() == io::ErrorKind::Interrupted => {}
+                _ => return Err(io::Error::last_os_error()),
+            }
+        }
+    }
+}
+
+impl AsFd for Doorbell {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl AsRawFd for Doorbell {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl From<Doorbell> for OwnedFd {
+    fn from(doorbell: Doorbell) -> Self {
+        doorbell.fd
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::Condvar, std::thread};
+
+    #[test]
+    fn ring() {
+        let doorbell = Doorbell::new().unwrap();
+        assert!(!doorbell.wait(Some(Duration::ZERO)).unwrap());
+
+        // A client holding a duplicate of the descriptor is woken by the server's notify.
+        let client = Doorbell::from_fd(doorbell.as_fd().try_clone_to_owned().unwrap());
+        let condvar = Condvar::new();
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                condvar.notify_all_ringing(&doorbell).unwrap();
+                condvar.notify_one_ringing(&doorbell).unwrap();
+            });
+            assert!(client.wait(None).unwrap());
+        });
+        assert_eq!(client.drain().unwrap(), 2);
+        assert_eq!(client.drain().unwrap(), 0);
+    }
+}
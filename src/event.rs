@@ -0,0 +1,127 @@
+use {
+    crate::{
+        futex::Interrupted,
+        ordering::{Acquire, Release},
+        Shareable,
+    },
+    core::{sync::atomic::AtomicU32, time::Duration},
+    std::time::Instant,
+};
+
+const RESET: u32 = 0;
+const SET: u32 = 1;
+
+/// A manual-reset event: once set, every waiter is released until the event is reset.
+#[derive(Default)]
+pub struct Event {
+    /// RESET or SET (also the futex waited on while RESET)
+    state: AtomicU32,
+}
+
+unsafe impl Shareable for Event {}
+
+impl Event {
+    pub const fn new(set: bool) -> Self {
+        Self {
+            state: AtomicU32::new(if set { SET } else { RESET }),
+        }
+    }
+
+    pub fn is_set(&self) -> bool {
+        self.state.load(Acquire) == SET
+    }
+
+    /// Sets the event, waking all waiters.
+    pub fn set(&self) {
+        if self.state.swap(SET, Release) == RESET {
+            crate::futex::wake_all(&self.state);
+        }
+    }
+
+    pub fn reset(&self) {
+        self.state.store(RESET, Release);
+    }
+
+    /// Blocks until the event is set.
+    pub fn wait(&self) {
+        while !self.is_set() {
+            crate::futex::wait(&self.state, RESET);
+        }
+    }
+
+    /// Blocks until the event is set, returning early if a signal handler runs while waiting.
+    pub fn wait_interruptible(&self) -> Result<(), Interrupted> {
+        while !self.is_set() {
+            crate::futex::wait_interruptible(&self.state, RESET, None)?;
+        }
+        Ok(())
+    }
+
+    /// Blocks until the event is set, returning false if `dur` elapses first.
+    pub fn wait_timeout(&self, dur: Duration) -> bool {
+        let start = Instant::now();
+        while !self.is_set() {
+            let Some(remaining) = dur.checked_sub(start.elapsed()) else {
+                return false;
+            };
+            crate::futex::wait_timeout(&self.state, RESET, Some(remaining));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn event() {
+        let event = Event::new(false);
+        assert!(!event.wait_timeout(Duration::from_millis(10)));
+
+        thread::scope(|s| {
+            for _ in 0..3 {
+                s.spawn(|| event.wait());
+            }
+            thread::sleep(Duration::from_millis(10));
+            event.set();
+        });
+        assert!(event.wait_timeout(Duration::ZERO));
+
+        event.reset();
+        assert!(!event.is_set());
+    }
+
+    // Threads parked by the heap backend aren't interrupted by signals.
+    #[cfg(not(shm_heap))]
+    #[test]
+    fn interrupted() {
+        use std::sync::atomic::{AtomicU64, Ordering::Relaxed};
+
+        extern "C" fn handler(_: libc::c_int) {}
+        // Without SA_RESTART
+        let mut action: libc::sigaction = unsafe { core::mem::zeroed() };
+        action.sa_sigaction = handler as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        assert_eq!(
+            unsafe { libc::sigaction(libc::SIGUSR1, &action, core::ptr::null_mut()) },
+            0
+        );
+
+        let event = Event::new(false);
+        let thread_id = AtomicU64::new(0);
+        thread::scope(|s| {
+            let waiter = s.spawn(|| {
+                thread_id.store(unsafe { libc::pthread_self() }, Relaxed);
+                event.wait_interruptible()
+            });
+            // Repeated in case the signal arrives before waiting
+            while !waiter.is_finished() {
+                if let id @ 1.. = thread_id.load(Relaxed) {
+                    unsafe { libc::pthread_kill(id, libc::SIGUSR1) };
+                }
+                thread::sleep(Duration::from_millis(1));
+            }
+            assert_eq!(waiter.join().unwrap(), Err(Interrupted));
+        });
+    }
+}
@@ -0,0 +1,134 @@
+//! Fairness auditing of Mutex acquisition and Condvar wakeups.
+//!
+//! Each waiter takes a ticket on arrival. Whenever the lock is acquired (or a Condvar waiter is
+//! woken) ahead of earlier arrivals, each of those earlier waiters is counted as bypassed. The
+//! number of bypasses a waiter suffered before being served is recorded per lock.
+//!
+//! Auditing is per process: waiters in other processes aren't observed.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Mutex,
+};
+
+/// Fairness statistics of a single lock or Condvar.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The address of the lock in this process
+    pub addr: usize,
+    pub acquisitions: u64,
+    /// Acquisitions (or wakeups) which had to wait
+    pub waited: u64,
+    /// The number of waiters served after being bypassed `n` times, keyed by `n`
+    pub bypassed: BTreeMap<u64, u64>,
+    /// Waiters still waiting and the number of times each has been bypassed so far
+    pub waiting: Vec<u64>,
+}
+
+impl Report {
+    /// The most times a waiter has been bypassed, including waiters still waiting.
+    pub fn max_bypassed(&self) -> u64 {
+        let served = self.bypassed.keys().next_back().copied();
+        let waiting = self.waiting.iter().max().copied();
+        served.max(waiting).unwrap_or(0)
+    }
+
+    /// The number of waiters bypassed at least `threshold` times.
+    pub fn starved(&self, threshold: u64) -> u64 {
+        let served: u64 = self.bypassed.range(threshold..).map(|(_, n)| n).sum();
+        served + self.waiting.iter().filter(|&&b| b >= threshold).count() as u64
+    }
+}
+
+#[derive(Default)]
+struct Audit {
+    next_ticket: u64,
+    /// Bypass counts of waiters, keyed by ticket (arrival order)
+    waiting: BTreeMap<u64, u64>,
+    acquisitions: u64,
+    waited: u64,
+    bypassed: BTreeMap<u64, u64>,
+}
+
+static AUDITS: Mutex<Option<HashMap<usize, Audit>>> = Mutex::new(None);
+
+fn with_audit<R>(addr: *const (), f: impl FnOnce(&mut Audit) -> R) -> R {
+    let mut audits = AUDITS.lock().unwrap_or_else(|e| e.into_inner());
+    f(audits
+        .get_or_insert_with(HashMap::new)
+        .entry(addr as usize)
+        .or_default())
+}
+
+/// Registers a waiter, returning its ticket.
+pub(crate) fn arrive<T: ?Sized>(lock: *const T) -> u64 {
+    with_audit(lock.cast(), |audit| {
+        let ticket = audit.next_ticket;
+        audit.next_ticket += 1;
+        audit.waiting.insert(ticket, 0);
+        ticket
+    })
+}
+
+/// Records an acquisition by the waiter holding `ticket`, or by a thread which didn't wait.
+pub(crate) fn acquired<T: ?Sized>(lock: *const T, ticket: Option<u64>) {
+    with_audit(lock.cast(), |audit| {
+        audit.acquisitions += 1;
+        let bypassed = ticket.and_then(|t| audit.waiting.remove(&t));
+        for (_, b) in audit.waiting.range_mut(..ticket.unwrap_or(u64::MAX)) {
+            *b += 1;
+        }
+        if let Some(b) = bypassed {
+            audit.waited += 1;
+            *audit.bypassed.entry(b).or_default() += 1;
+        }
+    })
+}
+
+/// Removes a waiter which gave up (ex: timed out) without being served.
+pub(crate) fn left<T: ?Sized>(lock: *const T, ticket: u64) {
+    with_audit(lock.cast(), |audit| audit.waiting.remove(&ticket));
+}
+
+/// Returns the statistics of every audited lock, the most bypassed first.
+pub fn report() -> Vec<Report> {
+    let audits = AUDITS.lock().unwrap_or_else(|e| e.into_inner());
+    let mut reports: Vec<_> = audits
+        .iter()
+        .flatten()
+        .map(|(&addr, audit)| Report {
+            addr,
+            acquisitions: audit.acquisitions,
+            waited: audit.waited,
+            bypassed: audit.bypassed.clone(),
+            waiting: audit.waiting.values().copied().collect(),
+        })
+        .collect();
+    reports.sort_by_key(|r| std::cmp::Reverse(r.max_bypassed()));
+    reports
+}
+
+/// Discards all statistics.
+pub fn reset() {
+    *AUDITS.lock().unwrap_or_else(|e| e.into_inner()) = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bypass_counting() {
+        let lock = 0u8;
+        let (a, b) = (arrive(&lock), arrive(&lock));
+        acquired(&lock, None);
+        acquired(&lock, Some(b));
+        acquired(&lock, Some(a));
+
+        let addr = &lock as *const _ as usize;
+        let report = report().into_iter().find(|r| r.addr == addr).unwrap();
+        assert_eq!((report.acquisitions, report.waited), (3, 2));
+        assert_eq!(report.max_bypassed(), 2);
+        assert_eq!(report.starved(2), 1);
+    }
+}
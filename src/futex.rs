@@ -1,82 +1,431 @@
 // This code derives from Rust Atomics and Locks by Mara Bos (O’Reilly).
 // Copyright 2023 Mara Bos, 978-1-098-11944-7."
 
-use core::{mem::MaybeUninit, sync::atomic::AtomicU32, time::Duration};
+use {
+    core::{fmt, mem::MaybeUninit, sync::atomic::AtomicU32, time::Duration},
+    std::time::Instant,
+};
 
 // Futex documentation reference:
 // https://man7.org/linux/man-pages/man2/futex.2.html
 
+/// Returned by the `*_interruptible` waits when a signal handler ran before the wait completed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "wait interrupted by a signal")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// A 32-bit word which processes can wait on and wake, for building custom protocols in shared
+/// memory. It dereferences to the AtomicU32 holding the value.
+///
+/// Futex operations omit FUTEX_PRIVATE_FLAG, so waiters and wakers may be in different processes
+/// as long as the Futex lives in a shared mapping (ex: within a [`crate::Shared`] region).
+///
+/// Waits return immediately if the value differs from `expected`, and may also return spuriously,
+/// so callers should recheck the value in a loop.
+#[derive(Debug, Default)]
+#[repr(transparent)]
+pub struct Futex(AtomicU32);
+
+unsafe impl crate::Shareable for Futex {}
+
+impl Futex {
+    pub const fn new(value: u32) -> Self {
+        Self(AtomicU32::new(value))
+    }
+
+    /// Blocks while the value is `expected`, until woken.
+    pub fn wait(&self, expected: u32) {
+        wait(&self.0, expected)
+    }
+
+    /// Like [`Self::wait`], but returns false if `timeout` elapses first.
+    pub fn wait_timeout(&self, expected: u32, timeout: Duration) -> bool {
+        wait_timeout(&self.0, expected, Some(timeout))
+    }
+
+    /// Like [`Self::wait`], but returns false if `deadline` passes first.
+    pub fn wait_until(&self, expected: u32, deadline: Instant) -> bool {
+        wait_until(&self.0, expected, Some(deadline))
+    }
+
+    /// Like [`Self::wait`], but returns [`Interrupted`] if a signal handler runs first.
+    pub fn wait_interruptible(&self, expected: u32) -> Result<(), Interrupted> {
+        wait_interruptible(&self.0, expected, None).map(|_| ())
+    }
+
+    /// Wakes one waiter, returning the number woken.
+    pub fn wake_one(&self) -> usize {
+        wake_one(&self.0)
+    }
+
+    /// Wakes up to `n` waiters, returning the number woken.
+    pub fn wake_n(&self, n: usize) -> usize {
+        wake_n(&self.0, n)
+    }
+
+    /// Wakes every waiter, returning the number woken.
+    pub fn wake_all(&self) -> usize {
+        wake_all(&self.0)
+    }
+}
+
+impl core::ops::Deref for Futex {
+    type Target = AtomicU32;
+
+    fn deref(&self) -> &AtomicU32 {
+        &self.0
+    }
+}
+
+/// A 32-bit word the functions below wait on and wake: an AtomicU32, or under `cfg(loom)` the
+/// loom atomic of a lock state machine, whose waits are modelled with loom's Condvar.
+pub(crate) trait Word {
+    fn wait(&self, expected: u32, deadline: Option<&libc::timespec>) -> Result<bool, Interrupted>;
+
+    fn wake(&self, n: i32) -> usize;
+
+    fn requeue(&self, expected: u32, wake: i32, to: &Self) -> Option<usize>;
+
+    /// Like `wait`, but only woken by wakes whose bitset intersects `mask`. Defaults to being
+    /// woken by any wake, which waiters tolerate as a spurious wakeup.
+    fn wait_bitset(
+        &self,
+        expected: u32,
+        deadline: Option<&libc::timespec>,
+        _mask: u32,
+    ) -> Result<bool, Interrupted> {
+        self.wait(expected, deadline)
+    }
+
+    /// Like `wake`, but only wakes waiters whose bitset intersects `mask`. Defaults to waking any.
+    fn wake_bitset(&self, n: i32, _mask: u32) -> usize {
+        self.wake(n)
+    }
+}
+
+#[cfg(not(shm_heap))]
+impl Word for AtomicU32 {
+    fn wait(&self, expected: u32, deadline: Option<&libc::timespec>) -> Result<bool, Interrupted> {
+        futex_wait(
+            self,
+            expected,
+            deadline,
+            libc::FUTEX_BITSET_MATCH_ANY as u32,
+        )
+    }
+
+    fn wake(&self, n: i32) -> usize {
+        crate::usdt::probe!("futex_wake", self as *const _, n);
+        let woken = unsafe { libc::syscall(libc::SYS_futex, self, libc::FUTEX_WAKE, n) };
+        usize::try_from(woken).unwrap_or(0)
+    }
+
+    fn wait_bitset(
+        &self,
+        expected: u32,
+        deadline: Option<&libc::timespec>,
+        mask: u32,
+    ) -> Result<bool, Interrupted> {
+        futex_wait(self, expected, deadline, mask)
+    }
+
+    fn wake_bitset(&self, n: i32, mask: u32) -> usize {
+        crate::usdt::probe!("futex_wake", self as *const _, n);
+        let woken = unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                self,
+                libc::FUTEX_WAKE_BITSET,
+                n,
+                core::ptr::null::<libc::timespec>(),
+                core::ptr::null::<u32>(),
+                mask,
+            )
+        };
+        usize::try_from(woken).unwrap_or(0)
+    }
+
+    fn requeue(&self, expected: u32, wake: i32, to: &Self) -> Option<usize> {
+        crate::usdt::probe!("futex_wake", self as *const _, wake);
+        let moved = unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                self,
+                libc::FUTEX_CMP_REQUEUE,
+                wake,
+                // The number to requeue is passed in place of the timeout.
+                i32::MAX as usize as *const libc::timespec,
+                to,
+                expected,
+            )
+        };
+        usize::try_from(moved).ok()
+    }
+}
+
+// Every word shares one parking lot, which is enough for the models' handful of threads. Checking
+// the value and parking under its lock gives the futex's atomicity, as wakers take the lock after
+// changing the value. The value is loaded SeqCst as the kernel issues a full barrier before reading
+// it. Each waiter records the word it's parked on, so a waiter requeued onto the wrong word is
+// never woken (and loom reports the deadlock). Timed waits can't be modelled, so they yield instead.
+#[cfg(loom)]
+#[derive(Default)]
+struct Parking {
+    /// The address of the word each waiter is parked on, or None once it's woken
+    waiters: loom::sync::Mutex<Vec<Option<usize>>>,
+    woken: loom::sync::Condvar,
+}
+
+#[cfg(loom)]
+loom::lazy_static! {
+    static ref PARKING: Parking = Parking::default();
+}
+
+#[cfg(loom)]
+impl Parking {
+    /// Wakes up to `n` waiters parked on `word`, moving the rest onto `to` if any.
+    fn wake(&self, waiters: &mut [Option<usize>], word: usize, n: i32, to: Option<usize>) -> usize {
+        let mut n = usize::try_from(n).unwrap_or(0);
+        let mut count = 0;
+        for waiter in waiters.iter_mut().filter(|waiter| **waiter == Some(word)) {
+            if n > 0 {
+                *waiter = None;
+                n -= 1;
+            } else if to.is_some() {
+                *waiter = to;
+            } else {
+                break;
+            }
+            count += 1;
+        }
+        self.woken.notify_all();
+        count
+    }
+}
+
+#[cfg(loom)]
+impl Word for loom::sync::atomic::AtomicU32 {
+    fn wait(&self, expected: u32, deadline: Option<&libc::timespec>) -> Result<bool, Interrupted> {
+        if deadline.is_some() {
+            if self.load(crate::ordering::Relaxed) == expected {
+                loom::thread::yield_now();
+            }
+            return Ok(true);
+        }
+        let mut waiters = PARKING.waiters.lock().unwrap();
+        if self.load(crate::ordering::SeqCst) == expected {
+            let parked = waiters.len();
+            waiters.push(Some(self as *const Self as usize));
+            while waiters[parked].is_some() {
+                waiters = PARKING.woken.wait(waiters).unwrap();
+            }
+        }
+        Ok(true)
+    }
+
+    fn wake(&self, n: i32) -> usize {
+        let mut waiters = PARKING.waiters.lock().unwrap();
+        PARKING.wake(&mut waiters, self as *const Self as usize, n, None)
+    }
+
+    fn requeue(&self, expected: u32, wake: i32, to: &Self) -> Option<usize> {
+        let mut waiters = PARKING.waiters.lock().unwrap();
+        (self.load(crate::ordering::SeqCst) == expected).then(|| {
+            let word = self as *const Self as usize;
+            PARKING.wake(&mut waiters, word, wake, Some(to as *const Self as usize))
+        })
+    }
+}
+
 #[inline]
-pub(crate) fn wait(a: &AtomicU32, expected: u32) {
+pub(crate) fn wait(a: &impl Word, expected: u32) {
     wait_timeout(a, expected, None);
 }
 
 // Returns false if wait timed out
-pub(crate) fn wait_timeout(a: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
-    let ts = {
-        fn add(ts: libc::timespec, dur: Duration) -> Option<libc::timespec> {
-            const NSEC_PER_SEC: i64 = 1_000_000_000;
-
-            let mut secs = ts.tv_sec.checked_add_unsigned(dur.as_secs())?;
-            let mut nsecs = ts.tv_nsec + i64::from(dur.subsec_nanos());
-            if nsecs >= NSEC_PER_SEC {
-                nsecs -= NSEC_PER_SEC;
-                secs = secs.checked_add(1)?;
-            }
+pub(crate) fn wait_timeout(a: &impl Word, expected: u32, timeout: Option<Duration>) -> bool {
+    let ts = deadline(timeout);
+    loop {
+        if let Ok(woken) = a.wait(expected, ts.as_ref()) {
+            break woken;
+        }
+    }
+}
 
-            Some(libc::timespec {
-                tv_sec: secs,
-                tv_nsec: nsecs,
-            })
+/// Like `wait_timeout`, but until the absolute `deadline` (Instant uses CLOCK_MONOTONIC, like the
+/// futex), so retry loops don't extend the total wait. Returns false if the wait timed out.
+pub(crate) fn wait_until(a: &impl Word, expected: u32, deadline: Option<Instant>) -> bool {
+    wait_timeout(
+        a,
+        expected,
+        deadline.map(|d| d.saturating_duration_since(Instant::now())),
+    )
+}
+
+/// Like `wait_timeout`, but returns instead of retrying when interrupted by a signal.
+pub(crate) fn wait_interruptible(
+    a: &impl Word,
+    expected: u32,
+    timeout: Option<Duration>,
+) -> Result<bool, Interrupted> {
+    a.wait(expected, deadline(timeout).as_ref())
+}
+
+/// The absolute CLOCK_MONOTONIC time at which `timeout` elapses.
+fn deadline(timeout: Option<Duration>) -> Option<libc::timespec> {
+    fn add(ts: libc::timespec, dur: Duration) -> Option<libc::timespec> {
+        const NSEC_PER_SEC: i64 = 1_000_000_000;
+
+        let mut secs = ts.tv_sec.checked_add_unsigned(dur.as_secs())?;
+        let mut nsecs = ts.tv_nsec + i64::from(dur.subsec_nanos());
+        if nsecs >= NSEC_PER_SEC {
+            nsecs -= NSEC_PER_SEC;
+            secs = secs.checked_add(1)?;
         }
 
-        // NOTE: overflow is rounded up to an infinite duration
-        timeout.and_then(|to| {
-            let mut ts = MaybeUninit::uninit();
-            (unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, ts.as_mut_ptr()) } == 0)
-                .then(|| unsafe { ts.assume_init() })
-                .and_then(|ts| add(ts, to))
+        Some(libc::timespec {
+            tv_sec: secs,
+            tv_nsec: nsecs,
         })
-    };
+    }
+
+    // NOTE: overflow is rounded up to an infinite duration
+    timeout.and_then(|to| {
+        let mut ts = MaybeUninit::uninit();
+        (unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, ts.as_mut_ptr()) } == 0)
+            .then(|| unsafe { ts.assume_init() })
+            .and_then(|ts| add(ts, to))
+    })
+}
 
-    let tsp = match ts {
-        Some(ref ts) => ts,
+#[cfg(not(shm_heap))]
+fn futex_wait(
+    a: *const AtomicU32,
+    expected: u32,
+    deadline: Option<&libc::timespec>,
+    mask: u32,
+) -> Result<bool, Interrupted> {
+    let tsp = match deadline {
+        Some(ts) => ts,
         None => core::ptr::null(),
     };
 
-    loop {
-        match (unsafe {
-            libc::syscall(
-                libc::SYS_futex,
-                a,
-                libc::FUTEX_WAIT_BITSET,
-                expected,
-                tsp,
-                core::ptr::null::<u32>(),
-                libc::FUTEX_BITSET_MATCH_ANY,
-            )
-        } < 0)
-            .then(|| unsafe { *libc::__errno_location() })
-        {
-            Some(libc::ETIMEDOUT) => break false,
-            Some(libc::EINTR) => continue,
-            _ => break true,
+    crate::usdt::probe!("futex_wait", a, expected);
+    match (unsafe {
+        libc::syscall(
+            libc::SYS_futex,
+            a,
+            libc::FUTEX_WAIT_BITSET,
+            expected,
+            tsp,
+            core::ptr::null::<u32>(),
+            mask,
+        )
+    } < 0)
+        .then(|| unsafe { *libc::__errno_location() })
+    {
+        Some(libc::ETIMEDOUT) => Ok(false),
+        Some(libc::EINTR) => Err(Interrupted),
+        _ => Ok(true),
+    }
+}
+
+/// Waits without blocking the async runtime, by handing the futex wait to tokio's blocking pool,
+/// so every pending wait occupies a pool thread. Cancelling the wait wakes the word's waiters to
+/// release the thread (the others wake spuriously and wait again). The pool thread only passes
+/// the word's address to the kernel, so the region may be unmapped once the caller is cancelled;
+/// each wait is still capped at `ASYNC_WAIT_MAX` for a thread which only starts waiting after that
+/// wake. Like `wait`, it may return spuriously.
+#[cfg(all(feature = "async", not(shm_heap)))]
+pub(crate) async fn wait_async(a: &AtomicU32, expected: u32) {
+    const ASYNC_WAIT_MAX: Duration = Duration::from_millis(100);
+
+    /// Wakes the word's waiters unless disarmed once the wait completes.
+    struct WakeOnDrop<'a>(Option<&'a AtomicU32>);
+
+    impl Drop for WakeOnDrop<'_> {
+        fn drop(&mut self) {
+            if let Some(a) = self.0 {
+                wake_all(a);
+            }
         }
     }
+
+    if a.load(crate::ordering::Relaxed) != expected {
+        return;
+    }
+    let addr = a as *const AtomicU32 as usize;
+    let ts = deadline(Some(ASYNC_WAIT_MAX));
+    let mut cancel = WakeOnDrop(Some(a));
+    let _ = tokio::task::spawn_blocking(move || {
+        futex_wait(
+            addr as *const AtomicU32,
+            expected,
+            ts.as_ref(),
+            libc::FUTEX_BITSET_MATCH_ANY as u32,
+        )
+    })
+    .await;
+    cancel.0 = None;
 }
 
+#[cfg(all(feature = "async", shm_heap))]
+pub(crate) use crate::heap::wait_async;
+
+// The wake functions return the number of waiters woken.
+
 #[inline]
-pub(crate) fn wake_one(a: &AtomicU32) {
-    unsafe {
-        libc::syscall(libc::SYS_futex, a, libc::FUTEX_WAKE, 1i32);
-    };
+pub(crate) fn wake_one(a: &impl Word) -> usize {
+    wake_n(a, 1)
 }
 
 #[inline]
-pub(crate) fn wake_all(a: &AtomicU32) {
-    unsafe {
-        libc::syscall(libc::SYS_futex, a, libc::FUTEX_WAKE, i32::MAX);
-    };
+pub(crate) fn wake_all(a: &impl Word) -> usize {
+    wake_n(a, usize::MAX)
+}
+
+/// Wakes up to `wake` waiters on `a` and moves the others to wait on `to`, provided `a` still
+/// holds `expected`. Returns the number woken and moved, or None if the value changed.
+pub(crate) fn requeue<W: Word>(a: &W, expected: u32, wake: usize, to: &W) -> Option<usize> {
+    a.requeue(expected, i32::try_from(wake).unwrap_or(i32::MAX), to)
+}
+
+/// Like `wait`, but only woken by `wake_bitset` calls whose mask intersects `mask` (or by the
+/// other wakes, which match any waiter). A mask of 0 is invalid.
+pub(crate) fn wait_bitset(a: &impl Word, expected: u32, mask: u32) {
+    while a.wait_bitset(expected, None, mask).is_err() {}
+}
+
+/// Like `wait_bitset`, but gives up once `deadline` has passed. Returns false if the wait timed out.
+pub(crate) fn wait_bitset_until(
+    a: &impl Word,
+    expected: u32,
+    mask: u32,
+    deadline: Option<Instant>,
+) -> bool {
+    let ts = self::deadline(deadline.map(|d| d.saturating_duration_since(Instant::now())));
+    loop {
+        if let Ok(woken) = a.wait_bitset(expected, ts.as_ref(), mask) {
+            break woken;
+        }
+    }
+}
+
+/// Wakes every waiter whose `wait_bitset` mask intersects `mask`, returning the number woken.
+pub(crate) fn wake_bitset(a: &impl Word, mask: u32) -> usize {
+    a.wake_bitset(i32::MAX, mask)
+}
+
+#[inline]
+pub(crate) fn wake_n(a: &impl Word, n: usize) -> usize {
+    a.wake(i32::try_from(n).unwrap_or(i32::MAX))
 }
 
 #[cfg(test)]
@@ -103,7 +452,7 @@ mod tests {
                 {
                     // wait shouldn't block when the expected value differs
                     let timer = Instant::now();
-                    wait(&fut, 1);
+                    wait(&*fut, 1);
                     let elapsed = timer.elapsed();
                     if elapsed > Duration::from_millis(5) {
                         panic!("{elapsed:?} exceeds threshold");
@@ -114,7 +463,7 @@ mod tests {
                     // wait should block when the expected value is the same
                     fut.store(1, Relaxed);
                     let timer = Instant::now();
-                    wait(&fut, 1);
+                    wait(&*fut, 1);
                     let elapsed = timer.elapsed();
                     if elapsed < Duration::from_millis(10) {
                         panic!("{elapsed:?} exceeds threshold");
@@ -126,7 +475,7 @@ mod tests {
                     // wait should also be notified by wake_all
                     fut.store(3, Relaxed);
                     let timer = Instant::now();
-                    wait(&fut, 3);
+                    wait(&*fut, 3);
                     let elapsed = timer.elapsed();
                     if elapsed < Duration::from_millis(10) {
                         panic!("{elapsed:?} exceeds threshold");
@@ -141,11 +490,11 @@ mod tests {
             match fut.load(Relaxed) {
                 1 => {
                     std::thread::sleep(Duration::from_millis(10));
-                    wake_one(&fut);
+                    wake_one(&*fut);
                 }
                 3 => {
                     std::thread::sleep(Duration::from_millis(10));
-                    wake_all(&fut);
+                    wake_all(&*fut);
                 }
                 _ => {}
             }
@@ -176,7 +525,7 @@ mod tests {
                 {
                     // wait_timeout shouldn't block when the expected value differs
                     let timer = Instant::now();
-                    wait_timeout(&fut, 1, Some(Duration::from_secs(1)));
+                    wait_timeout(&*fut, 1, Some(Duration::from_secs(1)));
                     let elapsed = timer.elapsed();
                     if elapsed > Duration::from_millis(5) {
                         panic!("{elapsed:?} exceeds threshold");
@@ -187,7 +536,7 @@ mod tests {
                     // wait_timeout should block when the expected value is the same
                     fut.store(1, Relaxed);
                     let timer = Instant::now();
-                    wait_timeout(&fut, 1, Some(Duration::from_secs(1)));
+                    wait_timeout(&*fut, 1, Some(Duration::from_secs(1)));
                     let elapsed = timer.elapsed();
                     if elapsed < Duration::from_millis(10) {
                         panic!("{elapsed:?} exceeds threshold");
@@ -199,7 +548,7 @@ mod tests {
                     // wait_timeout should return once the timeout expires
                     const TIMEOUT: Duration = Duration::from_millis(10);
                     let timer = Instant::now();
-                    wait_timeout(&fut, 2, Some(TIMEOUT));
+                    wait_timeout(&*fut, 2, Some(TIMEOUT));
                     let elapsed = timer.elapsed();
                     if elapsed < TIMEOUT {
                         panic!("{elapsed:?} exceeds threshold");
@@ -210,7 +559,7 @@ mod tests {
                     // wait should also be notified by wake_all
                     fut.store(3, Relaxed);
                     let timer = Instant::now();
-                    wait_timeout(&fut, 3, Some(Duration::from_secs(1)));
+                    wait_timeout(&*fut, 3, Some(Duration::from_secs(1)));
                     let elapsed = timer.elapsed();
                     if elapsed < Duration::from_millis(10) {
                         panic!("{elapsed:?} exceeds threshold");
@@ -225,11 +574,11 @@ mod tests {
             match fut.load(Relaxed) {
                 1 => {
                     std::thread::sleep(Duration::from_millis(10));
-                    wake_one(&fut);
+                    wake_one(&*fut);
                 }
                 3 => {
                     std::thread::sleep(Duration::from_millis(10));
-                    wake_all(&fut);
+                    wake_all(&*fut);
                 }
                 _ => {}
             }
@@ -248,4 +597,27 @@ mod tests {
             std::thread::sleep(Duration::from_millis(1));
         }
     }
+
+    // Regions of the heap backend aren't shared with forked processes.
+    #[cfg(not(shm_heap))]
+    #[test]
+    fn process_shared() {
+        let futex: crate::Shared<Futex> = crate::Shared::create_anon().unwrap();
+        match unsafe { libc::fork() } {
+            0 => {
+                std::thread::sleep(Duration::from_millis(10));
+                futex.store(1, Relaxed);
+                futex.wake_all();
+                unsafe { libc::_exit(0) }
+            }
+            pid => {
+                assert!(pid > 0);
+                while futex.load(Relaxed) == 0 {
+                    futex.wait_timeout(0, Duration::from_secs(5));
+                }
+                unsafe { libc::waitpid(pid, core::ptr::null_mut(), 0) };
+            }
+        }
+        assert!(!futex.wait_until(1, Instant::now()));
+    }
 }
@@ -0,0 +1,90 @@
+use {
+    crate::ordering::{Acquire, Relaxed, Release},
+    core::sync::atomic::AtomicU32,
+};
+
+/// Set in the gate once closed
+const CLOSED: u32 = 1 << 31;
+
+/// Counts the operations in progress on a queue (ex: sends), which may be closed to new ones.
+///
+/// Entering checks the closed bit and counts the operation in one atomic step, so once
+/// [`Gate::drain`] returns no operation is in progress or can start. An operation whose process
+/// dies before leaving stalls the drain.
+pub(crate) struct Gate(AtomicU32);
+
+/// An operation in progress, which leaves the gate when dropped
+pub(crate) struct Pass<'a>(&'a Gate);
+
+impl Gate {
+    pub(crate) const fn new() -> Self {
+        Self(AtomicU32::new(0))
+    }
+
+    /// Enters unless the gate is closed.
+    pub(crate) fn enter(&self) -> Option<Pass<'_>> {
+        // Leaves again when dropped if closed
+        let pass = Pass(self);
+        (self.0.fetch_add(1, Acquire) & CLOSED == 0).then_some(pass)
+    }
+
+    pub(crate) fn close(&self) {
+        self.0.fetch_or(CLOSED, Relaxed);
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.0.load(Relaxed) & CLOSED != 0
+    }
+
+    /// Closes the gate and waits until the operations in progress have left.
+    pub(crate) fn drain(&self) {
+        self.close();
+        loop {
+            match self.0.load(Acquire) {
+                CLOSED => return,
+                gate => crate::futex::wait(&self.0, gate),
+            }
+        }
+    }
+}
+
+impl Default for Gate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Pass<'_> {
+    fn drop(&mut self) {
+        if self.0 .0.fetch_sub(1, Release) == CLOSED | 1 {
+            crate::futex::wake_all(&self.0 .0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{sync::atomic::AtomicBool, thread, time::Duration},
+    };
+
+    #[test]
+    fn drain() {
+        let gate = Gate::new();
+        let left = AtomicBool::new(false);
+        thread::scope(|s| {
+            let pass = gate.enter().unwrap();
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                left.store(true, Relaxed);
+                drop(pass);
+            });
+            gate.drain();
+            assert!(left.load(Relaxed));
+        });
+        assert!(gate.is_closed());
+        assert!(gate.enter().is_none());
+        gate.drain();
+    }
+}
@@ -0,0 +1,64 @@
+use {
+    crate::{
+        rwlock::{ReadGuard, WriteGuard},
+        Result, RwLock, Shareable, Shared,
+    },
+    std::ffi::CStr,
+};
+
+#[derive(Default)]
+struct Region<T> {
+    lock: RwLock<T>,
+}
+
+unsafe impl<T: Shareable + Send> Shareable for Region<T> {}
+
+/// A shared object which (unlike [`Shared`], which derefs to T) is only reachable through a
+/// region-wide reader-writer lock, so every access is synchronized.
+pub struct Guarded<T>(Shared<Region<T>>);
+
+impl<T: Shareable + Send> Guarded<T> {
+    /// # Safety
+    ///
+    /// See [`Shared::create`].
+    pub unsafe fn create(name: &CStr) -> Result<Self> {
+        unsafe { Shared::create(name) }.map(Self)
+    }
+
+    /// # Safety
+    ///
+    /// The type T must match that used to create the Guarded<T> of the same name. See also
+    /// [`Shared::open`].
+    pub unsafe fn open(name: &CStr) -> Result<Self> {
+        unsafe { Shared::open(name) }.map(Self)
+    }
+
+    pub fn read(&self) -> ReadGuard<'_, T> {
+        self.0.lock.read()
+    }
+
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        self.0.lock.write()
+    }
+
+    /// Calls `f` while holding the read lock.
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        f(&self.read())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::AtomicF64, std::sync::atomic::Ordering::Relaxed};
+
+    #[test]
+    fn guarded() {
+        let name = c"/guarded";
+        let master = unsafe { Guarded::<AtomicF64>::create(name).unwrap() };
+        let client = unsafe { Guarded::<AtomicF64>::open(name).unwrap() };
+
+        *master.write() = AtomicF64::new(1.5);
+        assert_eq!(client.with(|value| value.load(Relaxed)), 1.5);
+        assert_eq!(client.read().load(Relaxed), 1.5);
+    }
+}
@@ -0,0 +1,436 @@
+use {
+    crate::{
+        align_up,
+        lock_table::fnv1a,
+        ordering::{Acquire, Relaxed, Release},
+        pid_alive, Error, Result, Shareable, ShmFd,
+    },
+    std::{
+        any::type_name,
+        cell::UnsafeCell,
+        ffi::{CStr, CString},
+        hash::{BuildHasher, RandomState},
+        io,
+        mem::{align_of, size_of, MaybeUninit},
+        num::NonZeroUsize,
+        ptr::addr_of_mut,
+        sync::atomic::AtomicU32,
+        time::{Duration, Instant},
+    },
+};
+
+/// Identifies regions created by this crate ("shm-rust" in big endian)
+const MAGIC: u64 = u64::from_be_bytes(*b"shm-rust");
+
+/// Incremented whenever the header's layout changes
+pub(crate) const VERSION: u32 = 7;
+
+/// The header state once the region is initialized (creators store their pid while initializing)
+const READY: u32 = u32::MAX;
+
+/// The redirect states (see [`Header::redirect`])
+const MOVING: u32 = 1;
+const MOVED: u32 = 2;
+
+/// The capacity of the redirect, which holds a region name (at most NAME_MAX bytes) and a NUL
+const MOVED_TO_LEN: usize = 256;
+
+/// How often openers check that a region's creator is still alive while waiting for it
+const CREATOR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The crate version recorded by creators, truncated to the header's field
+const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Prepended to every region created by [`crate::Shared`] (or [`crate::SharedSlice`]), so `open`
+/// rejects regions created by other software (or another version of the header) rather than
+/// reinterpreting them as a T.
+///
+/// Openers wait for the state to become READY before reading the rest of the region, which the
+/// creator initializes beforehand.
+#[repr(C)]
+pub(crate) struct Header {
+    /// 0 until the creator claims the region, then its pid until initialized, then READY
+    state: AtomicU32,
+    /// Set by handles which detach with [`crate::Lifecycle::UnlinkOnLastDetach`] while others
+    /// remain attached, handing the unlink to the last process to detach
+    orphaned: AtomicU32,
+    info: Info,
+    /// 0 until the region is migrated, MOVING while the redirect is written, then MOVED
+    moved: AtomicU32,
+    /// The NUL-terminated name of the region this one migrated to, once MOVED
+    moved_to: UnsafeCell<[u8; MOVED_TO_LEN]>,
+}
+
+/// The header fields written by the creator before the region is READY
+#[repr(C)]
+struct Info {
+    magic: u64,
+    /// The size of the payload in bytes
+    size: u64,
+    version: u32,
+    /// See [`Shareable::fingerprint`]
+    fingerprint: u64,
+    /// See [`crate::Shared::generation`]
+    generation: u64,
+    creator_pid: u32,
+    creator_uid: u32,
+    /// The creation time in nanoseconds since boot (CLOCK_BOOTTIME)
+    created_since_boot: u64,
+    /// The creator's crate version (NUL padded)
+    crate_version: [u8; 16],
+}
+
+/// The process which created a region (see [`crate::Shared::creator`]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Creator {
+    pub pid: u32,
+    pub uid: u32,
+    /// When the region was created, relative to boot (CLOCK_BOOTTIME)
+    pub created_since_boot: Duration,
+    /// The version of this crate used by the creator
+    pub crate_version: String,
+}
+
+/// A region's header, from [`crate::inspect`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RegionInfo {
+    /// The size of the payload in bytes
+    pub size: u64,
+    /// The offset of the payload from the start of the region
+    pub payload_offset: u64,
+    /// See [`crate::Shared::generation`]
+    pub generation: u64,
+    pub creator: Creator,
+    /// The number of handles attached to the region (from every process)
+    pub attached: usize,
+}
+
+impl Creator {
+    /// Returns false once the creator has exited (the pid may since have been reused).
+    pub fn is_alive(&self) -> bool {
+        pid_alive(self.pid)
+    }
+}
+
+impl Header {
+    /// Initializes the header of a new region at `header` whose payload is `count` T's (1 unless
+    /// it's a [`crate::SharedSlice`]), and the payload with `init_payload`.
+    ///
+    /// # Safety
+    ///
+    /// `header` must be valid for writes and the region (that of `fd`) zeroed (as after
+    /// ftruncate). The caller holds `fd`'s initialization lock, which is released once the region
+    /// is ready.
+    pub(crate) unsafe fn initialize<T: Shareable>(
+        header: *mut Self,
+        fd: &ShmFd,
+        count: usize,
+        init_payload: impl FnOnce(),
+    ) -> Result<()> {
+        let state = unsafe { &(*header).state };
+        state.store(std::process::id(), Relaxed);
+        let info = Info::new::<T>(count, fd.incarnation());
+        unsafe { addr_of_mut!((*header).info).write(info) };
+        init_payload();
+        state.store(READY, Release);
+        crate::futex::wake_all(state);
+        fd.unlock_init();
+        Ok(())
+    }
+
+    /// The orphaned mark, which lives in the region so any process which can map it can set it.
+    pub(crate) fn orphaned(&self) -> &AtomicU32 {
+        &self.orphaned
+    }
+
+    /// Redirects openers to the region named `name`, which this one migrated to. Returns false if
+    /// the region was already redirected.
+    pub(crate) fn redirect(&self, name: &CStr) -> Result<bool> {
+        let name = name.to_bytes_with_nul();
+        if name.len() > MOVED_TO_LEN {
+            return Err(Error::Open(io::Error::from_raw_os_error(
+                libc::ENAMETOOLONG,
+            )));
+        }
+        if self
+            .moved
+            .compare_exchange(0, MOVING, Acquire, Relaxed)
+            .is_err()
+        {
+            return Ok(false);
+        }
+        // [SAFETY]: Claiming MOVING excludes other writers, and readers wait for MOVED.
+        unsafe {
+            self.moved_to
+                .get()
+                .cast::<u8>()
+                .copy_from_nonoverlapping(name.as_ptr(), name.len())
+        };
+        self.moved.store(MOVED, Release);
+        crate::futex::wake_all(&self.moved);
+        Ok(true)
+    }
+
+    /// The name of the region this one migrated to, waiting up to `timeout` (None waits
+    /// indefinitely) for a migration. Returns None if the region hasn't migrated.
+    pub(crate) fn moved_to(&self, timeout: Option<Duration>) -> Option<CString> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            match self.moved.load(Acquire) {
+                // [SAFETY]: The redirect is immutable once MOVED.
+                MOVED => {
+                    let name = unsafe { &*self.moved_to.get() };
+                    return CStr::from_bytes_until_nul(name).ok().map(CStr::to_owned);
+                }
+                moved if deadline.is_none_or(|d| Instant::now() < d) => {
+                    crate::futex::wait_until(&self.moved, moved, deadline);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    /// Waits for the creator to finish initializing the region of `fd`.
+    ///
+    /// Returns [`Error::Uninitialized`] if no creator has claimed the region (ex: it's being
+    /// sized, or wasn't created by this crate) or the creator exited during initialization.
+    pub(crate) fn wait_ready(&self, fd: &ShmFd) -> Result<()> {
+        loop {
+            match self.state.load(Acquire) {
+                READY => return Ok(()),
+                0 => return Err(Error::Uninitialized),
+                // The creator's pid may be from another pid namespace, so its lock on the region
+                // tells whether it's still initializing (falling back to the pid if unsupported).
+                pid if !fd.initializing().unwrap_or_else(|_| pid_alive(pid)) => {
+                    // The creator may have finished just before releasing its lock
+                    return match self.state.load(Acquire) {
+                        READY => Ok(()),
+                        _ => Err(Error::Uninitialized),
+                    };
+                }
+                pid => {
+                    crate::futex::wait_timeout(&self.state, pid, Some(CREATOR_POLL_INTERVAL));
+                }
+            }
+        }
+    }
+
+    pub(crate) fn creator(&self) -> Creator {
+        self.info.creator()
+    }
+
+    /// Describes a region of `len` bytes, which may be uninitialized or foreign.
+    pub(crate) fn info(&self, len: u64) -> Result<RegionInfo> {
+        if self.state.load(Acquire) != READY {
+            return Err(Error::Uninitialized);
+        }
+        let info = &self.info;
+        if info.magic != MAGIC {
+            return Err(Error::MagicMismatch);
+        }
+        if info.version != VERSION {
+            return Err(Error::VersionMismatch(info.version));
+        }
+        Ok(RegionInfo {
+            size: info.size,
+            payload_offset: len.saturating_sub(info.size),
+            generation: info.generation,
+            creator: info.creator(),
+            attached: 0,
+        })
+    }
+
+    pub(crate) fn generation(&self) -> u64 {
+        self.info.generation
+    }
+
+    /// Checks the payload is `count` T's. Must only be called once the region is ready (see
+    /// [`Self::wait_ready`]).
+    pub(crate) fn validate<T: Shareable>(&self, count: usize) -> Result<()> {
+        self.info.validate::<T>(count)
+    }
+}
+
+impl Info {
+    fn new<T: Shareable>(count: usize, incarnation: u32) -> Self {
+        Self {
+            magic: MAGIC,
+            size: (count * size_of::<T>()) as u64,
+            version: VERSION,
+            fingerprint: T::fingerprint(),
+            generation: u64::from(incarnation) << 32
+                | u64::from(RandomState::new().hash_one(MAGIC) as u32),
+            creator_pid: std::process::id(),
+            creator_uid: unsafe { libc::getuid() },
+            created_since_boot: since_boot().as_nanos() as u64,
+            crate_version: {
+                let mut version = [0; 16];
+                let len = CRATE_VERSION.len().min(version.len());
+                version[..len].copy_from_slice(&CRATE_VERSION.as_bytes()[..len]);
+                version
+            },
+        }
+    }
+
+    fn creator(&self) -> Creator {
+        let version = self.crate_version.split(|&b| b == 0).next().unwrap_or(&[]);
+        Creator {
+            pid: self.creator_pid,
+            uid: self.creator_uid,
+            created_since_boot: Duration::from_nanos(self.created_since_boot),
+            crate_version: String::from_utf8_lossy(version).into(),
+        }
+    }
+
+    fn validate<T: Shareable>(&self, count: usize) -> Result<()> {
+        if self.magic != MAGIC {
+            return Err(Error::MagicMismatch);
+        }
+        if self.version != VERSION {
+            return Err(Error::VersionMismatch(self.version));
+        }
+        if self.size != (count * size_of::<T>()) as u64 {
+            return Err(Error::LengthMismatch);
+        }
+        if self.fingerprint != T::fingerprint() {
+            return Err(Error::LayoutMismatch);
+        }
+        Ok(())
+    }
+}
+
+fn since_boot() -> Duration {
+    let mut ts = MaybeUninit::uninit();
+    assert_eq!(
+        unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, ts.as_mut_ptr()) },
+        0
+    );
+    let ts = unsafe { ts.assume_init() };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// Hashes the name, size and alignment of T along with the given field offsets (ex: from
+/// [`core::mem::offset_of`]).
+///
+/// Type names aren't guaranteed to be stable across compiler versions, so processes sharing a
+/// region should be built with the same toolchain.
+pub fn layout_fingerprint<T>(field_offsets: &[usize]) -> u64 {
+    let mut key = Vec::from(type_name::<T>());
+    for n in [size_of::<T>(), align_of::<T>()]
+        .iter()
+        .chain(field_offsets)
+    {
+        key.extend((*n as u64).to_le_bytes());
+    }
+    fnv1a(&key)
+}
+
+/// The offset of the payload (a T) from the start of the region.
+pub(crate) fn payload_offset<T>() -> usize {
+    align_up(
+        size_of::<Header>(),
+        align_of::<T>().max(align_of::<Header>()),
+    )
+    .unwrap()
+}
+
+/// The length of a region holding a T.
+pub(crate) fn region_len<T>() -> NonZeroUsize {
+    NonZeroUsize::new(payload_offset::<T>() + size_of::<T>()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{AtomicF64, Shared},
+    };
+
+    #[test]
+    fn rejects_foreign_regions() {
+        let name = c"/header";
+        let shared = unsafe { Shared::<AtomicF64>::create(name).unwrap() };
+        let header = shared.as_ptr().cast::<Header>();
+
+        unsafe { (*header).info.version = VERSION + 1 };
+        assert!(matches!(
+            unsafe { Shared::<AtomicF64>::open(name) },
+            Err(Error::VersionMismatch(v)) if v == VERSION + 1
+        ));
+
+        // Same length, but not written by this crate
+        unsafe { (*header).info.magic = 0 };
+        assert!(matches!(
+            unsafe { Shared::<AtomicF64>::open(name) },
+            Err(Error::MagicMismatch)
+        ));
+
+        // A creator which exited before initializing the region
+        unsafe { (*header).state.store(i32::MAX as u32, Relaxed) };
+        assert!(matches!(
+            unsafe { Shared::<AtomicF64>::open(name) },
+            Err(Error::Uninitialized)
+        ));
+    }
+
+    #[test]
+    fn open_waits_for_init() {
+        static STARTED: AtomicU32 = AtomicU32::new(0);
+
+        #[derive(Default)]
+        struct Slow(AtomicF64);
+
+        unsafe impl Shareable for Slow {
+            unsafe fn init_in_place(ptr: *mut Self) {
+                // A pid meaningless to openers, as for a creator in another pid namespace
+                let header = unsafe { ptr.cast::<u8>().sub(payload_offset::<Self>()) };
+                let header = unsafe { &*header.cast::<Header>() };
+                header.state.store(i32::MAX as u32, Relaxed);
+                STARTED.store(1, Release);
+                std::thread::sleep(Duration::from_millis(50));
+                unsafe { ptr.write(Slow(AtomicF64::new(1.5))) };
+            }
+        }
+
+        let name = c"/open_waits_for_init";
+        std::thread::scope(|s| {
+            let creator = s.spawn(|| unsafe { Shared::<Slow>::create(name) }.unwrap());
+            while STARTED.load(Acquire) == 0 {
+                std::thread::yield_now();
+            }
+            let opened = unsafe { Shared::<Slow>::open(name) }.unwrap();
+            assert_eq!((*opened).0.load(Relaxed), 1.5);
+            drop(creator.join().unwrap());
+        });
+    }
+
+    #[test]
+    fn inspect() {
+        let name = c"/inspect";
+        let shared = unsafe { Shared::<AtomicF64>::create(name).unwrap() };
+        let info = crate::inspect(name).unwrap();
+        assert_eq!(info.size, 8);
+        assert_eq!(info.payload_offset, payload_offset::<AtomicF64>() as u64);
+        assert_eq!(info.generation, shared.generation().unwrap());
+        assert_eq!(info.creator.pid, std::process::id());
+        assert_eq!(info.attached, 1);
+    }
+
+    #[test]
+    fn layout_mismatch() {
+        #[derive(Default)]
+        struct Other {
+            _value: u64,
+        }
+
+        unsafe impl Shareable for Other {}
+
+        // The same size and alignment as AtomicF64
+        let name = c"/layout_mismatch";
+        let _shared = unsafe { Shared::<AtomicF64>::create(name).unwrap() };
+        assert!(matches!(
+            unsafe { Shared::<Other>::open(name) },
+            Err(Error::LayoutMismatch)
+        ));
+    }
+}
@@ -0,0 +1,553 @@
+//! The in-process backend selected by `RUSTFLAGS="--cfg shm_heap"`, so code using
+//! [`crate::Shared`] can be unit tested without /dev/shm or futex syscalls. It's a cfg rather
+//! than a feature as it replaces the shared memory backend for the whole build.
+//!
+//! Named regions are heap allocations registered in a process-wide table, so they're only shared
+//! between the threads of a process. Each region's descriptor is an empty memfd (or the file of a
+//! file-backed region) which identifies it to [`crate::Shared::from_fd`] within the process, so
+//! another process given the descriptor finds an empty region. File-backed regions aren't written
+//! to their file, and live as long as the file is linked or mapped. Read-only mappings (see
+//! [`crate::SharedRead`]) aren't protected from writes.
+//!
+//! Futex words are parked on in a table of std Condvars, which signal handlers don't interrupt
+//! (so `*_interruptible` waits only return once woken). Memory hints (ex:
+//! [`crate::Shared::advise`]) succeed without effect.
+
+use {
+    crate::{
+        futex::{Interrupted, Word},
+        header::Header,
+        is_aligned, monotonic_now,
+        ordering::Relaxed,
+        page_size, Error, Lifecycle, RegionInfo, Result, Stat,
+    },
+    std::{
+        alloc::{self, Layout},
+        collections::{btree_map::Entry, BTreeMap},
+        ffi::{c_void, CStr, CString},
+        io,
+        mem::{size_of, MaybeUninit},
+        num::NonZeroUsize,
+        os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd},
+        ptr::NonNull,
+        sync::{
+            atomic::{AtomicBool, AtomicU32},
+            Arc, Condvar, Mutex, MutexGuard, OnceLock, PoisonError, Weak,
+        },
+        time::{Duration, SystemTime},
+    },
+};
+
+/// The linked regions by name
+static REGIONS: Mutex<BTreeMap<CString, Arc<Memory>>> = Mutex::new(BTreeMap::new());
+
+fn regions() -> MutexGuard<'static, BTreeMap<CString, Arc<Memory>>> {
+    REGIONS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The regions created under each name (see [`ShmFd::incarnation`])
+static INCARNATIONS: Mutex<BTreeMap<CString, u32>> = Mutex::new(BTreeMap::new());
+
+/// Every region by the device and inode of its descriptor
+static DESCRIPTORS: Mutex<BTreeMap<(u64, u64), Registered>> = Mutex::new(BTreeMap::new());
+
+enum Registered {
+    Memfd(Weak<Memory>),
+    /// Kept while the file is linked, as its contents would be
+    File(Arc<Memory>),
+}
+
+impl Registered {
+    fn upgrade(&self) -> Option<Arc<Memory>> {
+        match self {
+            Self::Memfd(memory) => memory.upgrade(),
+            Self::File(memory) => Some(memory.clone()),
+        }
+    }
+
+    fn is_live(&self) -> bool {
+        match self {
+            Self::Memfd(memory) => memory.strong_count() > 0,
+            Self::File(memory) => fstat(&memory.fd).is_ok_and(|stat| stat.st_nlink > 0),
+        }
+    }
+}
+
+/// A region, which is freed once it's unlinked and every handle has detached.
+struct Memory {
+    fd: OwnedFd,
+    allocation: OnceLock<Allocation>,
+    /// Set once a handle with [`Lifecycle::UnlinkOnLastDetach`] has detached
+    orphaned: AtomicBool,
+    /// Set while the creator initializes the region (handles share the descriptor, so can't
+    /// tell a lock on it apart)
+    initializing: AtomicBool,
+    created: SystemTime,
+}
+
+struct Allocation {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+unsafe impl Send for Allocation {}
+unsafe impl Sync for Allocation {}
+
+impl Drop for Allocation {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) };
+    }
+}
+
+impl Memory {
+    fn new() -> io::Result<Arc<Self>> {
+        match unsafe { libc::memfd_create(c"shm".as_ptr(), libc::MFD_CLOEXEC) } {
+            fd if fd >= 0 => Ok(Self::of(unsafe { OwnedFd::from_raw_fd(fd) })),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// The region identified by `fd`, which is registered if it's new.
+    fn of(fd: OwnedFd) -> Arc<Self> {
+        let Ok(stat) = fstat(&fd) else {
+            return Arc::new(Self::with_fd(fd));
+        };
+        let mut descriptors = DESCRIPTORS.lock().unwrap_or_else(PoisonError::into_inner);
+        // The inode of an unlinked file may be reused by another.
+        descriptors.retain(|_, registered| registered.is_live());
+        let key = (stat.st_dev, stat.st_ino);
+        if let Some(memory) = descriptors.get(&key).and_then(Registered::upgrade) {
+            return memory;
+        }
+        let memory = Arc::new(Self::with_fd(fd));
+        let registered = match stat.st_nlink {
+            0 => Registered::Memfd(Arc::downgrade(&memory)),
+            _ => Registered::File(memory.clone()),
+        };
+        descriptors.insert(key, registered);
+        memory
+    }
+
+    fn with_fd(fd: OwnedFd) -> Self {
+        Self {
+            fd,
+            allocation: OnceLock::new(),
+            orphaned: AtomicBool::new(false),
+            initializing: AtomicBool::new(false),
+            created: SystemTime::now(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.allocation.get().map_or(0, |a| a.layout.size())
+    }
+
+    fn stat(&self, linked: bool) -> Stat {
+        Stat {
+            size: self.len() as u64,
+            dev: 0,
+            ino: self as *const Self as u64,
+            linked,
+            modified: self.created,
+            changed: self.created,
+        }
+    }
+}
+
+fn fstat(fd: &impl AsRawFd) -> io::Result<libc::stat> {
+    let mut stat = MaybeUninit::uninit();
+    match unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } {
+        0 => Ok(unsafe { stat.assume_init() }),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Whether `name` refers to `memory`.
+fn is_linked(regions: &BTreeMap<CString, Arc<Memory>>, name: &CStr, memory: &Arc<Memory>) -> bool {
+    regions.get(name).is_some_and(|m| Arc::ptr_eq(m, memory))
+}
+
+/// The heap counterpart of the region descriptor, one per handle.
+pub(crate) struct ShmFd {
+    /// None for anonymous regions
+    pub(crate) name: Option<Box<CStr>>,
+    pub(crate) lifecycle: Lifecycle,
+    memory: Arc<Memory>,
+}
+
+impl Drop for ShmFd {
+    fn drop(&mut self) {
+        self.detach(None);
+    }
+}
+
+impl ShmFd {
+    /// Applies the lifecycle to the region's name, once. The mark lives in the [`Memory`], so
+    /// the header's is unused.
+    pub(crate) fn detach(&mut self, _orphaned: Option<&AtomicU32>) {
+        let Some(name) = self.name.take() else {
+            return;
+        };
+        let mut regions = regions();
+        // Only the table and this handle remain
+        let last = is_linked(&regions, &name, &self.memory) && Arc::strong_count(&self.memory) == 2;
+        let unlink = match self.lifecycle {
+            Lifecycle::UnlinkOnDrop => true,
+            Lifecycle::UnlinkOnLastDetach => {
+                self.memory.orphaned.store(true, Relaxed);
+                last
+            }
+            Lifecycle::Manual => last && self.memory.orphaned.load(Relaxed),
+        };
+        if unlink {
+            regions.remove(&*name);
+        }
+    }
+}
+
+impl ShmFd {
+    pub(crate) fn create(name: &CStr) -> io::Result<Self> {
+        Self::create_with_mode(name, crate::DEFAULT_MODE, None)
+    }
+
+    /// Permissions don't apply within a process, so `mode` and `group` are ignored.
+    pub(crate) fn create_with_mode(
+        name: &CStr,
+        _mode: libc::mode_t,
+        _group: Option<libc::gid_t>,
+    ) -> io::Result<Self> {
+        match regions().entry(name.into()) {
+            Entry::Occupied(_) => Err(io::ErrorKind::AlreadyExists.into()),
+            Entry::Vacant(entry) => Ok(Self {
+                name: Some(name.into()),
+                lifecycle: Lifecycle::UnlinkOnDrop,
+                memory: entry.insert(Memory::new()?).clone(),
+            }),
+        }
+    }
+
+    pub(crate) fn open(name: &CStr) -> io::Result<Self> {
+        let memory = regions().get(name).cloned();
+        Ok(Self {
+            name: Some(name.into()),
+            lifecycle: Lifecycle::Manual,
+            memory: memory.ok_or(io::ErrorKind::NotFound)?,
+        })
+    }
+
+    /// Like `open`, but the handle doesn't take part in unlinking the region.
+    pub(crate) fn open_read_only(name: &CStr) -> io::Result<Self> {
+        let memory = regions().get(name).cloned();
+        Ok(Self {
+            name: None,
+            lifecycle: Lifecycle::Manual,
+            memory: memory.ok_or(io::ErrorKind::NotFound)?,
+        })
+    }
+
+    /// Counts the regions created under this one's name, including this region. 0 for unnamed
+    /// regions.
+    pub(crate) fn incarnation(&self) -> u32 {
+        let Some(name) = &self.name else {
+            return 0;
+        };
+        let mut incarnations = INCARNATIONS.lock().unwrap_or_else(PoisonError::into_inner);
+        let count = incarnations.entry(name.as_ref().into()).or_default();
+        *count = count.wrapping_add(1);
+        *count
+    }
+
+    /// Regions are only sized once, so there's nothing to seal.
+    pub(crate) fn seal(&self) {}
+
+    pub(crate) fn anon() -> io::Result<Self> {
+        Ok(Self {
+            name: None,
+            lifecycle: Lifecycle::Manual,
+            memory: Memory::new()?,
+        })
+    }
+
+    pub(crate) fn from_fd(fd: OwnedFd) -> Self {
+        Self {
+            name: None,
+            lifecycle: Lifecycle::Manual,
+            memory: Memory::of(fd),
+        }
+    }
+
+    /// Allocates the (zeroed) region, which may only be sized once.
+    pub(crate) fn resize(&self, len: NonZeroUsize) -> Result<()> {
+        let layout = Layout::from_size_align(len.get(), page_size())
+            .map_err(|e| Error::Resize(io::Error::new(io::ErrorKind::InvalidInput, e)))?;
+        let ptr = NonNull::new(unsafe { alloc::alloc_zeroed(layout) })
+            .ok_or_else(|| Error::Resize(io::ErrorKind::OutOfMemory.into()))?;
+        self.memory
+            .allocation
+            .set(Allocation { ptr, layout })
+            .map_err(|_| {
+                Error::Resize(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "region already sized",
+                ))
+            })
+    }
+
+    pub(crate) fn len(&self) -> Option<usize> {
+        Some(self.memory.len())
+    }
+
+    pub(crate) fn map(&self, len: NonZeroUsize, align: usize) -> Result<*mut c_void> {
+        match self.memory.allocation.get() {
+            Some(a) if a.layout.size() < len.get() => Err(Error::Mmap(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "mapping exceeds the region",
+            ))),
+            Some(a) if !is_aligned(a.ptr.as_ptr() as usize, align) => Err(Error::AlignmentMismatch),
+            Some(a) => {
+                let ptr = a.ptr.as_ptr().cast();
+                crate::owner::mapped(ptr, len.get(), self.as_fd());
+                Ok(ptr)
+            }
+            None => Err(Error::Mmap(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "region not sized",
+            ))),
+        }
+    }
+
+    /// Writes aren't prevented, so this is the same as `map`.
+    pub(crate) fn map_read_only(&self, len: NonZeroUsize, align: usize) -> Result<*mut c_void> {
+        self.map(len, align)
+    }
+
+    pub(crate) fn lock_init(&self) -> io::Result<()> {
+        self.memory.initializing.store(true, Relaxed);
+        Ok(())
+    }
+
+    pub(crate) fn unlock_init(&self) {
+        self.memory.initializing.store(false, Relaxed);
+    }
+
+    pub(crate) fn initializing(&self) -> io::Result<bool> {
+        Ok(self.memory.initializing.load(Relaxed))
+    }
+
+    pub(crate) fn wait_initialized(&self) -> io::Result<()> {
+        while self.initializing()? {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        Ok(())
+    }
+
+    /// Counts the handles (including this one) attached to the named region.
+    pub(crate) fn attached(&self) -> io::Result<usize> {
+        let Some(name) = &self.name else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only named regions track attachment",
+            ));
+        };
+        let regions = regions();
+        let table = usize::from(is_linked(&regions, name, &self.memory));
+        Ok(Arc::strong_count(&self.memory) - table)
+    }
+
+    pub(crate) fn describe(&self) -> io::Result<Stat> {
+        let linked = self
+            .name
+            .as_ref()
+            .is_some_and(|name| is_linked(&regions(), name, &self.memory));
+        Ok(self.memory.stat(linked))
+    }
+}
+
+impl AsFd for ShmFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.memory.fd.as_fd()
+    }
+}
+
+// Regions aren't backed by files and are freed with their memory, so there's nothing to write
+// back or unmap (beyond releasing this process's id in the region).
+
+pub(crate) fn msync(_: *mut c_void, _: usize) -> io::Result<()> {
+    Ok(())
+}
+
+pub(crate) fn msync_mode(_: usize, _: usize, _: crate::SyncMode) -> io::Result<()> {
+    Ok(())
+}
+
+pub(crate) fn unmap(ptr: *mut c_void, _: usize) {
+    crate::owner::unmapped(ptr);
+}
+
+pub(crate) fn unlink(name: &CStr) -> io::Result<()> {
+    match regions().remove(name) {
+        Some(_) => Ok(()),
+        None => Err(io::ErrorKind::NotFound.into()),
+    }
+}
+
+pub(crate) fn exists(name: &CStr) -> bool {
+    regions().contains_key(name)
+}
+
+pub(crate) fn metadata(name: &CStr) -> io::Result<Stat> {
+    match regions().get(name) {
+        Some(memory) => Ok(memory.stat(true)),
+        None => Err(io::ErrorKind::NotFound.into()),
+    }
+}
+
+pub(crate) fn inspect(name: &CStr) -> Result<RegionInfo> {
+    let memory = regions().get(name).cloned();
+    let memory = memory.ok_or_else(|| Error::Open(io::ErrorKind::NotFound.into()))?;
+    let Some(allocation) = memory.allocation.get() else {
+        return Err(Error::MagicMismatch);
+    };
+    if allocation.layout.size() < size_of::<Header>() {
+        return Err(Error::MagicMismatch);
+    }
+    // [SAFETY]: The allocation spans a Header, and is only read atomically until READY.
+    let header = unsafe { &*allocation.ptr.as_ptr().cast::<Header>() };
+    Ok(RegionInfo {
+        // Excluding the table and this reference
+        attached: Arc::strong_count(&memory) - 2,
+        ..header.info(allocation.layout.size() as u64)?
+    })
+}
+
+///////////////////////////////////////////////////////////////////////////////
+
+/// Parked threads, by the address of the word they wait on (hashed into buckets)
+struct Bucket {
+    waiting: Mutex<Vec<usize>>,
+    condvar: Condvar,
+}
+
+static BUCKETS: [Bucket; 64] = [const {
+    Bucket {
+        waiting: Mutex::new(Vec::new()),
+        condvar: Condvar::new(),
+    }
+}; 64];
+
+fn bucket(addr: usize) -> &'static Bucket {
+    // Words are 4 byte aligned
+    &BUCKETS[(addr >> 2) % BUCKETS.len()]
+}
+
+// Parks on the table above rather than calling futex(2).
+impl Word for AtomicU32 {
+    fn wait(
+        &self,
+        expected: u32,
+        deadline: Option<&libc::timespec>,
+    ) -> std::result::Result<bool, Interrupted> {
+        let addr = self as *const Self as usize;
+        let bucket = bucket(addr);
+        let mut waiting = bucket
+            .waiting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        // Wakers change the value before locking the bucket, so the wake can't be missed.
+        if self.load(Relaxed) != expected {
+            return Ok(true);
+        }
+        waiting.push(addr);
+        let mut woken = true;
+        waiting = match deadline {
+            None => bucket
+                .condvar
+                .wait(waiting)
+                .unwrap_or_else(PoisonError::into_inner),
+            Some(ts) => {
+                let deadline = Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32);
+                let timeout = deadline.saturating_sub(monotonic_now());
+                let (waiting, result) = bucket
+                    .condvar
+                    .wait_timeout(waiting, timeout)
+                    .unwrap_or_else(PoisonError::into_inner);
+                woken = !result.timed_out();
+                waiting
+            }
+        };
+        if let Some(i) = waiting.iter().position(|&w| w == addr) {
+            waiting.swap_remove(i);
+        }
+        Ok(woken)
+    }
+
+    /// Wakes every thread in the bucket (the others spuriously), returning the number parked on
+    /// this word, up to `n`.
+    fn wake(&self, n: i32) -> usize {
+        let addr = self as *const Self as usize;
+        let bucket = bucket(addr);
+        let waiting = bucket
+            .waiting
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let parked = waiting.iter().filter(|&&w| w == addr).count();
+        if parked > 0 {
+            bucket.condvar.notify_all();
+        }
+        parked.min(usize::try_from(n).unwrap_or(0))
+    }
+
+    fn requeue(&self, _: u32, _: i32, _: &Self) -> Option<usize> {
+        None
+    }
+}
+
+/// Like the futex backend's, but polls: a pool thread parked on the word could outlive the
+/// region, which is freed once its last handle is dropped.
+#[cfg(feature = "async")]
+pub(crate) async fn wait_async(a: &AtomicU32, expected: u32) {
+    const ASYNC_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+    if a.load(Relaxed) == expected {
+        let _ = tokio::task::spawn_blocking(|| std::thread::sleep(ASYNC_POLL_INTERVAL)).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{exists, inspect, metadata, Mutex, Shareable, Shared},
+        std::{thread, time::Duration},
+    };
+
+    #[derive(Default)]
+    struct Counter {
+        count: Mutex<u32>,
+    }
+
+    unsafe impl Shareable for Counter {}
+
+    #[test]
+    fn in_process() {
+        let name = c"/heap";
+        let created = unsafe { Shared::<Counter>::create(name).unwrap() };
+        let opened = unsafe { Shared::<Counter>::open(name).unwrap() };
+        assert!(metadata(c"/heap_missing").is_err());
+        assert_eq!(inspect(name).unwrap().attached, 2);
+
+        thread::scope(|s| {
+            let guard = created.count.lock();
+            s.spawn(|| *opened.count.lock() += 1);
+            thread::sleep(Duration::from_millis(10));
+            drop(guard);
+        });
+        assert_eq!(*created.count.lock(), 1);
+        assert!(created.is_current().unwrap());
+
+        // The creator unlinks the region, which the opener keeps using.
+        drop(created);
+        assert!(!exists(name));
+        assert!(!opened.is_current().unwrap());
+        *opened.count.lock() += 1;
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    crate::{lock_table::fnv1a, monotonic_now, ordering::Relaxed, Mutex, Shareable},
+    core::{sync::atomic::AtomicU64, time::Duration},
+};
+
+const MAX_KEY_LEN: usize = 32;
+
+#[derive(Clone, Copy, Default)]
+struct Slot<V> {
+    /// Key length, 0 if the slot is unused
+    len: u8,
+    key: [u8; MAX_KEY_LEN],
+    value: V,
+    /// Expiry on the CLOCK_MONOTONIC timeline in nanoseconds, or 0 if the entry doesn't expire
+    expires: u64,
+    /// Cache-wide tick of the most recent use (for LRU eviction)
+    used: u64,
+}
+
+impl<V> Slot<V> {
+    fn matches(&self, key: &str) -> bool {
+        self.len != 0 && &self.key[..usize::from(self.len)] == key.as_bytes()
+    }
+
+    fn expired(&self, now: u64) -> bool {
+        self.expires != 0 && self.expires <= now
+    }
+}
+
+/// A fixed capacity key-value cache with optional per-entry expiry and LRU eviction.
+///
+/// Keys hash to one of B buckets, each holding up to W entries behind its own lock. Inserting into
+/// a full bucket evicts an expired entry, or otherwise the least recently used.
+pub struct KvCache<V, const B: usize, const W: usize> {
+    tick: AtomicU64,
+    buckets: [Mutex<[Slot<V>; W]>; B],
+}
+
+unsafe impl<V: Shareable + Copy + Send, const B: usize, const W: usize> Shareable
+    for KvCache<V, B, W>
+{
+}
+
+impl<V: Copy + Default, const B: usize, const W: usize> Default for KvCache<V, B, W> {
+    fn default() -> Self {
+        Self {
+            tick: AtomicU64::new(0),
+            buckets: core::array::from_fn(|_| Mutex::new([Slot::default(); W])),
+        }
+    }
+}
+
+fn nanos(time: Duration) -> u64 {
+    u64::try_from(time.as_nanos()).unwrap_or(u64::MAX)
+}
+
+impl<V: Copy + Default, const B: usize, const W: usize> KvCache<V, B, W> {
+    /// The maximum length of a key in bytes.
+    pub const MAX_KEY_LEN: usize = MAX_KEY_LEN;
+
+    /// Returns the value of `key` unless it's missing or expired.
+    pub fn get(&self, key: &str) -> Option<V> {
+        let now = nanos(monotonic_now());
+        let mut bucket = self.bucket(key)?.lock();
+        let slot = bucket.iter_mut().find(|s| s.matches(key))?;
+        if slot.expired(now) {
+            *slot = Slot::default();
+            return None;
+        }
+        slot.used = self.tick.fetch_add(1, Relaxed);
+        Some(slot.value)
+    }
+
+    /// Inserts or replaces the value of `key`, expiring after `ttl` if given.
+    ///
+    /// Returns false if the key is longer than [`Self::MAX_KEY_LEN`] (or empty).
+    pub fn insert(&self, key: &str, value: V, ttl: Option<Duration>) -> bool {
+        let Some(bucket) = self.bucket(key) else {
+            return false;
+        };
+        let now = monotonic_now();
+        let expires = ttl.map_or(0, |ttl| nanos(now.saturating_add(ttl)).max(1));
+        let now = nanos(now);
+
+        let mut bucket = bucket.lock();
+        let slot = match bucket.iter().position(|s| s.matches(key)) {
+            Some(i) => &mut bucket[i],
+            None => bucket
+                .iter_mut()
+                .min_by_key(|s| (s.len != 0, !s.expired(now), s.used))
+                .expect("buckets hold at least one entry"),
+        };
+        let mut key_bytes = [0; MAX_KEY_LEN];
+        key_bytes[..key.len()].copy_from_slice(key.as_bytes());
+        *slot = Slot {
+            len: key.len() as u8,
+            key: key_bytes,
+            value,
+            expires,
+            used: self.tick.fetch_add(1, Relaxed),
+        };
+        true
+    }
+
+    /// Removes `key`, returning its value unless it was missing or expired.
+    pub fn remove(&self, key: &str) -> Option<V> {
+        let now = nanos(monotonic_now());
+        let mut bucket = self.bucket(key)?.lock();
+        let slot = bucket.iter_mut().find(|s| s.matches(key))?;
+        let removed = core::mem::take(slot);
+        (!removed.expired(now)).then_some(removed.value)
+    }
+
+    fn bucket(&self, key: &str) -> Option<&Mutex<[Slot<V>; W]>> {
+        const { assert!(B > 0 && W > 0, "the cache must hold at least one entry") };
+        (!key.is_empty() && key.len() <= MAX_KEY_LEN)
+            .then(|| &self.buckets[(fnv1a(key.as_bytes()) % B as u64) as usize])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry_and_eviction() {
+        // A single bucket of two entries
+        let cache = KvCache::<u64, 1, 2>::default();
+        assert!(cache.insert("a", 1, None));
+        assert!(cache.insert("b", 2, None));
+        assert_eq!(cache.get("a"), Some(1));
+
+        // "b" is least recently used
+        assert!(cache.insert("c", 3, None));
+        assert_eq!(cache.get("b"), None);
+
+        assert!(cache.insert("a", 4, Some(Duration::from_millis(10))));
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.remove("c"), Some(3));
+        assert!(!cache.insert(&"x".repeat(MAX_KEY_LEN + 1), 0, None));
+    }
+}
@@ -1,20 +1,149 @@
 #[cfg(target_os = "linux")]
 mod futex;
+#[cfg(target_os = "linux")]
+pub use futex::{Futex, Interrupted};
+mod header;
+pub use header::{layout_fingerprint, Creator, RegionInfo};
 
+mod atomic_float;
+pub use atomic_float::{AtomicF32, AtomicF64};
+mod atomic_u128;
+pub use atomic_u128::AtomicU128;
+mod atomic_watch;
+pub use atomic_watch::AtomicWatch;
+#[cfg(feature = "audit")]
+pub mod audit;
+mod builder;
+pub use builder::SharedBuilder;
+mod byte_log;
+pub use byte_log::{ByteLog, Collector, Record};
+mod capabilities;
+pub use capabilities::{capabilities, Capabilities};
+mod cancellation;
+pub use cancellation::{CancellationToken, CancellationTree};
+mod client_slots;
+pub use client_slots::{ClientSlot, ClientSlots};
+mod coalescing_condvar;
+pub use coalescing_condvar::CoalescingCondvar;
 mod condvar;
 pub use condvar::Condvar;
+#[cfg(feature = "diagnostics")]
+pub mod diagnostics;
+mod doorbell;
+pub use doorbell::Doorbell;
+mod event;
+pub use event::Event;
+#[cfg(feature = "fairness")]
+pub mod fairness;
+mod gate;
+mod guarded;
+pub use guarded::Guarded;
+#[cfg(shm_heap)]
+mod heap;
+#[cfg(shm_heap)]
+use heap::{msync, msync_mode, unmap, ShmFd};
+mod kv_cache;
+pub use kv_cache::KvCache;
+mod lock_table;
+pub use lock_table::LockTable;
+#[cfg(feature = "serde")]
+mod message_log;
+#[cfg(feature = "serde")]
+pub use message_log::{MessageLog, MessageReceiver};
+#[cfg(feature = "metrics")]
+pub mod metrics;
+mod mirror;
+pub use mirror::Mirror;
+mod monitor;
+pub use monitor::{Monitor, MonitorGuard};
+pub mod mpsc;
+mod namespace;
+pub use namespace::Namespace;
 mod mutex;
 pub use mutex::Mutex;
+mod numa;
+pub use numa::NumaPolicy;
+mod once;
+pub use once::{Once, OnceLock};
+mod oneshot;
+pub use oneshot::Oneshot;
+mod ordering;
+mod owner;
+mod page;
+pub use page::{align_up, is_aligned, page_size, round_up_to_page};
+mod pi_mutex;
+pub use pi_mutex::{PiMutex, PiMutexGuard};
+mod pipe;
+pub use pipe::{Pipe, PipeEnd};
+mod poison;
+pub use poison::{
+    LockResult, PoisonError, PoisonGuard, PoisonMutex, PoisonRwLock, PoisonWriteGuard,
+};
+mod project;
+pub use project::Project;
+mod registry;
+pub use registry::{Region, RegionSpec, Registry};
+mod resource_pool;
+pub use resource_pool::{Lease, ResourcePool};
+mod robust_mutex;
+pub use robust_mutex::{RobustMutex, RobustMutexGuard};
 mod rwlock;
-pub use rwlock::RwLock;
+pub use rwlock::{RwLock, UpgradableReadGuard};
+mod seqlock;
+pub use seqlock::{SeqLock, WriterDied};
+mod shared_deque;
+pub use shared_deque::SharedDeque;
+mod shared_lock;
+pub use shared_lock::SharedLock;
+mod shared_read;
+pub use shared_read::SharedRead;
+mod shared_slice;
+pub use shared_slice::SharedSlice;
+mod shm_hash_map;
+pub use shm_hash_map::ShmHashMap;
+mod shm_ptr;
+pub use shm_ptr::ShmPtr;
+mod shm_slab;
+pub use shm_slab::ShmSlab;
+mod shm_vec;
+pub use shm_vec::ShmVec;
+mod spin;
+pub mod spsc;
+mod state_cell;
+mod sync;
+pub use state_cell::StateCell;
+mod tagged_cell;
+pub use tagged_cell::TaggedCell;
+mod timer_queue;
+pub use timer_queue::{monotonic_now, TimerHandle, TimerQueue};
+#[cfg(feature = "trace")]
+pub mod trace;
+mod triple_buffer;
+pub use triple_buffer::{Publisher, Subscriber, TripleBuffer};
+mod usdt;
+mod verify;
+pub use verify::{Verifier, Violation};
+pub mod wait;
+mod watch;
+pub use watch::{Watch, WatchReceiver};
 
+// Some are only used by the shared memory backend's syscalls.
+#[cfg_attr(shm_heap, allow(unused_imports))]
 use std::{
     ffi::{c_int, c_void, CStr, CString},
-    fmt, io,
+    fmt,
+    fs::OpenOptions,
+    io,
     mem::{align_of, size_of, MaybeUninit},
     num::NonZeroUsize,
     ops::Deref,
-    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
+    os::{
+        fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::Path,
+    sync::atomic::AtomicU32,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -26,6 +155,20 @@ pub enum Error {
     Open(io::Error),
     Resize(io::Error),
     Mmap(io::Error),
+    /// Locking the mapping in RAM failed (ex: ENOMEM when RLIMIT_MEMLOCK would be exceeded)
+    MemoryLock(io::Error),
+    /// The region wasn't created by this crate
+    MagicMismatch,
+    /// The region's header has an unsupported version (the one found)
+    VersionMismatch(u32),
+    /// The region hasn't been initialized by its creator, which may have exited
+    Uninitialized,
+    /// The region holds a different type (see [`Shareable::fingerprint`])
+    LayoutMismatch,
+    /// Applying a mapping option failed (ex: a NUMA policy or huge page advice)
+    Configure(io::Error),
+    /// The region has already migrated (see [`Shared::migrate`])
+    Moved,
 }
 
 impl fmt::Display for Error {
@@ -38,6 +181,15 @@ impl fmt::Display for Error {
             Error::Open(_) => write!(f, "unable to open shared memory region"),
             Error::Resize(_) => write!(f, "unable to resize shared memory region"),
             Error::Mmap(_) => write!(f, "unable to map shared object"),
+            Error::MemoryLock(_) => write!(f, "unable to lock shared memory region in RAM"),
+            Error::MagicMismatch => write!(f, "shared memory region wasn't created by shm"),
+            Error::LayoutMismatch => write!(f, "shared memory region holds a different type"),
+            Error::Uninitialized => write!(f, "shared memory region isn't initialized"),
+            Error::VersionMismatch(v) => {
+                write!(f, "shared memory region header version {v} is unsupported")
+            }
+            Error::Configure(_) => write!(f, "unable to configure shared memory mapping"),
+            Error::Moved => write!(f, "shared memory region has already migrated"),
         }
     }
 }
@@ -45,8 +197,18 @@ impl fmt::Display for Error {
 impl std::error::Error for Error {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            Error::AlignmentMismatch | Error::LengthMismatch => None,
-            Error::Mmap(e) | Error::Open(e) | Error::Resize(e) => Some(e),
+            Error::AlignmentMismatch
+            | Error::LengthMismatch
+            | Error::LayoutMismatch
+            | Error::MagicMismatch
+            | Error::Moved
+            | Error::Uninitialized
+            | Error::VersionMismatch(_) => None,
+            Error::Configure(e)
+            | Error::Mmap(e)
+            | Error::MemoryLock(e)
+            | Error::Open(e)
+            | Error::Resize(e) => Some(e),
         }
     }
 }
@@ -64,9 +226,36 @@ impl std::error::Error for Error {
 ///
 /// Fortunately, this crate provides synchronization abstractions that can be used. Other
 /// available types include plain old data (u8, u16, u32, etc) and std::sync::atomic::Atomic*.
-pub unsafe trait Shareable: Default + Sync + Sized {}
+pub unsafe trait Shareable: Default + Sync + Sized {
+    /// Identifies the type's layout. It's recorded in the header of created regions and compared
+    /// by `open`, which returns [`Error::LayoutMismatch`] if the types differ.
+    ///
+    /// Defaults to a hash of the type's name, size and alignment. Implementations may include
+    /// their field offsets with [`layout_fingerprint`] to also detect reordered fields.
+    fn fingerprint() -> u64 {
+        layout_fingerprint::<Self>(&[])
+    }
+
+    /// Initializes the value in a newly created (zeroed) region. Defaults to writing
+    /// `Self::default()`, which large types (ex: a trace ring) may override to initialize their
+    /// fields in place rather than building the value on the stack.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes, aligned for `Self` and point to zeroed memory.
+    unsafe fn init_in_place(ptr: *mut Self) {
+        unsafe { ptr.write(Self::default()) }
+    }
+}
+
+/// With the `bytemuck` feature any [`bytemuck::Pod`] type is Shareable, as Pod types are
+/// pointer-free by construction. Types deriving Pod must not also implement Shareable.
+#[cfg(feature = "bytemuck")]
+unsafe impl<T: bytemuck::Pod + Default + Sync> Shareable for T {}
 
 /// A wrapper type providing inter-process access via shared memory.
+///
+/// The region starts with a header identifying it as created by this crate, followed by the T.
 pub struct Shared<T>(SharedInner<T>);
 
 impl<T> Deref for Shared<T> {
@@ -76,11 +265,359 @@ impl<T> Deref for Shared<T> {
         // [SAFETY]: In order to be dereferenceable the pointer must be properly aligned
         // and valid for the access bounds.  These properties are verified prior to
         // constructing the Shared<T> instance.
-        let (SharedInner::Owned { ptr, .. } | SharedInner::Open { ptr, .. }) = self.0;
-        unsafe { &*ptr }
+        unsafe { &*self.0.ptr }
     }
 }
 
+impl<T> Shared<T> {
+    /// The length of the mapping, which is the object size rounded up to whole pages.
+    pub fn mapped_len(&self) -> usize {
+        let len = self.0.len;
+        round_up_to_page(len.get()).unwrap_or(len.get())
+    }
+
+    /// The start of the mapping, which is page aligned. Together with [`Self::mapped_len`] this
+    /// describes the range to register with pinned-memory APIs (ex: cudaHostRegister).
+    ///
+    /// Both remain valid until the Shared is dropped, which must only happen after the range
+    /// has been unregistered.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.0.base
+    }
+
+    /// Locks the mapping in RAM (faulting in any pages not yet resident) so accesses never page
+    /// fault, as required by real-time users. The pages are unlocked when the Shared is dropped.
+    ///
+    /// Locked memory counts against RLIMIT_MEMLOCK unless the process has CAP_IPC_LOCK.
+    pub fn lock_memory(&self) -> Result<()> {
+        if cfg!(shm_heap) {
+            return Ok(());
+        }
+        match unsafe { libc::mlock(self.as_ptr().cast(), self.mapped_len()) } {
+            0 => Ok(()),
+            _ => Err(Error::MemoryLock(io::Error::last_os_error())),
+        }
+    }
+
+    /// Writes the whole region back to its backing file (see [`Self::sync_range`]).
+    pub fn sync(&self, mode: SyncMode) -> io::Result<()> {
+        let start = self.0.base as usize;
+        msync_mode(start, start + self.0.len.get(), mode)
+    }
+
+    /// Writes `len` bytes of the object starting at `offset` back to the backing file (see
+    /// msync(2)), so regions created with [`Self::create_file`] reach a durable state. Changes
+    /// are written at page granularity.
+    pub fn sync_range(&self, offset: usize, len: usize, mode: SyncMode) -> io::Result<()> {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > size_of::<T>())
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "range exceeds the object",
+            ));
+        }
+        let start = self.0.ptr as usize + offset;
+        msync_mode(start, start + len, mode)
+    }
+
+    /// Hints at how the mapping will be accessed (see madvise(2)).
+    pub fn advise(&self, advice: Advice) -> io::Result<()> {
+        if cfg!(shm_heap) {
+            return Ok(());
+        }
+        let advice = match advice {
+            Advice::Normal => libc::MADV_NORMAL,
+            Advice::Random => libc::MADV_RANDOM,
+            Advice::Sequential => libc::MADV_SEQUENTIAL,
+            Advice::WillNeed => libc::MADV_WILLNEED,
+            Advice::DontNeed => libc::MADV_DONTNEED,
+            Advice::HugePage => libc::MADV_HUGEPAGE,
+            Advice::NoHugePage => libc::MADV_NOHUGEPAGE,
+        };
+        match unsafe { libc::madvise(self.as_ptr().cast(), self.mapped_len(), advice) } {
+            0 => Ok(()),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    /// Sets the NUMA node(s) on which the region's pages are allocated. Intended for the creator
+    /// before the region is populated; pages already allocated are migrated where possible.
+    pub fn set_numa_policy(&self, policy: NumaPolicy) -> io::Result<()> {
+        if cfg!(shm_heap) {
+            return Ok(());
+        }
+        numa::bind(self.as_ptr(), self.mapped_len(), policy)
+    }
+
+    /// Sets what happens to the region's name when this Shared is dropped (anonymous regions
+    /// have no name to unlink).
+    pub fn set_lifecycle(&mut self, lifecycle: Lifecycle) {
+        self.0.fd.lifecycle = lifecycle;
+    }
+
+    /// Detaches without unlinking the region, which remains available to `open`.
+    pub fn persist(mut self) {
+        self.set_lifecycle(Lifecycle::Manual);
+    }
+
+    /// Describes the backing region (ex: for health checks).
+    pub fn stat(&self) -> io::Result<Stat> {
+        self.0.fd.describe()
+    }
+
+    /// Identifies this incarnation of the region, so a client can tell the region was recreated
+    /// (ex: by a restarted server). The high half counts the regions created under the name (kept
+    /// in a region named `{name}.incarnation`, which outlives them), so it increases with each
+    /// recreation. The low half is random, so incarnations are told apart even if the count was
+    /// lost (ex: on reboot). Unnamed regions count 0.
+    ///
+    /// None for regions without a header (see [`Self::open_unchecked_len`]).
+    pub fn generation(&self) -> Option<u64> {
+        self.header().map(header::Header::generation)
+    }
+
+    /// The process which created the region (ex: for debugging tools), or None for regions
+    /// without a header (see [`Self::open_unchecked_len`]).
+    pub fn creator(&self) -> Option<Creator> {
+        self.header().map(header::Header::creator)
+    }
+
+    /// Returns false if this mapping is stale: the region has been unlinked, or its name now
+    /// refers to another region (ex: recreated by a restarted server).
+    pub fn is_current(&self) -> io::Result<bool> {
+        let Some(name) = &self.0.fd.name else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only named regions can be recreated",
+            ));
+        };
+        let stat = self.stat()?;
+        if !stat.linked {
+            return Ok(false);
+        }
+        match metadata(name) {
+            Ok(current) => Ok((current.dev, current.ino) == (stat.dev, stat.ino)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// The name of the region this one migrated to (see [`Self::migrate`]), or None if it hasn't
+    /// (or has no header, see [`Self::open_unchecked_len`]).
+    pub fn moved_to(&self) -> Option<CString> {
+        self.header()?.moved_to(Some(Duration::ZERO))
+    }
+
+    /// Waits up to `timeout` (None waits indefinitely) for the region to migrate, returning the
+    /// name to reopen (ex: so attached clients rendezvous with the new region).
+    pub fn wait_moved(&self, timeout: Option<Duration>) -> Option<CString> {
+        self.header()?.moved_to(timeout)
+    }
+
+    fn header(&self) -> Option<&header::Header> {
+        // Headerless regions map the T at the start of the mapping.
+        (self.0.base != self.0.ptr.cast()).then(|| unsafe { &*self.0.base.cast() })
+    }
+
+    /// Waits until at least `n` other processes have attached to the named region (ex: so a
+    /// server publishes only once its expected clients have mapped it). Returns false on timeout.
+    ///
+    /// Attachment is observed through the flocks listed in /proc/locks, which only include
+    /// processes in this pid namespace.
+    pub fn wait_for_peers(&self, n: usize, timeout: Option<Duration>) -> io::Result<bool> {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if self.0.fd.attached()? > n {
+                return Ok(true);
+            }
+            let delay = match deadline.map(|d| d.saturating_duration_since(Instant::now())) {
+                Some(Duration::ZERO) => return Ok(false),
+                Some(remaining) => remaining.min(PEER_POLL_INTERVAL),
+                None => PEER_POLL_INTERVAL,
+            };
+            std::thread::sleep(delay);
+        }
+    }
+}
+
+impl<T: Project> Shared<T> {
+    /// Borrows the fields of the T individually (see [`projection!`]).
+    pub fn project(&self) -> T::Projection<'_> {
+        (**self).project()
+    }
+}
+
+#[cfg(feature = "bytemuck")]
+impl<T: bytemuck::Pod> Shared<T> {
+    /// The object's bytes, for hashing, checksumming or copying out the whole region.
+    ///
+    /// Other processes may write concurrently, so the bytes are only consistent if they're
+    /// otherwise synchronized (ex: the region is read-only).
+    pub fn as_bytes(&self) -> &[u8] {
+        bytemuck::bytes_of(&**self)
+    }
+}
+
+/// How often [`Shared::wait_for_peers`] recounts attached processes
+const PEER_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The backoff bounds of [`Shared::open_timeout`]
+const OPEN_RETRY_MIN: Duration = Duration::from_millis(1);
+const OPEN_RETRY_MAX: Duration = Duration::from_millis(100);
+
+/// Whether [`Shared::sync`] waits for the write back.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Returns once the pages have been written (MS_SYNC)
+    Sync,
+    /// Schedules the write back and returns immediately (MS_ASYNC)
+    Async,
+}
+
+/// Expected access patterns for [`Shared::advise`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Advice {
+    Normal,
+    Random,
+    Sequential,
+    /// Pages will be accessed soon (read ahead)
+    WillNeed,
+    /// Pages won't be accessed soon. The region's contents are kept, as it's a shared mapping.
+    DontNeed,
+    /// Back the mapping with transparent huge pages (if enabled for shared memory)
+    HugePage,
+    NoHugePage,
+}
+
+/// Properties of a region, from [`Shared::stat`] or [`metadata`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stat {
+    /// Size of the region in bytes (may exceed the mapped object)
+    pub size: u64,
+    pub dev: u64,
+    pub ino: u64,
+    /// False once the region's name has been unlinked
+    pub linked: bool,
+    /// Last modification of the region's size (ex: by ftruncate at creation)
+    pub modified: SystemTime,
+    /// Last status change of the region (ex: creation, resize or unlink)
+    pub changed: SystemTime,
+}
+
+impl Stat {
+    #[cfg(not(shm_heap))]
+    fn of(fd: &impl AsRawFd) -> io::Result<Self> {
+        let mut stat = MaybeUninit::uninit();
+        if unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let stat = unsafe { stat.assume_init() };
+
+        let time = |secs: i64, nsecs: i64| {
+            let since_epoch = Duration::new(secs.unsigned_abs(), nsecs as u32);
+            if secs < 0 {
+                UNIX_EPOCH - since_epoch
+            } else {
+                UNIX_EPOCH + since_epoch
+            }
+        };
+        Ok(Self {
+            size: stat.st_size as u64,
+            dev: stat.st_dev,
+            ino: stat.st_ino,
+            linked: stat.st_nlink > 0,
+            modified: time(stat.st_mtime, stat.st_mtime_nsec),
+            changed: time(stat.st_ctime, stat.st_ctime_nsec),
+        })
+    }
+}
+
+/// Removes the region's name (ex: to clean up after a crashed creator). Processes still attached
+/// keep their mappings.
+pub fn unlink(name: &CStr) -> io::Result<()> {
+    #[cfg(shm_heap)]
+    return heap::unlink(name);
+    #[cfg(not(shm_heap))]
+    match unsafe { libc::shm_unlink(name.as_ptr()) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Returns true if a region named `name` exists, even if this process lacks permission to open it.
+pub fn exists(name: &CStr) -> bool {
+    #[cfg(shm_heap)]
+    return heap::exists(name);
+    #[cfg(not(shm_heap))]
+    match shm_open(name, libc::O_RDONLY) {
+        Ok(_) => true,
+        Err(e) => e.raw_os_error() == Some(libc::EACCES),
+    }
+}
+
+/// Describes the region named `name` without mapping it.
+pub fn metadata(name: &CStr) -> io::Result<Stat> {
+    #[cfg(shm_heap)]
+    return heap::metadata(name);
+    #[cfg(not(shm_heap))]
+    Stat::of(&shm_open(name, libc::O_RDONLY)?)
+}
+
+/// Describes the region named `name` from its header, without attaching to it (ex: to find
+/// regions leaked by crashed processes, as `shm-tool` does).
+///
+/// Fails with [`Error::MagicMismatch`] if the region wasn't created by [`Shared`], or
+/// [`Error::Uninitialized`] if its creator hasn't finished initializing it.
+pub fn inspect(name: &CStr) -> Result<RegionInfo> {
+    #[cfg(shm_heap)]
+    return heap::inspect(name);
+    #[cfg(not(shm_heap))]
+    inspect_region(name)
+}
+
+/// The name of the region `fd` migrated to, read from its header alone as the payload may be
+/// another type. None if the region hasn't migrated or wasn't created by [`Shared`].
+fn redirect(fd: &ShmFd) -> Result<Option<CString>> {
+    let header_len = NonZeroUsize::new(size_of::<header::Header>()).unwrap();
+    let Some(len) = fd.len().filter(|&len| len >= header_len.get()) else {
+        return Ok(None);
+    };
+    let ptr = fd.map(header_len, align_of::<header::Header>())?;
+    // [SAFETY]: The mapping spans a Header, whose fields are only read once it's READY.
+    let header = unsafe { &*ptr.cast::<header::Header>() };
+    let moved_to = header
+        .info(len as u64)
+        .ok()
+        .and_then(|_| header.moved_to(Some(Duration::ZERO)));
+    unmap(ptr, header_len.get());
+    Ok(moved_to)
+}
+
+#[cfg(not(shm_heap))]
+fn inspect_region(name: &CStr) -> Result<RegionInfo> {
+    let fd = shm_open(name, libc::O_RDONLY).map_err(Error::Open)?;
+    let len = region_len(&fd).unwrap_or(0);
+    let header_len = NonZeroUsize::new(size_of::<header::Header>()).unwrap();
+    if len < header_len.get() {
+        return Err(Error::MagicMismatch);
+    }
+    let ptr = mmap_prot(
+        fd.as_raw_fd(),
+        header_len,
+        align_of::<header::Header>(),
+        libc::PROT_READ,
+    )?;
+    // [SAFETY]: The mapping spans a Header, and is only read atomically until READY.
+    let info = unsafe { &*ptr.cast::<header::Header>() }.info(len as u64);
+    let _ = unsafe { libc::munmap(ptr, header_len.get()) };
+    Ok(RegionInfo {
+        attached: flock_holders(&fd).map_err(Error::Open)?,
+        ..info?
+    })
+}
+
 impl<T: Shareable> Shared<T> {
     /// # Examples
     ///
@@ -121,112 +658,665 @@ impl<T: Shareable> Shared<T> {
     /// In order to prevent a data race (UB) the caller must not share the name of the shared memory region
     /// until after this method has succesfully returned.
     pub unsafe fn create(name: &CStr) -> Result<Self> {
+        unsafe { Self::create_with_mode(name, DEFAULT_MODE, None) }
+    }
+
+    /// Creates the region with the permission bits `mode` (not masked by the umask), optionally
+    /// changing its group (ex: so clients running as another user in the group can open it).
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::create`].
+    pub unsafe fn create_with_mode(
+        name: &CStr,
+        mode: libc::mode_t,
+        group: Option<libc::gid_t>,
+    ) -> Result<Self> {
+        let fd = ShmFd::create_with_mode(name, mode, group).map_err(Error::Open)?;
+        unsafe { Self::init(fd) }
+    }
+
+    /// Creates an anonymous region (memfd) which has no name, so it can't be opened by other
+    /// processes or leak once every process has detached. Instead the descriptor is passed (see
+    /// [`AsFd`]) to a child process or over a Unix socket, and mapped with [`Self::from_fd`].
+    ///
+    /// The descriptor is close-on-exec, so a child which calls exec must be given a duplicate.
+    /// Where supported (see [`Capabilities`]) the region's size is sealed, and kernels without
+    /// memfds get a POSIX region unlinked as soon as it's created.
+    pub fn create_anon() -> Result<Self> {
+        let fd = ShmFd::anon().map_err(Error::Open)?;
+        // [SAFETY]: The region isn't shared until the descriptor is obtained from the result.
+        let shared = unsafe { Self::init(fd) }?;
+        shared.0.fd.seal();
+        Ok(shared)
+    }
+
+    /// Creates a region backed by a regular file (ex: outside tmpfs, so the state survives a
+    /// reboot). The file must not already exist and isn't removed when dropped.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::create`].
+    pub unsafe fn create_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .mode(DEFAULT_MODE)
+            .open(path)
+            .map_err(Error::Open)?;
+        unsafe { Self::init(ShmFd::from_fd(file.into())) }
+    }
+
+    /// Configures region creation (ex: mode, huge pages, lifecycle).
+    pub fn builder(name: &CStr) -> SharedBuilder<T> {
+        SharedBuilder::new(name)
+    }
+
+    /// Initializes a newly created region, which must not yet be shared.
+    unsafe fn init(fd: ShmFd) -> Result<Self> {
+        unsafe { Self::init_with(fd, |_| Ok(())) }
+    }
+
+    /// Like [`Self::init`], calling `configure` on the mapping before its pages are touched.
+    unsafe fn init_with(fd: ShmFd, configure: impl FnOnce(&Self) -> Result<()>) -> Result<Self> {
         // [SAFETY]: The size of T is verified at compile-time to be non-zero.
         #[allow(clippy::let_unit_value)]
         let _ = SizeIsNonZeroI64::<T>::OK;
-        let len = NonZeroUsize::new(size_of::<T>()).unwrap();
+        let len = header::region_len::<T>();
 
-        let fd = ShmFd::create(name).map_err(Error::Open)?;
-        // [SAFETY]: The size of T is verified at compile time to be <= i64::MAX.
-        if unsafe { libc::ftruncate(fd.as_raw_fd(), i64::try_from(len.get()).unwrap()) } != 0 {
-            return Err(Error::Resize(io::Error::last_os_error()));
-        }
+        // Held from before the region is sized until it's ready, so openers wait for it
+        fd.lock_init().map_err(Error::Open)?;
+        fd.resize(len)?;
 
-        let ptr = mmap(fd.as_raw_fd(), len, align_of::<T>())?.cast::<T>();
+        let shared = Self(SharedInner::map(fd, len)?);
+        configure(&shared)?;
         // [SAFETY]: Successful truncation (above) guarantees the object's allocation size is valid.
         // Pointer validity and alignment are validated in the mmap call.
-        unsafe { ptr.write(Default::default()) };
-        let _ = msync(ptr as *mut c_void, len.get());
-        Ok(Self(SharedInner::Owned { _fd: fd, ptr, len }))
+        unsafe {
+            header::Header::initialize::<T>(shared.0.base.cast(), &shared.0.fd, 1, || {
+                T::init_in_place(shared.0.ptr)
+            })
+        }?;
+        let _ = msync(shared.0.base.cast(), len.get());
+        #[cfg(feature = "audit")]
+        audit::attached(shared.0.fd.name.as_deref(), shared.0.base, len.get(), true);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(region = ?shared.0.fd.name, len = len.get(), "created region");
+        Ok(shared)
     }
 
     /// # Safety
     ///
-    /// The type T must match that used to create the Shared<T> instance of the same name.
-    /// In order to prevent a data race (UB) this method must not be called until
-    /// after the named shared memory region has been successfully created.
+    /// The type T must match that used to create the Shared<T> instance of the same name. This is
+    /// checked at runtime by comparing [`Shareable::fingerprint`]s, so only types whose
+    /// fingerprints collide (ex: a custom fingerprint) can be mismatched.
+    ///
+    /// If the region is still being sized or initialized this waits for the creator to finish.
+    /// Returns [`Error::Uninitialized`] if the creator hasn't started initializing it yet (see
+    /// [`Self::open_timeout`]), or exited before finishing.
+    ///
+    /// Follows the redirects of migrated regions (see [`Self::migrate`]).
     pub unsafe fn open(name: &CStr) -> Result<Self> {
+        let mut fd = ShmFd::open(name).map_err(Error::Open)?;
+        while let Some(name) = redirect(&fd)? {
+            fd = ShmFd::open(&name).map_err(Error::Open)?;
+        }
+        unsafe { Self::map(fd) }
+    }
+
+    /// Migrates the region (ex: to grow it or change its schema) to a new region named `name`
+    /// holding a U, which `migrate` initializes from the T. This region is then redirected to
+    /// the new one: [`Self::open`] follows the redirect, and attached clients observe it with
+    /// [`Self::moved_to`] or [`Self::wait_moved`] and reopen.
+    ///
+    /// The caller quiesces writers (ex: holding a lock within the T) so `migrate` copies a
+    /// consistent state. This region remains linked so late openers find the redirect; its
+    /// [`Lifecycle`] decides when the name is removed.
+    ///
+    /// Returns [`Error::Moved`] if the region already migrated, in which case the new region is
+    /// removed.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::create`].
+    pub unsafe fn migrate<U: Shareable>(
+        &self,
+        name: &CStr,
+        migrate: impl FnOnce(&T, &U),
+    ) -> Result<Shared<U>> {
+        let header = self.header().ok_or(Error::MagicMismatch)?;
+        if self.moved_to().is_some() {
+            return Err(Error::Moved);
+        }
+        let shared = unsafe { Shared::<U>::create(name) }?;
+        migrate(self, &shared);
+        let _ = msync(shared.0.base.cast(), shared.0.len.get());
+        // The new region is removed on drop unless the redirect succeeds
+        if header.redirect(name)? {
+            Ok(shared)
+        } else {
+            Err(Error::Moved)
+        }
+    }
+
+    /// Opens a region which may not exist yet (ex: a client started before its server), retrying
+    /// with backoff until it has been created and initialized or `timeout` elapses. On timeout
+    /// the last error is returned.
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::open`].
+    pub unsafe fn open_timeout(name: &CStr, timeout: Duration) -> Result<Self> {
+        // Waits indefinitely if the deadline isn't representable
+        let deadline = Instant::now().checked_add(timeout);
+        let mut delay = OPEN_RETRY_MIN;
+        loop {
+            let error = match unsafe { Self::open(name) } {
+                Err(Error::Open(e)) if e.kind() == io::ErrorKind::NotFound => Error::Open(e),
+                // Not yet sized or initialized by the creator
+                Err(Error::LengthMismatch) if metadata(name).is_ok_and(|stat| stat.size == 0) => {
+                    Error::LengthMismatch
+                }
+                Err(Error::Uninitialized) => Error::Uninitialized,
+                result => return result,
+            };
+            match deadline.map(|d| d.saturating_duration_since(Instant::now())) {
+                Some(Duration::ZERO) => return Err(error),
+                Some(remaining) => std::thread::sleep(delay.min(remaining)),
+                None => std::thread::sleep(delay),
+            }
+            delay = (delay * 2).min(OPEN_RETRY_MAX);
+        }
+    }
+
+    /// Maps a region from its descriptor (ex: received from the process which called
+    /// [`Self::create_anon`]).
+    ///
+    /// # Safety
+    ///
+    /// The type T must match that used to create the region (see [`Self::open`]).
+    pub unsafe fn from_fd(fd: OwnedFd) -> Result<Self> {
+        unsafe { Self::map(ShmFd::from_fd(fd)) }
+    }
+
+    /// Opens a region created with [`Self::create_file`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Self::open`].
+    pub unsafe fn open_file(path: impl AsRef<Path>) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(path)
+            .map_err(Error::Open)?;
+        unsafe { Self::map(ShmFd::from_fd(file.into())) }
+    }
+
+    unsafe fn map(fd: ShmFd) -> Result<Self> {
+        // [SAFETY]: The size of T is verified at compile-time to be non-zero.
+        #[allow(clippy::let_unit_value)]
+        let _ = SizeIsNonZeroI64::<T>::OK;
+        let len = header::region_len::<T>();
+
+        // The length is only final once the creator releases its lock
+        let _ = fd.wait_initialized();
+        if Some(len.get()) != fd.len() {
+            return Err(Error::LengthMismatch);
+        }
+
+        let shared = Self(SharedInner::map(fd, len)?);
+        let header = unsafe { &*shared.0.base.cast::<header::Header>() };
+        header.wait_ready(&shared.0.fd)?;
+        header.validate::<T>(1)?;
+        #[cfg(feature = "audit")]
+        audit::attached(shared.0.fd.name.as_deref(), shared.0.base, len.get(), false);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(region = ?shared.0.fd.name, len = len.get(), "opened region");
+        Ok(shared)
+    }
+
+    /// Opens a region which may be larger than T (ex: created by a foreign process which rounds
+    /// the size up to a page multiple). Only the leading `size_of::<T>()` bytes are mapped.
+    ///
+    /// The region has no header (it's a bare T), so regions created by [`Self::create`] can't be
+    /// opened this way.
+    ///
+    /// Returns [`Error::LengthMismatch`] if the region is smaller than T.
+    ///
+    /// # Safety
+    ///
+    /// In addition to the requirements of [`Self::open`], the creator must have initialized a
+    /// valid T at the start of the region, as the length no longer confirms the region's type.
+    pub unsafe fn open_unchecked_len(name: &CStr) -> Result<Self> {
         // [SAFETY]: The size of T is verified at compile-time to be non-zero.
         #[allow(clippy::let_unit_value)]
         let _ = SizeIsNonZeroI64::<T>::OK;
         let len = NonZeroUsize::new(size_of::<T>()).unwrap();
 
-        let fd = shm_open(name, libc::O_RDWR).map_err(Error::Open)?;
+        let fd = ShmFd::open(name).map_err(Error::Open)?;
 
-        if Some(len.get()) != {
-            let mut stat = MaybeUninit::uninit();
-            (unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } == 0)
-                .then(|| unsafe { stat.assume_init() }.st_size)
-                .and_then(|size| usize::try_from(size).ok())
-        } {
+        // Mapping beyond the end of the region would fault (SIGBUS) on access.
+        if fd.len().is_none_or(|size| size < len.get()) {
             return Err(Error::LengthMismatch);
         }
 
-        let ptr = mmap(fd.as_raw_fd(), len, align_of::<T>())?.cast::<T>();
-        Ok(Self(SharedInner::Open { ptr, len }))
+        let base = fd.map(len, align_of::<T>())?.cast::<u8>();
+        #[cfg(feature = "audit")]
+        audit::attached(fd.name.as_deref(), base, len.get(), false);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(region = ?fd.name, len = len.get(), "opened region");
+        Ok(Self(SharedInner {
+            fd,
+            base,
+            ptr: base.cast(),
+            len,
+        }))
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
-enum SharedInner<T> {
-    Owned {
-        _fd: ShmFd,
-        ptr: *mut T,
-        len: NonZeroUsize,
-    },
-    Open {
-        ptr: *mut T,
-        len: NonZeroUsize,
-    },
+struct SharedInner<T> {
+    fd: ShmFd,
+    /// The start of the mapping
+    base: *mut u8,
+    /// The payload, which follows the header
+    ptr: *mut T,
+    len: NonZeroUsize,
+}
+
+impl<T> SharedInner<T> {
+    /// Maps a region of `len` bytes holding a header and a T.
+    fn map(fd: ShmFd, len: NonZeroUsize) -> Result<Self> {
+        let base = fd.map(len, align_of::<T>())?.cast::<u8>();
+        // [SAFETY]: The payload offset is within the region and aligned for T.
+        let ptr = unsafe { base.add(header::payload_offset::<T>()) }.cast();
+        Ok(Self { fd, base, ptr, len })
+    }
 }
 
 unsafe impl<T: Shareable> Send for SharedInner<T> {}
 unsafe impl<T: Shareable> Sync for SharedInner<T> {}
 
+impl<T> AsFd for Shared<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.fd.as_fd()
+    }
+}
+
 impl<T> Drop for SharedInner<T> {
     fn drop(&mut self) {
-        match &self {
-            Self::Owned { ptr, len, .. } | Self::Open { ptr, len } => {
-                let _ = msync(*ptr as *mut c_void, len.get());
-                let _ = unsafe { libc::munmap(*ptr as *mut c_void, len.get()) };
-            }
+        let (ptr, len) = (self.base as *mut c_void, self.len.get());
+        #[cfg(feature = "diagnostics")]
+        diagnostics::detaching(ptr.cast(), len);
+        #[cfg(feature = "audit")]
+        audit::detaching(ptr.cast());
+        #[cfg(feature = "tracing")]
+        tracing::debug!(region = ?self.fd.name, len, "detaching region");
+        // Regions opened without a header map the payload at the start
+        if self.ptr.cast::<u8>() != self.base {
+            // [SAFETY]: The header is mapped until unmap below.
+            let header = unsafe { &*self.base.cast::<header::Header>() };
+            self.fd.detach(Some(header.orphaned()));
         }
+        unmap(ptr, len);
     }
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// What happens to a region's name when a handle to it (ex: [`Shared`], [`SharedSlice`]) is
+/// dropped. Handles which created the region default to `UnlinkOnDrop`, others to `Manual`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Lifecycle {
+    /// The region is unlinked, although processes still attached keep their mappings.
+    #[default]
+    UnlinkOnDrop,
+    /// The region is unlinked by whichever process detaches last.
+    UnlinkOnLastDetach,
+    /// The region outlives the handle (ex: so a restarted server can reattach with `open`) until
+    /// removed with [`unlink`].
+    Manual,
+}
+
+/// A region descriptor. Every attached process holds a shared flock on the region so the last
+/// process to detach can be identified.
+#[cfg(not(shm_heap))]
 struct ShmFd {
-    name: Box<CStr>,
+    /// None for anonymous regions
+    name: Option<Box<CStr>>,
     fd: OwnedFd,
+    lifecycle: Lifecycle,
 }
 
+#[cfg(not(shm_heap))]
+impl AsFd for ShmFd {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(not(shm_heap))]
 impl AsRawFd for ShmFd {
     fn as_raw_fd(&self) -> RawFd {
         self.fd.as_raw_fd()
     }
 }
 
+#[cfg(not(shm_heap))]
 impl Drop for ShmFd {
     fn drop(&mut self) {
-        let _ = unsafe { libc::shm_unlink(self.name.as_ptr()) };
+        self.detach(None);
+    }
+}
+
+#[cfg(not(shm_heap))]
+impl ShmFd {
+    /// Applies the lifecycle to the region's name, once.
+    ///
+    /// `orphaned` is the header's mark, which handles pass while the region is still mapped.
+    /// Regions without a header (ex: [`Shared::open_unchecked_len`]) fall back to marking the
+    /// mode, which fails unless this process owns the region.
+    fn detach(&mut self, orphaned: Option<&AtomicU32>) {
+        let Some(name) = self.name.take() else {
+            return;
+        };
+        let unlink = match self.lifecycle {
+            Lifecycle::UnlinkOnDrop => true,
+            Lifecycle::UnlinkOnLastDetach => {
+                // The mark hands the unlink to the last opener if others remain attached.
+                // Marking before checking ensures one of the last processes sees the mark.
+                match orphaned {
+                    Some(orphaned) => orphaned.store(1, ordering::Release),
+                    None => {
+                        let _ =
+                            unsafe { libc::fchmod(self.fd.as_raw_fd(), self.mode() | ORPHANED) };
+                    }
+                }
+                self.last_detached()
+            }
+            // Completes an unlink handed off by a process which detached earlier
+            Lifecycle::Manual => {
+                self.last_detached()
+                    && match orphaned {
+                        Some(orphaned) => orphaned.load(ordering::Acquire) != 0,
+                        None => self.mode() & ORPHANED != 0,
+                    }
+            }
+        };
+        if unlink {
+            let _ = unsafe { libc::shm_unlink(name.as_ptr()) };
+        }
     }
 }
 
+#[cfg(not(shm_heap))]
 impl ShmFd {
     fn create(name: &CStr) -> io::Result<Self> {
-        shm_open(name, libc::O_RDWR | libc::O_CREAT | libc::O_EXCL).map(|fd| Self {
-            name: CString::from(name).into_boxed_c_str(),
+        Self::create_with_mode(name, DEFAULT_MODE, None)
+    }
+
+    fn create_with_mode(
+        name: &CStr,
+        mode: libc::mode_t,
+        group: Option<libc::gid_t>,
+    ) -> io::Result<Self> {
+        let fd = shm_open(name, libc::O_RDWR | libc::O_CREAT | libc::O_EXCL).map(|fd| Self {
+            name: Some(CString::from(name).into_boxed_c_str()),
             fd,
-        })
+            lifecycle: Lifecycle::UnlinkOnDrop,
+        })?;
+        fd.attach();
+        // The mode given to shm_open is masked by the umask. On error the region is unlinked.
+        if unsafe { libc::fchmod(fd.as_raw_fd(), mode) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        if let Some(gid) = group {
+            if unsafe { libc::fchown(fd.as_raw_fd(), libc::uid_t::MAX, gid) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(fd)
+    }
+
+    fn open(name: &CStr) -> io::Result<Self> {
+        let fd = shm_open(name, libc::O_RDWR).map(|fd| Self {
+            name: Some(CString::from(name).into_boxed_c_str()),
+            fd,
+            lifecycle: Lifecycle::Manual,
+        })?;
+        fd.attach();
+        Ok(fd)
+    }
+
+    /// Opens the region with `O_RDONLY`. The handle doesn't take part in unlinking the region.
+    fn open_read_only(name: &CStr) -> io::Result<Self> {
+        shm_open(name, libc::O_RDONLY).map(Self::from_fd)
+    }
+
+    /// A memfd, or a POSIX region unlinked once created where memfds are unsupported.
+    fn anon() -> io::Result<Self> {
+        let caps = capabilities();
+        if !caps.memfd {
+            return Self::anon_unlinked();
+        }
+        let flags = match caps.memfd_seals {
+            true => libc::MFD_CLOEXEC | libc::MFD_ALLOW_SEALING,
+            false => libc::MFD_CLOEXEC,
+        };
+        match unsafe { libc::memfd_create(c"shm".as_ptr(), flags) } {
+            fd if fd >= 0 => Ok(Self::from_fd(unsafe { OwnedFd::from_raw_fd(fd) })),
+            _ => Err(io::Error::last_os_error()),
+        }
+    }
+
+    fn anon_unlinked() -> io::Result<Self> {
+        static CREATED: AtomicU32 = AtomicU32::new(0);
+        loop {
+            let n = CREATED.fetch_add(1, ordering::Relaxed);
+            let name = CString::new(format!("/shm-anon.{}.{n}", std::process::id())).unwrap();
+            match shm_open(&name, libc::O_RDWR | libc::O_CREAT | libc::O_EXCL) {
+                Ok(fd) => {
+                    unsafe { libc::shm_unlink(name.as_ptr()) };
+                    return Ok(Self::from_fd(fd));
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Counts the regions created under this one's name, returning the count including this
+    /// region. The count is kept in a region named after this one (`{name}.incarnation`), which
+    /// isn't unlinked with it so a recreated region counts on. 0 for unnamed regions, or if the
+    /// count can't be kept.
+    fn incarnation(&self) -> u32 {
+        let Some(name) = &self.name else {
+            return 0;
+        };
+        let Ok(counter) = CString::new([name.to_bytes(), b".incarnation"].concat()) else {
+            return 0;
+        };
+        let Ok(fd) = shm_open(&counter, libc::O_RDWR | libc::O_CREAT) else {
+            return 0;
+        };
+        // Opened by whoever may recreate the region. Fails harmlessly for other users' counters.
+        if let Some(stat) = self.stat() {
+            unsafe {
+                libc::fchmod(fd.as_raw_fd(), stat.st_mode & 0o7777);
+                libc::fchown(fd.as_raw_fd(), libc::uid_t::MAX, stat.st_gid);
+            }
+        }
+        let len = NonZeroUsize::new(size_of::<AtomicU32>()).unwrap();
+        // Sizing is idempotent, so racing creators don't truncate each other's counts.
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), len.get() as libc::off_t) } != 0 {
+            return 0;
+        }
+        let Ok(ptr) = mmap(fd.as_raw_fd(), len, align_of::<AtomicU32>()) else {
+            return 0;
+        };
+        // [SAFETY]: The mapping is valid for the (page aligned) counter, which is only accessed
+        // atomically.
+        let count = unsafe { &*ptr.cast::<AtomicU32>() }.fetch_add(1, ordering::Relaxed);
+        let _ = unsafe { libc::munmap(ptr, len.get()) };
+        count.wrapping_add(1)
+    }
+
+    /// Fixes the size of a memfd where seals are supported, so a process it's passed to can't
+    /// truncate it (faulting every mapping).
+    fn seal(&self) {
+        if capabilities().memfd_seals {
+            let seals = libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+            let _ = unsafe { libc::fcntl(self.as_raw_fd(), libc::F_ADD_SEALS, seals) };
+        }
+    }
+
+    fn from_fd(fd: OwnedFd) -> Self {
+        Self {
+            name: None,
+            fd,
+            lifecycle: Lifecycle::Manual,
+        }
+    }
+
+    fn resize(&self, len: NonZeroUsize) -> Result<()> {
+        match unsafe { libc::ftruncate(self.as_raw_fd(), i64::try_from(len.get()).unwrap()) } {
+            0 => Ok(()),
+            _ => Err(Error::Resize(io::Error::last_os_error())),
+        }
+    }
+
+    fn len(&self) -> Option<usize> {
+        region_len(self)
+    }
+
+    fn map(&self, len: NonZeroUsize, align: usize) -> Result<*mut c_void> {
+        let ptr = mmap(self.as_raw_fd(), len, align)?;
+        owner::mapped(ptr, len.get(), self.as_fd());
+        Ok(ptr)
+    }
+
+    fn map_read_only(&self, len: NonZeroUsize, align: usize) -> Result<*mut c_void> {
+        mmap_prot(self.as_raw_fd(), len, align, libc::PROT_READ)
+    }
+
+    fn describe(&self) -> io::Result<Stat> {
+        Stat::of(self)
+    }
+
+    fn attach(&self) {
+        let _ = unsafe { libc::flock(self.fd.as_raw_fd(), libc::LOCK_SH) };
+    }
+
+    /// Write locks the region's first byte while its creator sizes and initializes it. Unlike
+    /// pids, open file description locks are visible across pid namespaces, and released if the
+    /// creator exits. They're independent of the flocks tracking attachment.
+    fn lock_init(&self) -> io::Result<()> {
+        // Waits out an opener briefly read locking the new region (see `wait_initialized`)
+        self.ofd_lock(libc::F_OFD_SETLKW, libc::F_WRLCK).map(drop)
+    }
+
+    fn unlock_init(&self) {
+        let _ = self.ofd_lock(libc::F_OFD_SETLK, libc::F_UNLCK);
+    }
+
+    /// Whether another open file description holds the initialization lock.
+    fn initializing(&self) -> io::Result<bool> {
+        self.ofd_lock(libc::F_OFD_GETLK, libc::F_WRLCK)
+            .map(|lock| lock.l_type != libc::F_UNLCK as libc::c_short)
+    }
+
+    /// Waits until no other open file description holds the initialization lock.
+    fn wait_initialized(&self) -> io::Result<()> {
+        loop {
+            match self.ofd_lock(libc::F_OFD_SETLKW, libc::F_RDLCK) {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(e),
+            }
+        }
+        self.unlock_init();
+        Ok(())
+    }
+
+    fn ofd_lock(&self, cmd: c_int, kind: c_int) -> io::Result<libc::flock> {
+        let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+        lock.l_type = kind as libc::c_short;
+        lock.l_whence = libc::SEEK_SET as libc::c_short;
+        lock.l_len = 1;
+        match unsafe { libc::fcntl(self.fd.as_raw_fd(), cmd, &mut lock) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(lock),
+        }
+    }
+
+    /// Detaches, returning true if no other process remains attached to the (still linked) region.
+    fn last_detached(&self) -> bool {
+        let fd = self.fd.as_raw_fd();
+        let _ = unsafe { libc::flock(fd, libc::LOCK_UN) };
+        (unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) } == 0)
+            && self.stat().is_some_and(|stat| stat.st_nlink > 0)
+    }
+
+    /// Counts the handles (including this one) attached to the named region.
+    fn attached(&self) -> io::Result<usize> {
+        if self.name.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "only named regions track attachment",
+            ));
+        }
+        flock_holders(self)
+    }
+
+    fn mode(&self) -> libc::mode_t {
+        self.stat()
+            .map_or(DEFAULT_MODE, |stat| stat.st_mode & 0o7777)
     }
+
+    fn stat(&self) -> Option<libc::stat> {
+        let mut stat = MaybeUninit::uninit();
+        (unsafe { libc::fstat(self.fd.as_raw_fd(), stat.as_mut_ptr()) } == 0)
+            .then(|| unsafe { stat.assume_init() })
+    }
+}
+
+/// Counts the flocks held on the file (ex: the handles attached to a named region).
+#[cfg(not(shm_heap))]
+fn flock_holders(fd: &impl AsRawFd) -> io::Result<usize> {
+    let mut stat = MaybeUninit::uninit();
+    if unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let stat = unsafe { stat.assume_init() };
+    // Ex: "1: FLOCK  ADVISORY  READ  1234 00:19:5 0 EOF" (blocked waiters include "->")
+    let dev = stat.st_dev;
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    let id = format!("{major:02x}:{minor:02x}:{}", stat.st_ino);
+    let locks = std::fs::read_to_string("/proc/locks")?;
+    Ok(locks
+        .lines()
+        .map(|line| line.split_whitespace().skip(1).collect::<Vec<_>>())
+        .filter(|fields| fields.first() == Some(&"FLOCK") && fields.get(4) == Some(&&*id))
+        .count())
 }
 
 ///////////////////////////////////////////////////////////////////////////////
 
+/// Permissions of created regions unless otherwise specified (owner read/write)
+const DEFAULT_MODE: libc::mode_t = libc::S_IRUSR | libc::S_IWUSR;
+
+/// Set (as the otherwise meaningless sticky bit) on regions without a header once a process
+/// holding the region with [`Lifecycle::UnlinkOnLastDetach`] has detached
+#[cfg(not(shm_heap))]
+const ORPHANED: libc::mode_t = libc::S_ISVTX;
+
+#[cfg(not(shm_heap))]
 fn shm_open(name: &CStr, oflag: c_int) -> io::Result<OwnedFd> {
-    let fd = unsafe { libc::shm_open(name.as_ptr(), oflag, libc::S_IRUSR | libc::S_IWUSR) };
+    let fd = unsafe { libc::shm_open(name.as_ptr(), oflag, DEFAULT_MODE) };
     if fd >= 0 {
         Ok(unsafe { OwnedFd::from_raw_fd(fd) })
     } else {
@@ -234,12 +1324,26 @@ fn shm_open(name: &CStr, oflag: c_int) -> io::Result<OwnedFd> {
     }
 }
 
+#[cfg(not(shm_heap))]
+fn region_len(fd: &impl AsRawFd) -> Option<usize> {
+    let mut stat = MaybeUninit::uninit();
+    (unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } == 0)
+        .then(|| unsafe { stat.assume_init() }.st_size)
+        .and_then(|size| usize::try_from(size).ok())
+}
+
+#[cfg(not(shm_heap))]
 fn mmap(fd: RawFd, len: NonZeroUsize, align: usize) -> Result<*mut c_void> {
+    mmap_prot(fd, len, align, libc::PROT_READ | libc::PROT_WRITE)
+}
+
+#[cfg(not(shm_heap))]
+fn mmap_prot(fd: RawFd, len: NonZeroUsize, align: usize, prot: c_int) -> Result<*mut c_void> {
     match unsafe {
         libc::mmap(
             std::ptr::null_mut(),
             len.get(),
-            libc::PROT_READ | libc::PROT_WRITE,
+            prot,
             libc::MAP_SHARED,
             fd.as_raw_fd(),
             0,
@@ -250,11 +1354,26 @@ fn mmap(fd: RawFd, len: NonZeroUsize, align: usize) -> Result<*mut c_void> {
             io::ErrorKind::InvalidData,
             "null pointer",
         ))),
-        ptr if ptr.align_offset(align) != 0 => Err(Error::AlignmentMismatch),
+        ptr if !is_aligned(ptr as usize, align) => Err(Error::AlignmentMismatch),
         ptr => Ok(ptr),
     }
 }
 
+/// Writes back the pages spanning `start..end`.
+#[cfg(not(shm_heap))]
+fn msync_mode(start: usize, end: usize, mode: SyncMode) -> io::Result<()> {
+    let page_start = start & !(page_size() - 1);
+    let flags = match mode {
+        SyncMode::Sync => libc::MS_SYNC,
+        SyncMode::Async => libc::MS_ASYNC,
+    };
+    match unsafe { libc::msync(page_start as *mut c_void, end - page_start, flags) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(shm_heap))]
 fn msync(ptr: *mut c_void, len: usize) -> io::Result<()> {
     match unsafe { libc::msync(ptr, len, libc::MS_SYNC) } {
         0 => Ok(()),
@@ -262,6 +1381,22 @@ fn msync(ptr: *mut c_void, len: usize) -> io::Result<()> {
     }
 }
 
+#[cfg(not(shm_heap))]
+fn unmap(ptr: *mut c_void, len: usize) {
+    owner::unmapped(ptr);
+    let _ = msync(ptr, len);
+    let _ = unsafe { libc::munmap(ptr, len) };
+}
+
+/// Returns false once the process no longer exists (the pid may since have been reused).
+pub(crate) fn pid_alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    (unsafe { libc::kill(pid, 0) } == 0)
+        || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
 ///////////////////////////////////////////////////////////////////////////////
 
 struct SizeIsNonZeroI64<T>(std::marker::PhantomData<T>);
@@ -301,6 +1436,334 @@ mod tests {
 
             let client: Shared<S> = unsafe { Shared::open(&shm_name).unwrap() };
             assert_eq!(client.f1, 0xA5);
+            assert_eq!(client.mapped_len(), page_size());
+        }
+    }
+
+    #[cfg(feature = "bytemuck")]
+    #[test]
+    fn pod() {
+        let shared: Shared<[u32; 4]> = Shared::create_anon().unwrap();
+        assert_eq!(shared.as_bytes(), [0; 16]);
+    }
+
+    #[test]
+    fn unchecked_len() {
+        #[derive(Default)]
+        struct S {
+            f1: u32,
+        }
+
+        unsafe impl Shareable for S {}
+
+        // A foreign creator sizing the region to a page multiple
+        let shm_name = CString::new("/unchecked_len").unwrap();
+        let fd = ShmFd::create(&shm_name).unwrap();
+        fd.resize(NonZeroUsize::new(page_size()).unwrap()).unwrap();
+
+        assert!(matches!(
+            unsafe { Shared::<S>::open(&shm_name) },
+            Err(Error::LengthMismatch)
+        ));
+        let client = unsafe { Shared::<S>::open_unchecked_len(&shm_name).unwrap() };
+        assert_eq!(client.f1, 0);
+    }
+
+    #[cfg(not(shm_heap))]
+    #[test]
+    fn create_with_mode() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let shm_name = CString::new("/with_mode").unwrap();
+        let gid = unsafe { libc::getegid() };
+        let _master: Shared<AtomicF64> =
+            unsafe { Shared::create_with_mode(&shm_name, 0o640, Some(gid)).unwrap() };
+
+        let meta = std::fs::metadata("/dev/shm/with_mode").unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+        assert_eq!(meta.gid(), gid);
+    }
+
+    #[test]
+    fn lifecycle() {
+        let shm_name = CString::new("/lifecycle").unwrap();
+        let exists = || unsafe { Shared::<AtomicF64>::open(&shm_name) }.is_ok();
+
+        let master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        master.persist();
+        assert!(exists());
+
+        let mut master: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+        master.set_lifecycle(Lifecycle::UnlinkOnDrop);
+        drop(master);
+        assert!(!exists());
+
+        let mut master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        master.set_lifecycle(Lifecycle::UnlinkOnLastDetach);
+        let client: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+        drop(master);
+        assert!(exists());
+        drop(client);
+        assert!(!exists());
+
+        // Any holder may hand off the unlink
+        let master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        let mut client: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+        client.set_lifecycle(Lifecycle::UnlinkOnLastDetach);
+        master.persist();
+        drop(client);
+        assert!(!exists());
+    }
+
+    #[test]
+    fn migrate() {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let (old_name, new_name) = (c"/migrate_old", c"/migrate_new");
+        let master: Shared<AtomicF32> = unsafe { Shared::create(old_name).unwrap() };
+        master.store(1.5, Relaxed);
+        let client: Shared<AtomicF32> = unsafe { Shared::open(old_name).unwrap() };
+        assert_eq!(client.moved_to(), None);
+
+        std::thread::scope(|s| {
+            let waiter = s.spawn(|| client.wait_moved(None));
+            let grown: Shared<AtomicF64> = unsafe {
+                master
+                    .migrate(new_name, |old, new: &AtomicF64| {
+                        new.store(old.load(Relaxed).into(), Relaxed)
+                    })
+                    .unwrap()
+            };
+            assert_eq!(waiter.join().unwrap().as_deref(), Some(new_name));
+
+            // Openers of the old name follow the redirect
+            let reopened: Shared<AtomicF64> = unsafe { Shared::open(old_name).unwrap() };
+            assert_eq!(reopened.load(Relaxed), 1.5);
+            assert_eq!(reopened.generation(), grown.generation());
+        });
+
+        let again = unsafe { master.migrate::<AtomicF64>(c"/migrate_again", |_, _| ()) };
+        assert!(matches!(again, Err(Error::Moved)));
+        assert!(!exists(c"/migrate_again"));
+        assert_eq!(
+            client.wait_moved(Some(Duration::ZERO)).as_deref(),
+            Some(new_name)
+        );
+    }
+
+    #[test]
+    #[cfg(not(shm_heap))]
+    fn orphaned_in_header() {
+        use std::os::unix::fs::PermissionsExt;
+
+        // The mark doesn't need the region's owner (fchmod fails with EPERM for others)
+        let shm_name = CString::new("/orphaned_in_header").unwrap();
+        let mut master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        master.set_lifecycle(Lifecycle::UnlinkOnLastDetach);
+        let client: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+        drop(master);
+
+        let header = unsafe { &*client.0.base.cast::<header::Header>() };
+        assert_eq!(header.orphaned().load(ordering::Relaxed), 1);
+        let meta = std::fs::metadata("/dev/shm/orphaned_in_header").unwrap();
+        assert_eq!(meta.permissions().mode() & ORPHANED, 0);
+        drop(client);
+        assert!(std::fs::metadata("/dev/shm/orphaned_in_header").is_err());
+    }
+
+    #[test]
+    fn stat() {
+        let shm_name = CString::new("/stat").unwrap();
+        let master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        let client: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+
+        let stat = client.stat().unwrap();
+        assert_eq!(stat.size, header::region_len::<AtomicF64>().get() as u64);
+        assert!(stat.linked);
+        assert_eq!(stat.ino, master.stat().unwrap().ino);
+
+        drop(master);
+        assert!(!client.stat().unwrap().linked);
+    }
+
+    #[test]
+    fn wait_for_peers() {
+        let shm_name = CString::new("/wait_for_peers").unwrap();
+        let master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        let timeout = Some(Duration::from_millis(20));
+        assert!(!master.wait_for_peers(1, timeout).unwrap());
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                let _client: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+                std::thread::sleep(Duration::from_millis(100));
+            });
+            assert!(master
+                .wait_for_peers(1, Some(Duration::from_secs(5)))
+                .unwrap());
+        });
+        assert!(!master.wait_for_peers(1, timeout).unwrap());
+    }
+
+    #[test]
+    fn lock_memory() {
+        let master: Shared<AtomicF64> = Shared::create_anon().unwrap();
+        let mut limit = MaybeUninit::uninit();
+        assert_eq!(
+            unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, limit.as_mut_ptr()) },
+            0
+        );
+        let limit = unsafe { limit.assume_init() }.rlim_cur;
+        match master.lock_memory() {
+            Ok(()) => {}
+            Err(Error::MemoryLock(_)) => assert!(limit < page_size() as u64),
+            Err(e) => panic!("{e}"),
+        }
+    }
+
+    #[test]
+    fn advise() {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let master: Shared<AtomicF64> = Shared::create_anon().unwrap();
+        master.store(4.5, Relaxed);
+        for advice in [Advice::Sequential, Advice::WillNeed, Advice::DontNeed] {
+            master.advise(advice).unwrap();
         }
+        assert_eq!(master.load(Relaxed), 4.5);
+    }
+
+    #[test]
+    fn anon() {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let master: Shared<AtomicF64> = Shared::create_anon().unwrap();
+        let fd = master.as_fd().try_clone_to_owned().unwrap();
+        let client: Shared<AtomicF64> = unsafe { Shared::from_fd(fd).unwrap() };
+
+        master.store(2.5, Relaxed);
+        assert_eq!(client.load(Relaxed), 2.5);
+
+        // The receiving process can't truncate the region under the creator's mapping
+        if capabilities().memfd_seals {
+            let len = NonZeroUsize::new(1).unwrap();
+            assert!(client.0.fd.resize(len).is_err());
+        }
+    }
+
+    #[test]
+    fn file_backed() {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let path = std::env::temp_dir().join(format!("shm_file_backed.{}", std::process::id()));
+        {
+            let master: Shared<AtomicF64> = unsafe { Shared::create_file(&path).unwrap() };
+            master.store(3.5, Relaxed);
+            assert!(unsafe { Shared::<AtomicF64>::create_file(&path) }.is_err());
+        }
+        let client: Shared<AtomicF64> = unsafe { Shared::open_file(&path).unwrap() };
+        assert_eq!(client.load(Relaxed), 3.5);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn generation() {
+        let shm_name = CString::new("/generation").unwrap();
+        let old: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        assert!(old.is_current().unwrap());
+
+        unlink(&shm_name).unwrap();
+        let new: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        assert!(!old.is_current().unwrap());
+        assert!(new.is_current().unwrap());
+        assert!(new.generation() > old.generation());
+        // The count survived unlinking
+        assert_eq!(
+            new.generation().unwrap() >> 32,
+            (old.generation().unwrap() >> 32) + 1
+        );
+    }
+
+    #[test]
+    fn creator() {
+        let shm_name = CString::new("/creator").unwrap();
+        let _master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        let client: Shared<AtomicF64> = unsafe { Shared::open(&shm_name).unwrap() };
+
+        let creator = client.creator().unwrap();
+        assert_eq!(creator.pid, std::process::id());
+        assert_eq!(creator.uid, unsafe { libc::getuid() });
+        assert_eq!(creator.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(creator.is_alive());
+    }
+
+    #[test]
+    fn open_timeout() {
+        let shm_name = CString::new("/open_timeout").unwrap();
+        let timeout = Duration::from_millis(20);
+        assert!(matches!(
+            unsafe { Shared::<AtomicF64>::open_timeout(&shm_name, timeout) },
+            Err(Error::Open(e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                let master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+                master.persist();
+            });
+            let mut client =
+                unsafe { Shared::<AtomicF64>::open_timeout(&shm_name, Duration::MAX).unwrap() };
+            client.set_lifecycle(Lifecycle::UnlinkOnDrop);
+        });
+    }
+
+    #[test]
+    fn open_while_sizing() {
+        let shm_name = CString::new("/open_while_sizing").unwrap();
+        // A creator which hasn't sized the region yet
+        let fd = ShmFd::create(&shm_name).unwrap();
+        fd.lock_init().unwrap();
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                let master = unsafe { Shared::<AtomicF64>::init(fd).unwrap() };
+                master.persist();
+            });
+            let mut client = unsafe { Shared::<AtomicF64>::open(&shm_name).unwrap() };
+            client.set_lifecycle(Lifecycle::UnlinkOnDrop);
+        });
+    }
+
+    #[test]
+    fn sync() {
+        use std::sync::atomic::Ordering::Relaxed;
+
+        let path = std::env::temp_dir().join(format!("shm_sync.{}", std::process::id()));
+        let shared: Shared<AtomicF64> = unsafe { Shared::create_file(&path).unwrap() };
+        shared.store(1.5, Relaxed);
+        shared.sync(SyncMode::Sync).unwrap();
+        shared.sync_range(4, 4, SyncMode::Async).unwrap();
+        assert!(shared.sync_range(4, 8, SyncMode::Sync).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn by_name() {
+        let shm_name = CString::new("/by_name").unwrap();
+        let master: Shared<AtomicF64> = unsafe { Shared::create(&shm_name).unwrap() };
+        master.persist();
+
+        assert!(exists(&shm_name));
+        let len = header::region_len::<AtomicF64>().get() as u64;
+        assert_eq!(metadata(&shm_name).unwrap().size, len);
+        unlink(&shm_name).unwrap();
+        assert!(!exists(&shm_name));
+        assert_eq!(
+            metadata(&shm_name).unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(unlink(&shm_name).is_err());
     }
 }
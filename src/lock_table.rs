@@ -0,0 +1,203 @@
+use {
+    crate::{
+        mutex::MutexGuard,
+        ordering::{Acquire, Relaxed, Release},
+        Mutex, Shareable,
+    },
+    core::{
+        hash::Hasher,
+        sync::atomic::{AtomicU32, AtomicU64},
+    },
+};
+
+const MAX_KEY_LEN: usize = 32;
+
+const EMPTY: u32 = 0;
+const NAMED: u32 = u32::MAX;
+
+#[derive(Default)]
+struct Entry {
+    /// EMPTY, NAMED or the id of the process naming the entry (see [`crate::owner`])
+    state: AtomicU32,
+    len: AtomicU32,
+    key: [AtomicU64; MAX_KEY_LEN / 8],
+    lock: Mutex<()>,
+}
+
+/// A fixed capacity table of advisory locks identified by string keys.
+///
+/// Keys are assigned a slot on first use and keep it for the lifetime of the table, so at most
+/// `N` distinct keys can be used.
+pub struct LockTable<const N: usize> {
+    entries: [Entry; N],
+}
+
+unsafe impl<const N: usize> Shareable for LockTable<N> {}
+
+impl<const N: usize> Default for LockTable<N> {
+    fn default() -> Self {
+        Self {
+            entries: core::array::from_fn(|_| Entry::default()),
+        }
+    }
+}
+
+impl<const N: usize> LockTable<N> {
+    /// The maximum length of a key in bytes.
+    pub const MAX_KEY_LEN: usize = MAX_KEY_LEN;
+
+    /// Acquires the lock named `key`.
+    ///
+    /// Returns None if the key is longer than [`Self::MAX_KEY_LEN`] or the table is full.
+    pub fn lock(&self, key: &str) -> Option<MutexGuard<'_, ()>> {
+        self.find(key).map(Mutex::lock)
+    }
+
+    /// Attempts to acquire the lock named `key` without blocking.
+    pub fn try_lock(&self, key: &str) -> Option<MutexGuard<'_, ()>> {
+        self.find(key).and_then(Mutex::try_lock)
+    }
+
+    fn find(&self, key: &str) -> Option<&Mutex<()>> {
+        let packed = pack(key)?;
+        let start = fnv1a(key.as_bytes()) as usize;
+
+        for i in 0..N {
+            let e = &self.entries[(start + i) % N];
+            loop {
+                match e.state.load(Acquire) {
+                    EMPTY => {
+                        let id = crate::owner::id(self);
+                        if e.state
+                            .compare_exchange(EMPTY, id, Acquire, Relaxed)
+                            .is_ok()
+                        {
+                            e.len.store(key.len() as u32, Relaxed);
+                            for (k, p) in e.key.iter().zip(packed) {
+                                k.store(p, Relaxed);
+                            }
+                            // Fails if the entry was reclaimed from this process meanwhile (its id
+                            // taken by another process), in which case the entry is reevaluated.
+                            if e.state
+                                .compare_exchange(id, NAMED, Release, Relaxed)
+                                .is_ok()
+                            {
+                                return Some(&e.lock);
+                            }
+                        }
+                    }
+                    NAMED => break,
+                    // The process naming this entry exited part way, so it's reclaimed.
+                    id if !crate::owner::alive(self, id) => {
+                        let _ = e.state.compare_exchange(id, EMPTY, Relaxed, Relaxed);
+                    }
+                    // Another process is naming this entry
+                    _ => std::thread::yield_now(),
+                }
+            }
+
+            if e.len.load(Relaxed) == key.len() as u32
+                && e.key.iter().zip(packed).all(|(k, p)| k.load(Relaxed) == p)
+            {
+                return Some(&e.lock);
+            }
+        }
+        None
+    }
+}
+
+fn pack(key: &str) -> Option<[u64; MAX_KEY_LEN / 8]> {
+    (key.len() <= MAX_KEY_LEN).then(|| {
+        let mut bytes = [0; MAX_KEY_LEN];
+        bytes[..key.len()].copy_from_slice(key.as_bytes());
+        core::array::from_fn(|i| u64::from_ne_bytes(bytes[i * 8..][..8].try_into().unwrap()))
+    })
+}
+
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv1a::default();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// An FNV-1a hasher, which unlike std's hashers gives every process the same hashes.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        self.0 = bytes.iter().fold(self.0, |hash, &b| {
+            (hash ^ u64::from(b)).wrapping_mul(0x100000001b3)
+        });
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_table() {
+        let table = LockTable::<2>::default();
+
+        let a = table.lock("alpha").unwrap();
+        assert!(table.try_lock("alpha").is_none());
+        let b = table.try_lock("beta").unwrap();
+
+        // Full table and oversized keys
+        assert!(table.lock("gamma").is_none());
+        assert!(table.lock(&"x".repeat(MAX_KEY_LEN + 1)).is_none());
+
+        drop(a);
+        assert!(table.try_lock("alpha").is_some());
+        drop(b);
+    }
+
+    #[test]
+    fn concurrent_claims() {
+        use std::{sync::atomic::AtomicUsize, thread};
+
+        let table = LockTable::<4>::default();
+        let holders = AtomicUsize::new(0);
+
+        // Every thread names the key at once, so all but one find the entry being claimed.
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        let _guard = table.lock("shared").unwrap();
+                        assert_eq!(holders.fetch_add(1, Relaxed), 0);
+                        holders.fetch_sub(1, Relaxed);
+                    }
+                });
+            }
+        });
+        let named = table
+            .entries
+            .iter()
+            .filter(|e| e.state.load(Relaxed) == NAMED);
+        assert_eq!(named.count(), 1);
+    }
+
+    #[test]
+    fn dead_claimer() {
+        let table = crate::Shared::<LockTable<2>>::create_anon().unwrap();
+
+        // An id no live process holds the lock of
+        let start = fnv1a(b"orphan") as usize % 2;
+        table.entries[start].state.store(i32::MAX as u32, Relaxed);
+
+        // The entry is reclaimed instead of waiting for the dead process forever.
+        assert!(table.try_lock("orphan").is_some());
+        assert_eq!(table.entries[start].state.load(Relaxed), NAMED);
+    }
+}
@@ -0,0 +1,159 @@
+//! A multi-producer single-consumer channel of serde-encoded messages (ex: Strings and Vecs which
+//! can't be stored in shared memory directly), for senders which accept the encoding cost.
+
+use {
+    crate::{ByteLog, Collector, Shareable},
+    core::marker::PhantomData,
+    serde::{de::DeserializeOwned, Serialize},
+    std::io,
+};
+
+/// A [`ByteLog`] of capacity N bytes carrying messages of type M, each encoded (with postcard's
+/// compact binary format) into one record so the log's headers frame them.
+///
+/// The encoding isn't self-describing, so M can't rely on `deserialize_any` (ex: untagged enums).
+///
+/// As messages are copied in and out, the Pod channels in [`crate::mpsc`] remain faster.
+pub struct MessageLog<M, const N: usize> {
+    log: ByteLog<N>,
+    _message: PhantomData<fn(M) -> M>,
+}
+
+unsafe impl<M, const N: usize> Shareable for MessageLog<M, N> {}
+
+impl<M, const N: usize> Default for MessageLog<M, N> {
+    fn default() -> Self {
+        Self {
+            log: ByteLog::default(),
+            _message: PhantomData,
+        }
+    }
+}
+
+impl<M: Serialize + DeserializeOwned, const N: usize> MessageLog<M, N> {
+    /// Sends without blocking, returning None if the log is full or closed.
+    ///
+    /// Fails if the message can't be encoded or its encoding exceeds
+    /// [`ByteLog::MAX_RECORD_LEN`].
+    pub fn try_send(&self, message: &M) -> io::Result<Option<u32>> {
+        let bytes = encode::<M, N>(message)?;
+        Ok(self.log.try_append(&bytes))
+    }
+
+    /// Sends, blocking while the log is full, and returns the message's sequence number.
+    ///
+    /// Fails as [`Self::try_send`], or with [`io::ErrorKind::BrokenPipe`] if the log is closed.
+    pub fn send(&self, message: &M) -> io::Result<u32> {
+        let bytes = encode::<M, N>(message)?;
+        self.log
+            .append(&bytes)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::BrokenPipe, "the log is closed"))
+    }
+
+    /// Claims the receiving end, returning None if another live process holds it.
+    pub fn receiver(&self) -> Option<MessageReceiver<'_, M, N>> {
+        Some(MessageReceiver {
+            collector: self.log.collector()?,
+            _message: PhantomData,
+        })
+    }
+}
+
+fn encode<M: Serialize, const N: usize>(message: &M) -> io::Result<Vec<u8>> {
+    let bytes = postcard::to_allocvec(message)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if bytes.len() > ByteLog::<N>::MAX_RECORD_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "message exceeds the log's capacity",
+        ));
+    }
+    Ok(bytes)
+}
+
+/// The receiving end of a [`MessageLog`], released when dropped.
+pub struct MessageReceiver<'a, M, const N: usize> {
+    collector: Collector<'a, N>,
+    _message: PhantomData<fn() -> M>,
+}
+
+impl<M: DeserializeOwned, const N: usize> MessageReceiver<'_, M, N> {
+    /// Receives the next message unless none has been sent.
+    ///
+    /// A message which fails to decode (ex: sent by a process with a different version of M) is
+    /// consumed and reported as an [`io::ErrorKind::InvalidData`] error.
+    pub fn try_recv(&mut self) -> Option<io::Result<M>> {
+        let record = self.collector.try_read()?;
+        Some(decode(&record.data))
+    }
+
+    /// Receives the next message, blocking until one is sent.
+    ///
+    /// Fails as [`Self::try_recv`].
+    pub fn recv(&mut self) -> io::Result<M> {
+        decode(&self.collector.read().data)
+    }
+}
+
+fn decode<M: DeserializeOwned>(bytes: &[u8]) -> io::Result<M> {
+    postcard::from_bytes(bytes).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::Shared, std::thread};
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Event {
+        name: String,
+        values: Vec<u32>,
+    }
+
+    #[test]
+    fn messages() {
+        let log: Shared<MessageLog<Event, 256>> = Shared::create_anon().unwrap();
+        let mut rx = log.receiver().unwrap();
+        assert!(log.receiver().is_none());
+        assert!(rx.try_recv().is_none());
+
+        let too_long = Event {
+            name: "x".repeat(256),
+            values: Vec::new(),
+        };
+        let e = log.try_send(&too_long).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+        thread::scope(|s| {
+            for t in 0..2 {
+                let log = &log;
+                s.spawn(move || {
+                    for i in 0..50 {
+                        let event = Event {
+                            name: format!("sender {t}"),
+                            values: vec![i; (i % 4) as usize],
+                        };
+                        log.send(&event).unwrap();
+                    }
+                });
+            }
+            let mut next = [0; 2];
+            for _ in 0..100 {
+                let event = rx.recv().unwrap();
+                let t = usize::from(event.name == "sender 1");
+                assert_eq!(event.values, vec![next[t]; (next[t] % 4) as usize]);
+                next[t] += 1;
+            }
+        });
+    }
+
+    #[test]
+    fn undecodable() {
+        let log = MessageLog::<Event, 64>::default();
+        let mut rx = log.receiver().unwrap();
+        // A name longer than the record
+        log.log.append(&[100, b'x']);
+        let e = rx.try_recv().unwrap().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(rx.try_recv().is_none());
+    }
+}
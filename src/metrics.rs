@@ -0,0 +1,149 @@
+//! Contention counters of each Mutex and RwLock, and usage counters of each ResourcePool.
+//!
+//! The counters are stored in the region after the lock's data, so they include acquisitions by
+//! every process. The locks are larger than their layout in `c/shm_sync.h` while this feature is
+//! enabled, so they can't be shared with C code.
+
+use {
+    crate::{ordering::Relaxed, sync::AtomicU32},
+    core::sync::atomic::AtomicU64,
+};
+
+/// A snapshot of a lock's counters (see [`crate::Mutex::stats`] and [`crate::RwLock::stats`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct LockStats {
+    pub acquisitions: u64,
+    /// Acquisitions which found the lock held (including ones which then timed out)
+    pub contended: u64,
+    /// Futex waits while acquiring (a contended acquisition may wait several times)
+    pub sleeps: u64,
+}
+
+#[derive(Debug, Default)]
+#[repr(C)]
+pub(crate) struct Counters {
+    acquisitions: AtomicU64,
+    contended: AtomicU64,
+    sleeps: AtomicU64,
+}
+
+impl Counters {
+    pub(crate) const fn new() -> Self {
+        Self {
+            acquisitions: AtomicU64::new(0),
+            contended: AtomicU64::new(0),
+            sleeps: AtomicU64::new(0),
+        }
+    }
+
+    #[inline]
+    pub(crate) fn acquired(&self) {
+        self.acquisitions.fetch_add(1, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn contended(&self) {
+        self.contended.fetch_add(1, Relaxed);
+    }
+
+    /// Counts a futex wait on `futex` while it holds `expected`.
+    #[inline]
+    pub(crate) fn sleeping(&self, futex: &AtomicU32, expected: u32) {
+        if futex.load(Relaxed) == expected {
+            self.sleeps.fetch_add(1, Relaxed);
+        }
+    }
+
+    pub(crate) fn stats(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.acquisitions.load(Relaxed),
+            contended: self.contended.load(Relaxed),
+            sleeps: self.sleeps.load(Relaxed),
+        }
+    }
+}
+
+/// A snapshot of a pool's counters (see [`crate::ResourcePool::stats`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolStats {
+    /// Objects currently leased
+    pub leased: u32,
+    /// The most objects leased at once
+    pub high_water: u32,
+    /// Checkouts refused because the process had reached its quota
+    pub quota_rejections: u64,
+}
+
+/// A process's use of a pool (see [`crate::ResourcePool::usage`]).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ProcessUsage {
+    /// The process's id in the region, as reported by [`crate::ResourcePool::leased`]
+    pub owner: u32,
+    /// Objects leased (or being checked out) by the process
+    pub leased: u32,
+    /// The most objects the process has leased at once
+    pub high_water: u32,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct PoolCounters {
+    high_water: core::sync::atomic::AtomicU32,
+    quota_rejections: AtomicU64,
+}
+
+impl PoolCounters {
+    #[inline]
+    pub(crate) fn leased(&self, leased: u32) {
+        self.high_water.fetch_max(leased, Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn rejected(&self) {
+        self.quota_rejections.fetch_add(1, Relaxed);
+    }
+
+    pub(crate) fn stats(&self, leased: u32) -> PoolStats {
+        PoolStats {
+            leased,
+            high_water: self.high_water.load(Relaxed),
+            quota_rejections: self.quota_rejections.load(Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Mutex, RwLock},
+        std::{thread, time::Duration},
+    };
+
+    #[test]
+    fn contention() {
+        let mutex = Mutex::new(0);
+        *mutex.lock() += 1;
+        thread::scope(|s| {
+            let guard = mutex.lock();
+            s.spawn(|| *mutex.lock() += 1);
+            thread::sleep(Duration::from_millis(20));
+            drop(guard);
+        });
+        let stats = mutex.stats();
+        assert_eq!(stats.acquisitions, 3);
+        assert_eq!(stats.contended, 1);
+        assert!(stats.sleeps >= 1);
+
+        let rwlock = RwLock::new(0);
+        drop(rwlock.read());
+        thread::scope(|s| {
+            let writer = rwlock.write();
+            s.spawn(|| *rwlock.read());
+            thread::sleep(Duration::from_millis(20));
+            drop(writer);
+        });
+        let stats = rwlock.stats();
+        assert_eq!(stats.acquisitions, 3);
+        assert_eq!(stats.contended, 1);
+        assert!(stats.sleeps >= 1);
+    }
+}
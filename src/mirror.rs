@@ -0,0 +1,116 @@
+use {
+    crate::{ByteLog, Collector, Record},
+    std::time::Duration,
+};
+
+type Apply<'a, T> = Box<dyn FnMut(&T, &Record) + 'a>;
+
+/// Keeps a warm standby's copy of a primary's state, by applying the updates the primary appends
+/// to a [`ByteLog`] (its write-ahead log) to a region the standby owns.
+///
+/// The primary and standby never share a writer lock: the primary only appends, and the standby
+/// is the log's collector. On failover, the standby calls [`Self::catch_up`] once the primary has
+/// died, losing only the updates it hadn't committed.
+pub struct Mirror<'a, T, const N: usize> {
+    collector: Collector<'a, N>,
+    standby: &'a T,
+    apply: Apply<'a, T>,
+    applied: Option<u32>,
+}
+
+impl<'a, T, const N: usize> Mirror<'a, T, N> {
+    /// Mirrors `log` into `standby` with `apply`, returning None if another live process collects
+    /// the log.
+    pub fn new(
+        log: &'a ByteLog<N>,
+        standby: &'a T,
+        apply: impl FnMut(&T, &Record) + 'a,
+    ) -> Option<Self> {
+        Some(Self {
+            collector: log.collector()?,
+            standby,
+            apply: Box::new(apply),
+            applied: None,
+        })
+    }
+
+    /// Mirrors only the updates tagged with one of `topics` (see [`Collector::filter`]).
+    pub fn filter(&mut self, topics: u32) {
+        self.collector.filter(topics);
+    }
+
+    /// The sequence number of the last update applied.
+    pub fn applied(&self) -> Option<u32> {
+        self.applied
+    }
+
+    /// Applies every committed update, returning how many were applied.
+    pub fn catch_up(&mut self) -> usize {
+        let mut n = 0;
+        while let Some(record) = self.collector.try_read() {
+            self.apply(&record);
+            n += 1;
+        }
+        n
+    }
+
+    /// Applies updates as they're committed until `stop` returns true, which is checked at least
+    /// every `poll`.
+    pub fn run_until(&mut self, poll: Duration, mut stop: impl FnMut() -> bool) {
+        while !stop() {
+            if let Some(record) = self.collector.read_timeout(poll) {
+                self.apply(&record);
+                self.catch_up();
+            }
+        }
+    }
+
+    fn apply(&mut self, record: &Record) {
+        (self.apply)(self.standby, record);
+        self.applied = Some(record.seq);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::Mutex,
+        core::sync::atomic::{AtomicBool, Ordering::Relaxed},
+        std::thread,
+    };
+
+    #[test]
+    fn mirror() {
+        let log = ByteLog::<64>::default();
+        let standby = Mutex::new([0u32; 4]);
+        let mut mirror = Mirror::new(&log, &standby, |state: &Mutex<[u32; 4]>, record| {
+            let [index, value] =
+                [0, 4].map(|at| u32::from_ne_bytes(record.data[at..at + 4].try_into().unwrap()));
+            state.lock()[index as usize] = value;
+        })
+        .unwrap();
+        assert!(log.collector().is_none());
+        assert_eq!(mirror.catch_up(), 0);
+
+        let update = |index: u32, value: u32| {
+            let mut data = index.to_ne_bytes().to_vec();
+            data.extend(value.to_ne_bytes());
+            log.append(&data)
+        };
+        let stop = AtomicBool::new(false);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 0..100 {
+                    update(i % 4, i);
+                }
+                stop.store(true, Relaxed);
+            });
+            mirror.run_until(Duration::from_millis(1), || stop.load(Relaxed));
+        });
+        // The primary died, so the standby applies what was committed and takes over.
+        mirror.catch_up();
+        assert_eq!(mirror.applied(), Some(99));
+        assert_eq!(*standby.lock(), [96, 97, 98, 99]);
+    }
+}
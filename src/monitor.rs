@@ -0,0 +1,150 @@
+use {
+    crate::{condvar::WaitTimeoutResult, mutex::MutexGuard, sync::const_fn, Condvar, Mutex},
+    core::{
+        fmt,
+        ops::{Deref, DerefMut},
+        time::Duration,
+    },
+    std::time::Instant,
+};
+
+/// Data protected by a Mutex together with the Condvar used to wait for changes to it.
+#[derive(Default)]
+pub struct Monitor<T> {
+    mutex: Mutex<T>,
+    condvar: Condvar,
+}
+
+impl<T: fmt::Debug> fmt::Debug for Monitor<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Monitor")
+            .field("mutex", &self.mutex)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Monitor<T> {
+    const_fn! {
+        pub fn new(value: T) -> Self {
+            Self {
+                mutex: Mutex::new(value),
+                condvar: Condvar::new(),
+            }
+        }
+    }
+
+    pub fn lock(&self) -> MonitorGuard<'_, T> {
+        MonitorGuard {
+            monitor: self,
+            guard: self.mutex.lock(),
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<MonitorGuard<'_, T>> {
+        self.mutex.try_lock().map(|guard| MonitorGuard {
+            monitor: self,
+            guard,
+        })
+    }
+
+    /// Locks the data, blocking until `condition` returns false.
+    pub fn wait_while(&self, condition: impl FnMut(&mut T) -> bool) -> MonitorGuard<'_, T> {
+        self.lock().wait_while(condition)
+    }
+
+    pub fn notify_one(&self) {
+        self.condvar.notify_one()
+    }
+
+    pub fn notify_all(&self) {
+        self.condvar.notify_all()
+    }
+}
+
+#[must_use = "if unused the Monitor will immediately unlock"]
+pub struct MonitorGuard<'a, T> {
+    monitor: &'a Monitor<T>,
+    guard: MutexGuard<'a, T>,
+}
+
+impl<T> Deref for MonitorGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for MonitorGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<'a, T> MonitorGuard<'a, T> {
+    /// Releases the lock until notified.
+    pub fn wait(self) -> Self {
+        let Self { monitor, guard } = self;
+        Self {
+            monitor,
+            // [SAFETY]: The Mutex and Condvar are fields of the same Monitor.
+            guard: unsafe { monitor.condvar.wait_requeue(guard) },
+        }
+    }
+
+    /// Releases the lock until notified or the timeout expires.
+    pub fn wait_timeout(self, dur: Duration) -> (Self, WaitTimeoutResult) {
+        let Self { monitor, guard } = self;
+        // [SAFETY]: The Mutex and Condvar are fields of the same Monitor.
+        let (guard, result) = unsafe { monitor.condvar.wait_timeout_requeue(guard, dur) };
+        (Self { monitor, guard }, result)
+    }
+
+    /// Releases the lock until notified or the deadline passes.
+    pub fn wait_deadline(self, deadline: Instant) -> (Self, WaitTimeoutResult) {
+        let Self { monitor, guard } = self;
+        // [SAFETY]: The Mutex and Condvar are fields of the same Monitor.
+        let (guard, result) = unsafe { monitor.condvar.wait_deadline_requeue(guard, deadline) };
+        (Self { monitor, guard }, result)
+    }
+
+    /// Waits for notifications until `condition` returns false.
+    pub fn wait_while(mut self, mut condition: impl FnMut(&mut T) -> bool) -> Self {
+        while condition(&mut self) {
+            self = self.wait();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{thread, time::Duration},
+    };
+
+    #[test]
+    fn monitor() {
+        let monitor = Monitor::new(0);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                for _ in 0..3 {
+                    thread::sleep(Duration::from_millis(10));
+                    *monitor.lock() += 1;
+                    monitor.notify_all();
+                }
+            });
+
+            let guard = monitor.wait_while(|v| *v < 3);
+            assert_eq!(*guard, 3);
+            let (guard, result) = guard.wait_timeout(Duration::from_millis(10));
+            assert!(result.timed_out());
+            assert_eq!(*guard, 3);
+            let (guard, result) = guard.wait_deadline(Instant::now());
+            assert!(result.timed_out());
+            assert_eq!(*guard, 3);
+        });
+    }
+}
@@ -0,0 +1,364 @@
+//! A bounded multi-producer single-consumer channel (ex: several client processes feeding one
+//! collector).
+
+use {
+    crate::{
+        gate::Gate,
+        ordering::{Acquire, Relaxed, Release},
+        spsc::claim,
+        wait::{Park, WaitStrategy},
+        Shareable,
+    },
+    core::{cell::UnsafeCell, sync::atomic::AtomicU32, time::Duration},
+    std::time::Instant,
+};
+
+struct Slot<T> {
+    /// The position the slot is next written at, plus one once written (see [`Channel`])
+    seq: AtomicU32,
+    value: UnsafeCell<T>,
+}
+
+/// The storage of a channel of capacity N (a power of two), embedded in a Shareable struct.
+///
+/// Senders claim positions atomically and publish each slot once written. The receiver takes
+/// values in claim order, so it waits on a slot which is claimed but not yet written (a sender
+/// which dies in between stalls the channel).
+///
+/// The channel may be closed to new sends (ex: to migrate the region), and drained of those in
+/// progress (see [`Channel::drain`]).
+pub struct Channel<T, const N: usize> {
+    /// Total positions claimed by senders (wrapping)
+    head: AtomicU32,
+    /// Total values received (wrapping; also the futex waited on by blocked senders)
+    tail: AtomicU32,
+    /// Incremented by every send (the futex waited on by the receiver)
+    sent: AtomicU32,
+    /// The id (see [`crate::owner`]) of the process receiving, or 0
+    receiver: AtomicU32,
+    /// Counts the sends in progress
+    senders: Gate,
+    slots: [Slot<T>; N],
+}
+
+unsafe impl<T: Send, const N: usize> Sync for Channel<T, N> {}
+
+unsafe impl<T: Shareable + Send, const N: usize> Shareable for Channel<T, N> {}
+
+impl<T: Default, const N: usize> Default for Channel<T, N> {
+    fn default() -> Self {
+        const {
+            assert!(
+                N.is_power_of_two() && N <= 1 << 31,
+                "capacity must be a power of two <= 2^31"
+            )
+        };
+        Self {
+            head: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            sent: AtomicU32::new(0),
+            receiver: AtomicU32::new(0),
+            senders: Gate::new(),
+            slots: core::array::from_fn(|i| Slot {
+                seq: AtomicU32::new(i as u32),
+                value: UnsafeCell::default(),
+            }),
+        }
+    }
+}
+
+/// Returns both ends of the channel stored in `channel`, or None if another live process holds
+/// the receiving end. Each end parks in the kernel while blocked unless given another
+/// [`WaitStrategy`] (see [`Sender::with_wait`] and [`Receiver::with_wait`]).
+pub fn channel_in<T: Default, const N: usize>(
+    channel: &Channel<T, N>,
+) -> Option<(Sender<'_, T, N>, Receiver<'_, T, N>)> {
+    Some((channel.sender(), channel.receiver()?))
+}
+
+impl<T: Default, const N: usize> Channel<T, N> {
+    pub fn sender(&self) -> Sender<'_, T, N> {
+        Sender {
+            channel: self,
+            wait: Park,
+        }
+    }
+
+    /// Claims the receiving end, returning None if another live process holds it.
+    pub fn receiver(&self) -> Option<Receiver<'_, T, N>> {
+        claim(&self.receiver).then(|| Receiver {
+            channel: self,
+            wait: Park,
+        })
+    }
+
+    /// Values sent but not yet received.
+    pub fn len(&self) -> usize {
+        self.head
+            .load(Acquire)
+            .wrapping_sub(self.tail.load(Acquire)) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Closes the channel to new sends. Values already sent may still be received.
+    pub fn close(&self) {
+        self.senders.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.senders.is_closed()
+    }
+
+    /// Closes the channel and waits until the sends in progress have finished, so only the
+    /// receiver changes it (ex: while the region is migrated). Senders blocked on a full channel
+    /// finish once the receiver makes room.
+    pub fn drain(&self) {
+        self.senders.drain();
+    }
+
+    fn slot(&self, pos: u32) -> &Slot<T> {
+        &self.slots[pos as usize & (N - 1)]
+    }
+}
+
+/// A sending end of a [`Channel`], which may be cloned (ex: one per thread).
+pub struct Sender<'a, T, const N: usize, W = Park> {
+    channel: &'a Channel<T, N>,
+    wait: W,
+}
+
+impl<T, const N: usize, W: Clone> Clone for Sender<'_, T, N, W> {
+    fn clone(&self) -> Self {
+        Self {
+            channel: self.channel,
+            wait: self.wait.clone(),
+        }
+    }
+}
+
+impl<'a, T: Default, const N: usize> Sender<'a, T, N> {
+    /// Waits with `wait` rather than parking while the channel is full.
+    pub fn with_wait<W: WaitStrategy>(self, wait: W) -> Sender<'a, T, N, W> {
+        Sender {
+            channel: self.channel,
+            wait,
+        }
+    }
+}
+
+impl<T: Default, const N: usize, W: WaitStrategy> Sender<'_, T, N, W> {
+    /// Sends without blocking, returning the value if the channel is full or closed.
+    pub fn try_send(&self, value: T) -> Result<(), T> {
+        let Some(_sending) = self.channel.senders.enter() else {
+            return Err(value);
+        };
+        self.send_entered(value)
+    }
+
+    /// Sends, blocking while the channel is full, or returns the value if the channel is closed.
+    pub fn send(&self, mut value: T) -> Result<(), T> {
+        let Some(_sending) = self.channel.senders.enter() else {
+            return Err(value);
+        };
+        loop {
+            let tail = self.channel.tail.load(Acquire);
+            match self.send_entered(value) {
+                Ok(()) => return Ok(()),
+                Err(v) => {
+                    value = v;
+                    self.wait.wait(&self.channel.tail, tail, None);
+                }
+            }
+        }
+    }
+
+    /// Sends without blocking once counted as in progress, returning the value if the channel is
+    /// full.
+    fn send_entered(&self, value: T) -> Result<(), T> {
+        let channel = self.channel;
+        let mut pos = channel.head.load(Relaxed);
+        let slot = loop {
+            let slot = channel.slot(pos);
+            // The slot is free for this position once its previous value has been received.
+            match slot.seq.load(Acquire).wrapping_sub(pos) as i32 {
+                0 => match channel.head.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Relaxed,
+                    Relaxed,
+                ) {
+                    Ok(_) => break slot,
+                    Err(actual) => pos = actual,
+                },
+                lag if lag < 0 => return Err(value),
+                _ => pos = channel.head.load(Relaxed),
+            }
+        };
+
+        // [SAFETY]: The slot is only accessed by the sender which claimed it until published.
+        unsafe { *slot.value.get() = value };
+        slot.seq.store(pos.wrapping_add(1), Release);
+
+        channel.sent.fetch_add(1, Release);
+        crate::futex::wake_one(&channel.sent);
+        self.wait.woke();
+        Ok(())
+    }
+}
+
+/// The receiving end of a [`Channel`], released when dropped.
+pub struct Receiver<'a, T, const N: usize, W = Park> {
+    channel: &'a Channel<T, N>,
+    wait: W,
+}
+
+impl<'a, T: Default, const N: usize> Receiver<'a, T, N> {
+    /// Waits with `wait` rather than parking while the channel is empty.
+    pub fn with_wait<W: WaitStrategy>(self, wait: W) -> Receiver<'a, T, N, W> {
+        // The claim carries over to the returned end.
+        let this = core::mem::ManuallyDrop::new(self);
+        Receiver {
+            channel: this.channel,
+            wait,
+        }
+    }
+}
+
+impl<T: Default, const N: usize, W: WaitStrategy> Receiver<'_, T, N, W> {
+    /// Receives the next value unless none has been sent.
+    pub fn try_recv(&mut self) -> Option<T> {
+        let channel = self.channel;
+        let pos = channel.tail.load(Relaxed);
+        let slot = channel.slot(pos);
+        if slot.seq.load(Acquire) != pos.wrapping_add(1) {
+            return None;
+        }
+        // [SAFETY]: The slot was published by its sender and isn't reused until released below.
+        let value = core::mem::take(unsafe { &mut *slot.value.get() });
+        slot.seq.store(pos.wrapping_add(N as u32), Release);
+
+        channel.tail.store(pos.wrapping_add(1), Release);
+        crate::futex::wake_all(&channel.tail);
+        self.wait.woke();
+        Some(value)
+    }
+
+    /// Receives the next value, blocking until one is sent.
+    pub fn recv(&mut self) -> T {
+        loop {
+            let sent = self.channel.sent.load(Acquire);
+            match self.try_recv() {
+                Some(value) => return value,
+                None => {
+                    self.wait.wait(&self.channel.sent, sent, None);
+                }
+            }
+        }
+    }
+
+    /// Receives the next value, blocking until one is sent or `timeout` elapses.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<T> {
+        let deadline = Instant::now().checked_add(timeout);
+        loop {
+            let sent = self.channel.sent.load(Acquire);
+            if let Some(value) = self.try_recv() {
+                return Some(value);
+            }
+            if !self.wait.wait(&self.channel.sent, sent, deadline) {
+                return self.try_recv();
+            }
+        }
+    }
+}
+
+impl<T, const N: usize, W> Drop for Receiver<'_, T, N, W> {
+    fn drop(&mut self) {
+        self.channel.receiver.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn many_senders() {
+        let channel = Channel::<u64, 4>::default();
+        let (tx, mut rx) = channel_in(&channel).unwrap();
+        assert!(channel.receiver().is_none());
+
+        for i in 0..4 {
+            tx.try_send(i).unwrap();
+        }
+        assert_eq!(tx.try_send(4), Err(4));
+        assert_eq!(rx.try_recv(), Some(0));
+        assert_eq!(rx.recv_timeout(Duration::from_millis(1)), Some(1));
+
+        thread::scope(|s| {
+            for t in 0..3 {
+                let tx = tx.clone();
+                s.spawn(move || {
+                    for i in 0..100 {
+                        tx.send(100 * (t + 1) + i).unwrap();
+                    }
+                });
+            }
+            let mut received: Vec<_> = (0..302).map(|_| rx.recv()).collect();
+            received.sort();
+            assert!(received.into_iter().eq((2..4).chain(100..400)));
+        });
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)), None);
+    }
+
+    #[test]
+    fn wait_strategies() {
+        use crate::wait::{BusySpin, SpinThenYield};
+
+        let channel = Channel::<u64, 2>::default();
+        let (tx, rx) = channel_in(&channel).unwrap();
+        let mut rx = rx.with_wait(BusySpin);
+        assert_eq!(rx.recv_timeout(Duration::from_millis(1)), None);
+
+        thread::scope(|s| {
+            let spinning = tx.clone().with_wait(SpinThenYield::default());
+            s.spawn(move || (0..50).for_each(|i| spinning.send(i).unwrap()));
+            // Parks, and is woken by the spinning receiver
+            s.spawn(move || (50..100).for_each(|i| tx.send(i).unwrap()));
+
+            let mut received: Vec<_> = (0..100).map(|_| rx.recv()).collect();
+            received.sort();
+            assert!(received.into_iter().eq(0..100));
+        });
+        assert!(channel.receiver().is_none());
+        drop(rx);
+        assert!(channel.receiver().is_some());
+    }
+
+    #[test]
+    fn drain() {
+        let channel = Channel::<u64, 2>::default();
+        let (tx, mut rx) = channel_in(&channel).unwrap();
+        tx.send(0).unwrap();
+        tx.send(1).unwrap();
+
+        thread::scope(|s| {
+            // Blocked on the full channel when it's drained
+            let sending = s.spawn(|| tx.send(2));
+            thread::sleep(Duration::from_millis(20));
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                assert_eq!(rx.recv(), 0);
+            });
+            channel.drain();
+            assert_eq!(sending.join().unwrap(), Ok(()));
+        });
+        assert!(channel.is_closed());
+        assert_eq!(tx.try_send(3), Err(3));
+        assert_eq!(tx.send(4), Err(4));
+        assert_eq!(rx.try_recv(), Some(1));
+        assert_eq!(rx.try_recv(), Some(2));
+    }
+}
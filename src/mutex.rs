@@ -1,23 +1,37 @@
 // This code derives from Rust Atomics and Locks by Mara Bos (O’Reilly).
 // Copyright 2023 Mara Bos, 978-1-098-11944-7."
 
-use core::{
-    cell::UnsafeCell,
-    ops::{Deref, DerefMut},
-    sync::atomic::{
-        AtomicU32,
-        Ordering::{Acquire, Relaxed, Release},
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        sync::{const_fn, AtomicU32},
     },
+    core::{
+        cell::UnsafeCell,
+        ops::{Deref, DerefMut},
+        time::Duration,
+    },
+    std::time::Instant,
 };
 
+/// The layout is C compatible (see `c/shm_sync.h`): a 32-bit futex word followed by the data.
+#[repr(C)]
 pub struct Mutex<T> {
     /// 0: unlocked
     /// 1: locked, no other threads waiting
     /// 2: locked, other threads waiting (contended)
     state: AtomicU32,
     data: UnsafeCell<T>,
+    #[cfg(feature = "metrics")]
+    metrics: crate::metrics::Counters,
 }
 
+// C11 ABI: `_Atomic uint32_t state` at offset 0
+#[cfg(not(loom))]
+const _: () = assert!(core::mem::offset_of!(Mutex<u8>, state) == 0);
+#[cfg(not(loom))]
+const _: () = assert!(core::mem::offset_of!(Mutex<u64>, data) == 8);
+
 #[must_use = "if unused the Mutex will immediately unlock"]
 pub struct MutexGuard<'a, T> {
     pub(crate) mutex: &'a Mutex<T>,
@@ -43,6 +57,16 @@ impl<T> DerefMut for MutexGuard<'_, T> {
 impl<T> Drop for MutexGuard<'_, T> {
     #[inline]
     fn drop(&mut self) {
+        #[cfg(feature = "trace")]
+        crate::trace::record(self.mutex, crate::trace::Op::Release);
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self.mutex, crate::audit::Access::Unlock);
+        crate::usdt::probe!("lock_release", self.mutex as *const _);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(lock = ?(self.mutex as *const Mutex<T>), "mutex released");
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::released(self.mutex);
+        crate::ordering::guard_fence();
         if self.mutex.state.swap(0, Release) == 2 {
             crate::futex::wake_one(&self.mutex.state);
         }
@@ -86,19 +110,48 @@ where
 }
 
 impl<T> Mutex<T> {
-    #[inline]
-    pub const fn new(value: T) -> Self {
-        Self {
-            state: AtomicU32::new(0),
-            data: UnsafeCell::new(value),
+    const_fn! {
+        #[inline]
+        pub fn new(value: T) -> Self {
+            Self {
+                state: AtomicU32::new(0),
+                data: UnsafeCell::new(value),
+                #[cfg(feature = "metrics")]
+                metrics: crate::metrics::Counters::new(),
+            }
         }
     }
 
+    /// Initializes an unlocked Mutex in memory not managed by this crate (ex: mapped by another
+    /// library or owned by C code).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and aligned for `Self`, and no other thread or process may
+    /// access the memory until this method returns.
+    pub unsafe fn init_in_place(ptr: *mut Self, value: T) {
+        unsafe { ptr.write(Self::new(value)) }
+    }
+
+    /// Borrows an initialized Mutex from memory not managed by this crate.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned and point to a Mutex initialized by [`Self::init_in_place`],
+    /// [`Self::new`] or the C protocol in `c/shm_sync.h`, which remains mapped for `'a`.
+    pub unsafe fn from_raw<'a>(ptr: *const Self) -> &'a Self {
+        unsafe { &*ptr }
+    }
+
     #[inline]
     pub fn try_lock(&self) -> Option<MutexGuard<T>> {
         self.state
             .compare_exchange(0, 1, Acquire, Relaxed)
-            .map(|_| MutexGuard { mutex: self })
+            .map(|_| {
+                #[cfg(feature = "fairness")]
+                crate::fairness::acquired(self, None);
+                self.guard()
+            })
             .ok()
     }
 
@@ -106,8 +159,101 @@ impl<T> Mutex<T> {
     pub fn lock(&self) -> MutexGuard<T> {
         if self.state.compare_exchange(0, 1, Acquire, Relaxed).is_err() {
             // The lock was already locked
+            #[cfg(feature = "fairness")]
+            let ticket = crate::fairness::arrive(self);
             self.lock_contended();
+            #[cfg(feature = "fairness")]
+            crate::fairness::acquired(self, Some(ticket));
+        } else {
+            #[cfg(feature = "fairness")]
+            crate::fairness::acquired(self, None);
+        }
+        self.guard()
+    }
+
+    /// Like [`Self::lock`], but gives up after `timeout` (ex: so a client can bail out instead of
+    /// hanging when the server stalls).
+    pub fn lock_timeout(&self, timeout: Duration) -> Option<MutexGuard<'_, T>> {
+        self.lock_deadline(Instant::now() + timeout)
+    }
+
+    /// Like [`Self::lock`], but gives up once `deadline` has passed.
+    pub fn lock_deadline(&self, deadline: Instant) -> Option<MutexGuard<'_, T>> {
+        if let Some(guard) = self.try_lock() {
+            return Some(guard);
         }
+        #[cfg(feature = "fairness")]
+        let ticket = crate::fairness::arrive(self);
+        let locked = self.lock_contended_until(deadline);
+        #[cfg(feature = "fairness")]
+        match locked {
+            true => crate::fairness::acquired(self, Some(ticket)),
+            false => crate::fairness::left(self, ticket),
+        }
+        locked.then(|| self.guard())
+    }
+
+    /// Like [`Self::lock`], but waits on tokio's blocking pool rather than blocking the runtime's
+    /// thread (ex: in an async server). Each contended wait occupies a pool thread, which
+    /// cancelling the returned future releases by waking the Mutex's other waiters.
+    #[cfg(all(feature = "async", not(loom)))]
+    pub async fn lock_async(&self) -> MutexGuard<'_, T> {
+        if let Some(guard) = self.try_lock() {
+            return guard;
+        }
+        crate::usdt::probe!("lock_contend", self as *const _);
+        #[cfg(feature = "metrics")]
+        self.metrics.contended();
+        #[cfg(feature = "tracing")]
+        tracing::debug!(lock = ?(self as *const Self), "mutex contended");
+        while self.state.swap(2, Acquire) != 0 {
+            #[cfg(feature = "metrics")]
+            self.metrics.sleeping(&self.state, 2);
+            crate::futex::wait_async(&self.state, 2).await;
+        }
+        #[cfg(feature = "fairness")]
+        crate::fairness::acquired(self, None);
+        self.guard()
+    }
+
+    /// Locks on behalf of a waiter which a Condvar may have requeued onto the state. The state is
+    /// left contended so the unlock also wakes the next requeued waiter.
+    pub(crate) fn lock_requeued(&self) -> MutexGuard<'_, T> {
+        while self.state.swap(2, Acquire) != 0 {
+            #[cfg(feature = "metrics")]
+            self.metrics.sleeping(&self.state, 2);
+            crate::futex::wait(&self.state, 2);
+        }
+        #[cfg(feature = "fairness")]
+        crate::fairness::acquired(self, None);
+        self.guard()
+    }
+
+    /// The contention counters, shared by every process.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> crate::metrics::LockStats {
+        self.metrics.stats()
+    }
+
+    /// The futex word, which is at the start of the Mutex.
+    pub(crate) fn state(&self) -> &AtomicU32 {
+        &self.state
+    }
+
+    #[inline]
+    fn guard(&self) -> MutexGuard<'_, T> {
+        crate::ordering::guard_fence();
+        #[cfg(feature = "trace")]
+        crate::trace::record(self, crate::trace::Op::Acquire);
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self, crate::audit::Access::Lock);
+        crate::usdt::probe!("lock_acquire", self as *const _);
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::acquired(self, crate::diagnostics::Kind::Exclusive);
+        #[cfg(feature = "metrics")]
+        self.metrics.acquired();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(lock = ?(self as *const Self), "mutex acquired");
         MutexGuard { mutex: self }
     }
 
@@ -118,10 +264,17 @@ impl<T> Mutex<T> {
 
     #[cold]
     fn lock_contended(&self) {
-        let mut spin_count = 100;
+        crate::usdt::probe!("lock_contend", self as *const _);
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("mutex_contended", lock = ?(self as *const Self)).entered();
+        #[cfg(feature = "metrics")]
+        self.metrics.contended();
+        // Loom runs the holder until it yields, so spinning would hide waits which overlap it.
+        let mut spin_count = if cfg!(loom) { 0 } else { 100 };
 
         while self.state.load(Relaxed) == 1 && spin_count > 0 {
-            core::hint::spin_loop();
+            crate::spin::relax(&self.state, 1);
             spin_count -= 1;
         }
 
@@ -130,7 +283,118 @@ impl<T> Mutex<T> {
         }
 
         while self.state.swap(2, Acquire) != 0 {
+            #[cfg(feature = "metrics")]
+            self.metrics.sleeping(&self.state, 2);
             crate::futex::wait(&self.state, 2);
         }
     }
+
+    #[cold]
+    fn lock_contended_until(&self, deadline: Instant) -> bool {
+        crate::usdt::probe!("lock_contend", self as *const _);
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("mutex_contended", lock = ?(self as *const Self)).entered();
+        #[cfg(feature = "metrics")]
+        self.metrics.contended();
+        while self.state.swap(2, Acquire) != 0 {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            #[cfg(feature = "metrics")]
+            self.metrics.sleeping(&self.state, 2);
+            crate::futex::wait_until(&self.state, 2, Some(deadline));
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, core::mem::MaybeUninit};
+
+    #[test]
+    fn foreign_memory() {
+        // Memory owned by "another library"
+        let mut buf = MaybeUninit::<Mutex<u64>>::uninit();
+        unsafe { Mutex::init_in_place(buf.as_mut_ptr(), 5) };
+
+        let mutex = unsafe { Mutex::from_raw(buf.as_ptr()) };
+        *mutex.lock() += 1;
+        assert_eq!(*mutex.try_lock().unwrap(), 6);
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let mutex = Mutex::new(0);
+        let guard = mutex.lock();
+        let timeout = Duration::from_millis(20);
+        let start = Instant::now();
+        assert!(mutex.lock_timeout(timeout).is_none());
+        assert!(start.elapsed() >= timeout);
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(timeout);
+                drop(guard);
+            });
+            *mutex.lock_timeout(Duration::from_secs(5)).unwrap() += 1;
+        });
+        assert_eq!(*mutex.lock_deadline(Instant::now()).unwrap(), 1);
+    }
+
+    #[cfg(all(feature = "async", not(loom)))]
+    #[test]
+    fn lock_async() {
+        let mutex = Mutex::new(0);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        std::thread::scope(|s| {
+            let guard = mutex.lock();
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(20));
+                drop(guard);
+            });
+            runtime.block_on(async { *mutex.lock_async().await += 1 });
+        });
+        assert_eq!(*mutex.lock(), 1);
+    }
+
+    #[cfg(all(feature = "async", not(loom)))]
+    #[test]
+    fn lock_async_cancelled() {
+        let mutex = Mutex::new(0);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .max_blocking_threads(1)
+            .build()
+            .unwrap();
+        let _guard = mutex.lock();
+        runtime.block_on(async {
+            let wait = tokio::time::timeout(Duration::from_millis(10), mutex.lock_async());
+            assert!(wait.await.is_err());
+            // The only pool thread is released rather than left waiting on the Mutex.
+            let start = Instant::now();
+            tokio::task::spawn_blocking(|| ()).await.unwrap();
+            assert!(start.elapsed() < Duration::from_millis(50));
+        });
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn loom_mutex() {
+        use loom::{sync::Arc, thread};
+
+        loom::model(|| {
+            let mutex = Arc::new(Mutex::new(0));
+            let other = thread::spawn({
+                let mutex = mutex.clone();
+                move || *mutex.lock() += 1
+            });
+            *mutex.lock() += 1;
+            other.join().unwrap();
+            assert_eq!(*mutex.lock(), 2);
+        });
+    }
 }
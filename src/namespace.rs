@@ -0,0 +1,107 @@
+use {
+    crate::lock_table::fnv1a,
+    std::{ffi::CString, io},
+};
+
+/// Derives concrete region names from a logical name, so several users or instances on one host
+/// can run the same binaries without their names colliding.
+///
+/// The concrete name is the logical name suffixed with a hash of the selected components (ex:
+/// `/telemetry.0123456789abcdef`). Every process sharing a region must select the same components;
+/// tooling can compute the name with [`Self::name`].
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Namespace {
+    uid: Option<libc::uid_t>,
+    boot_id: Option<String>,
+    tag: Option<String>,
+}
+
+impl Namespace {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Isolates users by the effective uid of this process.
+    pub fn per_user(self) -> Self {
+        self.uid(unsafe { libc::geteuid() })
+    }
+
+    /// Isolates the given user (ex: to inspect another user's regions).
+    pub fn uid(mut self, uid: libc::uid_t) -> Self {
+        self.uid = Some(uid);
+        self
+    }
+
+    /// Isolates boots, so regions persisted before a reboot are never mistaken for current ones.
+    pub fn per_boot(mut self) -> io::Result<Self> {
+        let id = std::fs::read_to_string("/proc/sys/kernel/random/boot_id")?;
+        self.boot_id = Some(id.trim().into());
+        Ok(self)
+    }
+
+    /// Isolates versions of this crate, whose region layouts may differ.
+    pub fn per_crate_version(self) -> Self {
+        self.tag(concat!("shm ", env!("CARGO_PKG_VERSION")))
+    }
+
+    /// Isolates instances by an arbitrary tag (ex: an application version or instance id).
+    /// Tags accumulate.
+    pub fn tag(mut self, tag: impl AsRef<str>) -> Self {
+        let tag = tag.as_ref();
+        self.tag = Some(match self.tag.take() {
+            Some(tags) => format!("{tags}\0{tag}"),
+            None => tag.into(),
+        });
+        self
+    }
+
+    /// The concrete name of the region with the logical `name` (with or without a leading '/').
+    ///
+    /// # Panics
+    ///
+    /// If `name` contains a NUL.
+    pub fn name(&self, name: &str) -> CString {
+        let name = name.strip_prefix('/').unwrap_or(name);
+        let mut key = Vec::new();
+        if let Some(uid) = self.uid {
+            key.extend(b"uid\0");
+            key.extend(uid.to_ne_bytes());
+        }
+        for (label, value) in [("boot", &self.boot_id), ("tag", &self.tag)] {
+            if let Some(value) = value {
+                key.extend(label.bytes().chain([0]));
+                key.extend(value.bytes().chain([0]));
+            }
+        }
+        CString::new(format!("/{name}.{:016x}", fnv1a(&key))).expect("name contains a NUL")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        let ns = Namespace::new().uid(1000).tag("a");
+        assert_eq!(ns.name("/region"), ns.clone().name("region"));
+        assert!(ns.name("region").to_str().unwrap().starts_with("/region."));
+        assert_ne!(
+            ns.name("region"),
+            Namespace::new().uid(1001).tag("a").name("region")
+        );
+        assert_ne!(ns.name("region"), ns.clone().tag("b").name("region"));
+        assert_ne!(ns.name("region"), Namespace::new().name("region"));
+
+        // The same boot and user from any process
+        let ns = Namespace::new().per_user().per_boot().unwrap();
+        assert_eq!(
+            ns.name("region"),
+            Namespace::new()
+                .per_user()
+                .per_boot()
+                .unwrap()
+                .name("region")
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use std::io;
+
+/// Migrate pages already allocated elsewhere (if only mapped by this process)
+const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+
+/// Where the pages of a region are allocated on a multi-socket machine.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumaPolicy {
+    /// Only on the node
+    Bind(u32),
+    /// On the node while it has free memory, otherwise elsewhere
+    Preferred(u32),
+    /// Round-robin across the nodes in the mask (bit n for node n)
+    Interleave(u64),
+}
+
+/// Applies `policy` to the pages of the mapping at `ptr`. Being a shared memory mapping, the
+/// policy belongs to the region itself, so it also applies to pages allocated by other processes.
+pub(crate) fn bind(ptr: *mut u8, len: usize, policy: NumaPolicy) -> io::Result<()> {
+    let (mode, nodes) = match policy {
+        NumaPolicy::Bind(node) => (libc::MPOL_BIND, node_mask(node)?),
+        NumaPolicy::Preferred(node) => (libc::MPOL_PREFERRED, node_mask(node)?),
+        NumaPolicy::Interleave(nodes) => (libc::MPOL_INTERLEAVE, nodes),
+    };
+    // The kernel ignores the last bit of `maxnode`.
+    let maxnode = u64::BITS as libc::c_ulong + 1;
+    match unsafe {
+        libc::syscall(
+            libc::SYS_mbind,
+            ptr,
+            len,
+            mode,
+            &nodes as *const u64,
+            maxnode,
+            MPOL_MF_MOVE,
+        )
+    } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+fn node_mask(node: u32) -> io::Result<u64> {
+    1u64.checked_shl(node)
+        .ok_or_else(|| io::Error::from_raw_os_error(libc::EINVAL))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::page_size};
+
+    #[test]
+    fn node_zero() {
+        let len = page_size();
+        let ptr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        assert_ne!(ptr, libc::MAP_FAILED);
+
+        // Kernels without NUMA support don't implement mbind.
+        for policy in [NumaPolicy::Bind(0), NumaPolicy::Interleave(1)] {
+            match bind(ptr.cast(), len, policy) {
+                Err(e) if e.raw_os_error() == Some(libc::ENOSYS) => {}
+                result => result.unwrap(),
+            }
+        }
+        assert!(bind(ptr.cast(), len, NumaPolicy::Bind(64)).is_err());
+        unsafe { libc::munmap(ptr, len) };
+    }
+}
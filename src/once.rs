@@ -0,0 +1,215 @@
+use {
+    crate::{
+        ordering::{Acquire, Release},
+        Shareable,
+    },
+    core::{cell::UnsafeCell, mem::MaybeUninit, sync::atomic::AtomicU32, time::Duration},
+};
+
+const INCOMPLETE: u32 = 0;
+/// Combined with the initializer's id shifted by [`ID_SHIFT`]
+const RUNNING: u32 = 1;
+const POISONED: u32 = 2;
+const COMPLETE: u32 = 3;
+
+/// The phase is in the low bits of the state, below the initializer's id
+const PHASE: u32 = 0b11;
+const ID_SHIFT: u32 = 2;
+
+/// How often waiters check whether the initializer has died.
+const INITIALIZER_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A one-time initialization shared by all attached processes.
+///
+/// If the initializer dies before completing, waiters poison the Once rather than waiting
+/// forever.
+#[derive(Default)]
+pub struct Once {
+    /// INCOMPLETE, POISONED or COMPLETE, or RUNNING with the id (see [`crate::owner`]) of the
+    /// initializing process (also the futex waited on while running)
+    state: AtomicU32,
+}
+
+unsafe impl Shareable for Once {}
+
+/// Poisons the Once if the initializer unwinds.
+struct RunningGuard<'a> {
+    once: &'a Once,
+    state: u32,
+}
+
+impl Drop for RunningGuard<'_> {
+    fn drop(&mut self) {
+        self.once.state.store(self.state, Release);
+        crate::futex::wake_all(&self.once.state);
+    }
+}
+
+impl Once {
+    pub const fn new() -> Self {
+        Self {
+            state: AtomicU32::new(INCOMPLETE),
+        }
+    }
+
+    pub fn is_completed(&self) -> bool {
+        self.state.load(Acquire) == COMPLETE
+    }
+
+    /// Returns true if an initializer panicked.
+    pub fn is_poisoned(&self) -> bool {
+        self.state.load(Acquire) == POISONED
+    }
+
+    /// Runs `f` if no process has yet completed the initialization, blocking until the
+    /// initialization is complete.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an initializer (this or a previous one) panicked.
+    pub fn call_once(&self, f: impl FnOnce()) {
+        if !self.is_completed() {
+            self.call_once_slow(f);
+        }
+    }
+
+    #[cold]
+    fn call_once_slow(&self, f: impl FnOnce()) {
+        let mut state = self.state.load(Acquire);
+        loop {
+            match state {
+                COMPLETE => return,
+                POISONED => panic!("Once instance has previously been poisoned"),
+                s if s & PHASE == RUNNING => {
+                    if !crate::futex::wait_timeout(&self.state, s, Some(INITIALIZER_POLL_INTERVAL))
+                        && !crate::owner::alive(self, s >> ID_SHIFT)
+                        && self
+                            .state
+                            .compare_exchange(s, POISONED, Acquire, Acquire)
+                            .is_ok()
+                    {
+                        crate::futex::wake_all(&self.state);
+                    }
+                    state = self.state.load(Acquire);
+                }
+                _ => match self.state.compare_exchange(
+                    INCOMPLETE,
+                    RUNNING | crate::owner::id(self) << ID_SHIFT,
+                    Acquire,
+                    Acquire,
+                ) {
+                    Ok(_) => {
+                        let mut guard = RunningGuard {
+                            once: self,
+                            state: POISONED,
+                        };
+                        f();
+                        guard.state = COMPLETE;
+                        return;
+                    }
+                    Err(s) => state = s,
+                },
+            }
+        }
+    }
+}
+
+/// A value initialized by exactly one of the attached processes.
+pub struct OnceLock<T> {
+    once: Once,
+    value: UnsafeCell<MaybeUninit<T>>,
+}
+
+unsafe impl<T: Send + Sync> Sync for OnceLock<T> {}
+
+unsafe impl<T: Shareable + Send> Shareable for OnceLock<T> {}
+
+impl<T> Default for OnceLock<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for OnceLock<T> {
+    fn drop(&mut self) {
+        if self.once.is_completed() {
+            unsafe { self.value.get_mut().assume_init_drop() };
+        }
+    }
+}
+
+impl<T> OnceLock<T> {
+    pub const fn new() -> Self {
+        Self {
+            once: Once::new(),
+            value: UnsafeCell::new(MaybeUninit::uninit()),
+        }
+    }
+
+    /// Returns the value if it has been initialized.
+    pub fn get(&self) -> Option<&T> {
+        self.once
+            .is_completed()
+            .then(|| unsafe { (*self.value.get()).assume_init_ref() })
+    }
+
+    /// Returns the value, initializing it with `f` if no process has yet done so.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an initializer (this or a previous one) panicked.
+    pub fn get_or_init(&self, f: impl FnOnce() -> T) -> &T {
+        self.once.call_once(|| unsafe {
+            (*self.value.get()).write(f());
+        });
+        unsafe { (*self.value.get()).assume_init_ref() }
+    }
+
+    /// Initializes the value, returning it back if the lock was already initialized.
+    pub fn set(&self, value: T) -> Result<(), T> {
+        let mut value = Some(value);
+        self.get_or_init(|| value.take().unwrap());
+        value.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn once_lock() {
+        let lock = OnceLock::<u32>::new();
+        assert!(lock.get().is_none());
+
+        thread::scope(|s| {
+            for i in 0..4 {
+                let lock = &lock;
+                s.spawn(move || lock.get_or_init(|| i));
+            }
+        });
+        let value = *lock.get().unwrap();
+        assert_eq!(lock.set(value + 1), Err(value + 1));
+    }
+
+    #[test]
+    fn poison() {
+        let once = Once::new();
+        let result =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| once.call_once(|| panic!())));
+        assert!(result.is_err());
+        assert!(once.is_poisoned());
+    }
+
+    #[test]
+    fn dead_initializer() {
+        // Initializing in a process which has since died (ids are below 2^30)
+        let dead = u32::MAX >> ID_SHIFT;
+        let once = Once {
+            state: AtomicU32::new(RUNNING | dead << ID_SHIFT),
+        };
+        let result = std::panic::catch_unwind(|| once.call_once(|| {}));
+        assert!(result.is_err());
+        assert!(once.is_poisoned());
+    }
+}
@@ -0,0 +1,117 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::{cell::UnsafeCell, sync::atomic::AtomicU32, time::Duration},
+    std::time::Instant,
+};
+
+const EMPTY: u32 = 0;
+const WRITING: u32 = 1;
+const SENT: u32 = 2;
+const TAKEN: u32 = 3;
+
+/// A cell through which one process sends exactly one value to another (ex: a server
+/// acknowledging a client's handshake during startup).
+#[derive(Default)]
+pub struct Oneshot<T> {
+    /// EMPTY, WRITING, SENT or TAKEN (also the futex waited on by the receiver)
+    state: AtomicU32,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send> Sync for Oneshot<T> {}
+
+unsafe impl<T: Shareable + Send> Shareable for Oneshot<T> {}
+
+impl<T: Default> Oneshot<T> {
+    /// Sends the value, returning it if a value was already sent.
+    pub fn send(&self, value: T) -> Result<(), T> {
+        if self
+            .state
+            .compare_exchange(EMPTY, WRITING, Relaxed, Relaxed)
+            .is_err()
+        {
+            return Err(value);
+        }
+        // [SAFETY]: Only the sender which moved the state to WRITING accesses the value.
+        unsafe { *self.value.get() = value };
+        self.state.store(SENT, Release);
+        crate::futex::wake_all(&self.state);
+        Ok(())
+    }
+
+    pub fn is_sent(&self) -> bool {
+        self.state.load(Acquire) >= SENT
+    }
+
+    /// Takes the value if it has been sent (and not already taken).
+    pub fn try_recv(&self) -> Option<T> {
+        self.state
+            .compare_exchange(SENT, TAKEN, Acquire, Relaxed)
+            .ok()
+            // [SAFETY]: Only the receiver which moved the state to TAKEN accesses the value.
+            .map(|_| core::mem::take(unsafe { &mut *self.value.get() }))
+    }
+
+    /// Blocks until the value is sent, returning None if it was already taken.
+    pub fn recv(&self) -> Option<T> {
+        self.recv_until(None)
+    }
+
+    /// Blocks until the value is sent or `timeout` elapses, returning None on timeout or if the
+    /// value was already taken.
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<T> {
+        self.recv_until(Instant::now().checked_add(timeout))
+    }
+
+    fn recv_until(&self, deadline: Option<Instant>) -> Option<T> {
+        loop {
+            match self.state.load(Acquire) {
+                SENT => return self.try_recv(),
+                TAKEN => return None,
+                state => {
+                    if !crate::futex::wait_until(&self.state, state, deadline) {
+                        return self.try_recv();
+                    }
+                }
+            }
+        }
+    }
+
+    /// Empties the cell so it can be reused for another exchange, dropping any value not yet
+    /// received. Returns false if a send is in progress.
+    pub fn reset(&self) -> bool {
+        drop(self.try_recv());
+        self.state
+            .compare_exchange(TAKEN, EMPTY, Release, Relaxed)
+            .is_ok()
+            || self.state.load(Relaxed) == EMPTY
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn oneshot() {
+        let oneshot = Oneshot::<u64>::default();
+        assert_eq!(oneshot.recv_timeout(Duration::from_millis(10)), None);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                oneshot.send(7).unwrap();
+            });
+            assert_eq!(oneshot.recv(), Some(7));
+        });
+        assert_eq!(oneshot.send(8), Err(8));
+        assert_eq!(oneshot.recv(), None);
+
+        assert!(oneshot.reset());
+        oneshot.send(9).unwrap();
+        assert_eq!(oneshot.try_recv(), Some(9));
+    }
+}
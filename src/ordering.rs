@@ -0,0 +1,27 @@
+// Memory orderings used by the synchronization primitives.
+//
+// With the `seqcst` feature every ordering is upgraded to SeqCst and guard boundaries gain a
+// full fence, which helps to bisect suspected memory-ordering bugs by behavior difference.
+
+#[cfg(not(feature = "seqcst"))]
+pub(crate) use core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst};
+
+#[cfg(feature = "seqcst")]
+#[allow(non_upper_case_globals)]
+mod seqcst {
+    pub(crate) use core::sync::atomic::Ordering::SeqCst;
+    use core::sync::atomic::Ordering;
+
+    pub(crate) const AcqRel: Ordering = SeqCst;
+    pub(crate) const Relaxed: Ordering = SeqCst;
+    pub(crate) const Acquire: Ordering = SeqCst;
+    pub(crate) const Release: Ordering = SeqCst;
+}
+#[cfg(feature = "seqcst")]
+pub(crate) use seqcst::{AcqRel, Acquire, Relaxed, Release, SeqCst};
+
+#[inline]
+pub(crate) fn guard_fence() {
+    #[cfg(feature = "seqcst")]
+    core::sync::atomic::fence(core::sync::atomic::Ordering::SeqCst);
+}
@@ -0,0 +1,192 @@
+//! Identifies the process holding a role in a region (ex: a ring's producer, or a client's slot)
+//! by an id recorded in the region, so others can tell once it has died.
+//!
+//! Pids can't be used, as a process in another pid namespace sees a live process's pid as
+//! another process (or none). Instead each process takes an id in every region it maps, by write
+//! locking the byte at `LIVENESS_BASE` plus the id (far beyond the region's end) with an open file
+//! description lock on the region's descriptor. The lock is visible to every process with the
+//! region open, ensures no two live processes share an id, and is released when the process
+//! exits (or unmaps the region). As with pids, an id may later be taken by another process.
+//!
+//! Memory which isn't part of a mapped region (ex: a primitive on the stack) is private to this
+//! process, so its id is the pid and no other id is alive.
+
+use std::{
+    ffi::c_void,
+    fs::OpenOptions,
+    hash::{BuildHasher, RandomState},
+    io,
+    os::fd::{AsRawFd, BorrowedFd, OwnedFd},
+    sync::{Arc, Mutex, PoisonError, RwLock},
+};
+
+/// The offset of the byte locked for id 0
+const LIVENESS_BASE: i64 = 1 << 62;
+
+/// Ids are below this, leaving the top bits of the words recording them free for flags
+const MAX_ID: u32 = 1 << 30;
+
+/// The mapped regions
+static MAPPINGS: RwLock<Vec<Arc<Mapping>>> = RwLock::new(Vec::new());
+
+struct Mapping {
+    start: usize,
+    len: usize,
+    /// A duplicate of the region's descriptor, through which liveness is queried
+    fd: OwnedFd,
+    /// This process's id in the region, taken on first use
+    id: Mutex<Option<Id>>,
+}
+
+struct Id {
+    id: u32,
+    /// The process which took the id, as a child forked since must take its own
+    pid: u32,
+    /// The open file description holding the id's lock
+    _lock: OwnedFd,
+}
+
+impl Mapping {
+    /// This process's id, unless it couldn't take one.
+    fn own_id(&self) -> Option<u32> {
+        let pid = std::process::id();
+        let mut id = self.id.lock().unwrap_or_else(PoisonError::into_inner);
+        if id.as_ref().is_none_or(|id| id.pid != pid) {
+            *id = self.take_id(pid).ok();
+        }
+        id.as_ref().map(|id| id.id)
+    }
+
+    fn take_id(&self, pid: u32) -> io::Result<Id> {
+        // A description of its own, unless /proc is unavailable, so a forked child's lock isn't
+        // shared with its parent
+        let lock = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!("/proc/self/fd/{}", self.fd.as_raw_fd()))
+            .map(OwnedFd::from)
+            .or_else(|_| self.fd.try_clone())?;
+        loop {
+            let id = (RandomState::new().hash_one(pid) as u32) % (MAX_ID - 1) + 1;
+            match ofd_lock(&lock, libc::F_OFD_SETLK, libc::F_WRLCK, id) {
+                Ok(_) => {
+                    return Ok(Id {
+                        id,
+                        pid,
+                        _lock: lock,
+                    })
+                }
+                // Another live process has the id
+                Err(e) if matches!(e.raw_os_error(), Some(libc::EAGAIN | libc::EACCES)) => {}
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+/// Registers the mapping of `len` bytes at `ptr` of the region open as `fd`.
+pub(crate) fn mapped(ptr: *mut c_void, len: usize, fd: BorrowedFd) {
+    let Ok(fd) = fd.try_clone_to_owned() else {
+        return;
+    };
+    let mapping = Arc::new(Mapping {
+        start: ptr as usize,
+        len,
+        fd,
+        id: Mutex::new(None),
+    });
+    MAPPINGS
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .push(mapping);
+}
+
+/// Unregisters the mapping at `ptr`, releasing this process's id in it.
+pub(crate) fn unmapped(ptr: *mut c_void) {
+    let mut mappings = MAPPINGS.write().unwrap_or_else(PoisonError::into_inner);
+    // Regions mapped more than once at the same address (by the heap backend) are looked up by
+    // their first mapping, whose id must outlive the others.
+    if let Some(i) = mappings.iter().rposition(|m| m.start == ptr as usize) {
+        mappings.remove(i);
+    }
+}
+
+fn mapping<T: ?Sized>(within: &T) -> Option<Arc<Mapping>> {
+    let addr = within as *const T as *const u8 as usize;
+    let mappings = MAPPINGS.read().unwrap_or_else(PoisonError::into_inner);
+    mappings
+        .iter()
+        .find(|m| (m.start..m.start + m.len).contains(&addr))
+        .cloned()
+}
+
+/// This process's id in the region holding `within`, which is nonzero and below 2^30.
+///
+/// Falls back to the pid if open file description locks are unsupported, in which case no
+/// process can be told apart from a dead one (see [`alive`]).
+pub(crate) fn id<T: ?Sized>(within: &T) -> u32 {
+    let pid = std::process::id();
+    mapping(within).and_then(|m| m.own_id()).unwrap_or(pid)
+}
+
+/// Whether the process which took `id` in the region holding `within` is alive. Processes are
+/// assumed alive if their liveness can't be queried.
+pub(crate) fn alive<T: ?Sized>(within: &T, id: u32) -> bool {
+    let Some(mapping) = mapping(within) else {
+        return id == std::process::id();
+    };
+    // A lock isn't a conflict for the description holding it
+    mapping.own_id() == Some(id)
+        || !matches!(
+            ofd_lock(&mapping.fd, libc::F_OFD_GETLK, libc::F_WRLCK, id),
+            Ok(lock) if lock.l_type == libc::F_UNLCK as libc::c_short
+        )
+}
+
+fn ofd_lock(fd: &OwnedFd, cmd: libc::c_int, kind: libc::c_int, id: u32) -> io::Result<libc::flock> {
+    let mut lock: libc::flock = unsafe { std::mem::zeroed() };
+    lock.l_type = kind as libc::c_short;
+    lock.l_whence = libc::SEEK_SET as libc::c_short;
+    lock.l_start = LIVENESS_BASE + i64::from(id);
+    lock.l_len = 1;
+    match unsafe { libc::fcntl(fd.as_raw_fd(), cmd, &mut lock) } {
+        -1 => Err(io::Error::last_os_error()),
+        _ => Ok(lock),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Futex, Shared},
+        std::{os::fd::AsFd, sync::atomic::AtomicU32},
+    };
+
+    #[test]
+    fn liveness() {
+        let shared = Shared::<Futex>::create_anon().unwrap();
+        let own = id(&*shared);
+        assert!(own > 0 && own < MAX_ID);
+        assert_eq!(id(&*shared), own);
+        assert!(alive(&*shared, own));
+
+        // Another process's id, held by its own open file description until it exits
+        let other = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(format!("/proc/self/fd/{}", shared.as_fd().as_raw_fd()))
+            .map(OwnedFd::from)
+            .unwrap();
+        let id = own % (MAX_ID - 1) + 1;
+        ofd_lock(&other, libc::F_OFD_SETLK, libc::F_WRLCK, id).unwrap();
+        assert!(alive(&*shared, id));
+        drop(other);
+        assert!(!alive(&*shared, id));
+
+        // Memory only this process can access
+        let private = AtomicU32::new(0);
+        assert_eq!(super::id(&private), std::process::id());
+        assert!(!alive(&private, std::process::id() + 1));
+    }
+}
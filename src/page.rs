@@ -0,0 +1,56 @@
+use core::sync::atomic::{AtomicUsize, Ordering::Relaxed};
+
+/// The system page size in bytes.
+pub fn page_size() -> usize {
+    static PAGE_SIZE: AtomicUsize = AtomicUsize::new(0);
+
+    match PAGE_SIZE.load(Relaxed) {
+        0 => {
+            let size = usize::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) })
+                .ok()
+                .filter(|size| size.is_power_of_two())
+                .expect("invalid page size");
+            PAGE_SIZE.store(size, Relaxed);
+            size
+        }
+        size => size,
+    }
+}
+
+/// Rounds `len` up to a multiple of the page size, or None on overflow.
+pub fn round_up_to_page(len: usize) -> Option<usize> {
+    align_up(len, page_size())
+}
+
+/// Rounds `value` up to a multiple of `align`, which must be a power of two.
+///
+/// Returns None on overflow.
+pub fn align_up(value: usize, align: usize) -> Option<usize> {
+    assert!(align.is_power_of_two(), "alignment must be a power of two");
+    value.checked_add(align - 1).map(|v| v & !(align - 1))
+}
+
+/// Returns true if `addr` is a multiple of `align`, which must be a power of two.
+pub fn is_aligned(addr: usize, align: usize) -> bool {
+    assert!(align.is_power_of_two(), "alignment must be a power of two");
+    addr & (align - 1) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_alignment() {
+        let page = page_size();
+        assert!(page >= 4096 && page.is_power_of_two());
+        assert_eq!(round_up_to_page(0), Some(0));
+        assert_eq!(round_up_to_page(1), Some(page));
+        assert_eq!(round_up_to_page(page), Some(page));
+        assert_eq!(round_up_to_page(usize::MAX), None);
+
+        assert_eq!(align_up(13, 8), Some(16));
+        assert!(is_aligned(64, 16));
+        assert!(!is_aligned(65, 16));
+    }
+}
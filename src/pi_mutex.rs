@@ -0,0 +1,233 @@
+use {
+    crate::ordering::{Acquire, Relaxed, Release},
+    core::{
+        cell::UnsafeCell,
+        marker::PhantomData,
+        ops::{Deref, DerefMut},
+        sync::atomic::AtomicU32,
+    },
+};
+
+/// A priority-inheritance mutex for real-time processes of mixed priorities: while a thread waits,
+/// the kernel boosts the owner to the waiter's priority so a medium-priority thread can't starve
+/// the owner (priority inversion).
+///
+/// The state follows the kernel PI futex protocol (see futex(2)): 0 if unlocked, otherwise the
+/// owner's thread id, with FUTEX_WAITERS set by the kernel while threads are blocked. Uncontended
+/// locking stays in userspace. Heavier than [`crate::Mutex`] when contended.
+///
+/// The kernel doesn't release the lock of a thread which exits holding it (no robust list is
+/// registered), so the next thread to lock it takes it over and is told so by
+/// [`PiMutexGuard::owner_died`]. Threads already blocked on it are handed the lock by the kernel
+/// without being told. Thread ids are resolved in the caller's pid namespace, so the processes
+/// sharing a PiMutex must share one.
+pub struct PiMutex<T> {
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T> Sync for PiMutex<T> where T: Send {}
+
+impl<T: Default> Default for PiMutex<T> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T> PiMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<PiMutexGuard<'_, T>> {
+        let tid = tid();
+        if self
+            .state
+            .compare_exchange(0, tid, Acquire, Relaxed)
+            .is_ok()
+        {
+            return Some(self.guard(false));
+        }
+        loop {
+            if self.futex(libc::FUTEX_TRYLOCK_PI) == 0 {
+                return Some(self.guard(false));
+            }
+            match std::io::Error::last_os_error().raw_os_error() {
+                Some(libc::ESRCH) if self.reclaim() => return Some(self.guard(true)),
+                // Another thread changed the state first
+                Some(libc::ESRCH) => {}
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn lock(&self) -> PiMutexGuard<'_, T> {
+        let mut owner_died = false;
+        if self
+            .state
+            .compare_exchange(0, tid(), Acquire, Relaxed)
+            .is_err()
+        {
+            crate::usdt::probe!("lock_contend", self as *const _);
+            while self.futex(libc::FUTEX_LOCK_PI) != 0 {
+                let e = std::io::Error::last_os_error();
+                match e.raw_os_error() {
+                    // Retried if interrupted by a signal (or while an exiting owner is cleaned up)
+                    Some(libc::EINTR | libc::EAGAIN) => {}
+                    Some(libc::ESRCH) if self.reclaim() => {
+                        owner_died = true;
+                        break;
+                    }
+                    Some(libc::ESRCH) => {}
+                    _ => panic!("unable to lock PiMutex: {e}"),
+                }
+            }
+        }
+        self.guard(owner_died)
+    }
+
+    /// Takes over the lock from an owner which no longer exists (the kernel fails with ESRCH
+    /// rather than blocking on it). Returns false if the state changed in the meantime.
+    fn reclaim(&self) -> bool {
+        let s = self.state.load(Relaxed);
+        // Waiters are kept so unlocking still goes through the kernel.
+        s & libc::FUTEX_TID_MASK != 0
+            && self
+                .state
+                .compare_exchange(s, tid() | (s & libc::FUTEX_WAITERS), Acquire, Relaxed)
+                .is_ok()
+    }
+
+    fn futex(&self, op: libc::c_int) -> libc::c_long {
+        unsafe {
+            libc::syscall(
+                libc::SYS_futex,
+                &self.state,
+                op,
+                0,
+                core::ptr::null::<libc::timespec>(),
+            )
+        }
+    }
+
+    fn guard(&self, owner_died: bool) -> PiMutexGuard<'_, T> {
+        crate::ordering::guard_fence();
+        #[cfg(feature = "trace")]
+        crate::trace::record(self, crate::trace::Op::Acquire);
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self, crate::audit::Access::Lock);
+        crate::usdt::probe!("lock_acquire", self as *const _);
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::acquired(self, crate::diagnostics::Kind::Exclusive);
+        PiMutexGuard {
+            mutex: self,
+            owner_died,
+            _owner_thread: PhantomData,
+        }
+    }
+}
+
+fn tid() -> u32 {
+    unsafe { libc::gettid() as u32 }
+}
+
+/// Must be dropped by the thread which locked it, as the kernel tracks the owning thread.
+#[must_use = "if unused the PiMutex will immediately unlock"]
+pub struct PiMutexGuard<'a, T> {
+    mutex: &'a PiMutex<T>,
+    owner_died: bool,
+    _owner_thread: PhantomData<*const ()>,
+}
+
+impl<T> PiMutexGuard<'_, T> {
+    /// True if the lock was taken over from a thread which exited holding it.
+    pub fn owner_died(&self) -> bool {
+        self.owner_died
+    }
+}
+
+impl<T> Deref for PiMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: The very existence of this Guard guarantees we've exclusively acquired the lock.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for PiMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: The very existence of this Guard guarantees we've exclusively acquired the lock.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for PiMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "trace")]
+        crate::trace::record(self.mutex, crate::trace::Op::Release);
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self.mutex, crate::audit::Access::Unlock);
+        crate::usdt::probe!("lock_release", self.mutex as *const _);
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::released(self.mutex);
+        crate::ordering::guard_fence();
+        let state = &self.mutex.state;
+        if state.compare_exchange(tid(), 0, Release, Relaxed).is_err() {
+            // Waiters are blocked in the kernel, which hands the lock to the highest priority one.
+            self.mutex.futex(libc::FUTEX_UNLOCK_PI);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn pi_mutex() {
+        let mutex = PiMutex::new(0);
+        let guard = mutex.lock();
+        assert_eq!(mutex.state.load(Relaxed), tid());
+        thread::scope(|s| assert!(s.spawn(|| mutex.try_lock().is_none()).join().unwrap()));
+        drop(guard);
+
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        *mutex.lock() += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(*mutex.lock(), 4000);
+        assert_eq!(mutex.state.load(Relaxed), 0);
+    }
+
+    #[test]
+    fn owner_died() {
+        let mutex = PiMutex::new(0);
+        // Joined explicitly, as the end of a scope doesn't wait for its threads to exit
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut guard = mutex.lock();
+                *guard += 1;
+                // Exits holding the lock
+                core::mem::forget(guard);
+            })
+            .join()
+            .unwrap()
+        });
+
+        let guard = mutex.lock();
+        assert!(guard.owner_died());
+        assert_eq!(*guard, 1);
+        drop(guard);
+        assert!(!mutex.lock().owner_died());
+        assert!(!mutex.try_lock().unwrap().owner_died());
+    }
+}
@@ -0,0 +1,316 @@
+use {
+    crate::{
+        ordering::{Acquire, Release},
+        spsc::{Consumer, Producer, Ring},
+        Shareable,
+    },
+    core::sync::atomic::AtomicU32,
+    std::io::{self, Read, Write},
+};
+
+/// Set in [`Direction::signal`] once the writer has hung up
+const CLOSED: u32 = 1;
+/// Set in [`Direction::signal`] once the reader's end is dropped
+const GONE: u32 = 2;
+
+#[derive(Default)]
+struct Direction<const N: usize> {
+    ring: Ring<N>,
+    /// Incremented by 4 on every write, with CLOSED set once the writing end hangs up and GONE
+    /// once the reading end is dropped (the futex waited on by the reader)
+    signal: AtomicU32,
+}
+
+/// A duplex byte stream between a server and a client process, built from a [`Ring`] per
+/// direction, so protocol code written against [`Read`] and [`Write`] (or tokio's `AsyncRead` and
+/// `AsyncWrite` with the `async` feature) runs over shared memory unchanged.
+///
+/// Reads return end of file once the other end is dropped and its bytes are drained, and writes
+/// fail with [`io::ErrorKind::BrokenPipe`] once the other end is dropped. An end whose process
+/// dies without dropping it leaves its peer waiting.
+#[derive(Default)]
+pub struct Pipe<const N: usize> {
+    to_client: Direction<N>,
+    to_server: Direction<N>,
+}
+
+unsafe impl<const N: usize> Shareable for Pipe<N> {}
+
+impl<const N: usize> Pipe<N> {
+    /// Claims the server's end, returning None if another live process holds it.
+    pub fn server(&self) -> Option<PipeEnd<'_, N>> {
+        PipeEnd::claim(&self.to_client, &self.to_server)
+    }
+
+    /// Claims the client's end, returning None if another live process holds it.
+    pub fn client(&self) -> Option<PipeEnd<'_, N>> {
+        PipeEnd::claim(&self.to_server, &self.to_client)
+    }
+}
+
+/// An end of a [`Pipe`], which hangs up when dropped.
+pub struct PipeEnd<'a, const N: usize> {
+    tx: Producer<'a, N>,
+    rx: Consumer<'a, N>,
+    out: &'a Direction<N>,
+    inbound: &'a Direction<N>,
+    #[cfg(feature = "async")]
+    reading: Option<WaitFuture<'a>>,
+    #[cfg(feature = "async")]
+    writing: Option<WaitFuture<'a>>,
+}
+
+#[cfg(feature = "async")]
+type WaitFuture<'a> = core::pin::Pin<Box<dyn core::future::Future<Output = ()> + Send + 'a>>;
+
+impl<'a, const N: usize> PipeEnd<'a, N> {
+    fn claim(out: &'a Direction<N>, inbound: &'a Direction<N>) -> Option<Self> {
+        let (tx, rx) = (out.ring.producer()?, inbound.ring.consumer()?);
+        out.signal.fetch_and(!CLOSED, Release);
+        inbound.signal.fetch_and(!GONE, Release);
+        Some(Self {
+            tx,
+            rx,
+            out,
+            inbound,
+            #[cfg(feature = "async")]
+            reading: None,
+            #[cfg(feature = "async")]
+            writing: None,
+        })
+    }
+
+    /// Hangs up the writing direction, so the peer reads end of file once drained (ex: to end its
+    /// `read_to_end`) while this end keeps reading. Dropping the end also hangs up.
+    pub fn shutdown(&self) {
+        self.out.signal.fetch_or(CLOSED, Release);
+        crate::futex::wake_all(&self.out.signal);
+    }
+
+    /// Notifies the reader of bytes written.
+    fn sent(&self) {
+        self.out.signal.fetch_add(4, Release);
+        crate::futex::wake_one(&self.out.signal);
+    }
+
+    /// Fails if the peer's end was dropped, so nothing will read what's written.
+    fn check_peer(&self) -> io::Result<()> {
+        match self.out.signal.load(Acquire) & GONE {
+            0 => Ok(()),
+            _ => Err(io::ErrorKind::BrokenPipe.into()),
+        }
+    }
+}
+
+impl<const N: usize> Read for PipeEnd<'_, N> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        loop {
+            let signal = self.inbound.signal.load(Acquire);
+            match self.rx.try_pop(buf) {
+                // Every write preceded the hangup, so the ring is drained.
+                0 if signal & CLOSED != 0 => return Ok(0),
+                0 => crate::futex::wait(&self.inbound.signal, signal),
+                n => return Ok(n),
+            }
+        }
+    }
+}
+
+impl<const N: usize> Write for PipeEnd<'_, N> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let tail = self.out.ring.tail();
+        loop {
+            // A peer dropped after this load discards its bytes, which advances the tail.
+            let consumed = tail.load(Acquire);
+            self.check_peer()?;
+            match self.tx.try_push(buf) {
+                // Block until there's room for at least a byte.
+                0 => crate::futex::wait(tail, consumed),
+                n => {
+                    self.sent();
+                    return Ok(n);
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const N: usize> tokio::io::AsyncRead for PipeEnd<'_, N> {
+    fn poll_read(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> core::task::Poll<io::Result<()>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(wait) = &mut this.reading {
+                core::task::ready!(wait.as_mut().poll(cx));
+                this.reading = None;
+            }
+            let inbound = this.inbound;
+            let signal = inbound.signal.load(Acquire);
+            match this.rx.try_pop(buf.initialize_unfilled()) {
+                0 if signal & CLOSED == 0 && buf.remaining() > 0 => {
+                    this.reading =
+                        Some(Box::pin(crate::futex::wait_async(&inbound.signal, signal)));
+                }
+                n => {
+                    buf.advance(n);
+                    return core::task::Poll::Ready(Ok(()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl<const N: usize> tokio::io::AsyncWrite for PipeEnd<'_, N> {
+    fn poll_write(
+        self: core::pin::Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &[u8],
+    ) -> core::task::Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(wait) = &mut this.writing {
+                core::task::ready!(wait.as_mut().poll(cx));
+                this.writing = None;
+            }
+            let tail = this.out.ring.tail();
+            let consumed = tail.load(Acquire);
+            if let Err(e) = this.check_peer() {
+                return core::task::Poll::Ready(Err(e));
+            }
+            match this.tx.try_push(buf) {
+                0 if !buf.is_empty() => {
+                    this.writing = Some(Box::pin(crate::futex::wait_async(tail, consumed)));
+                }
+                n => {
+                    if n > 0 {
+                        this.sent();
+                    }
+                    return core::task::Poll::Ready(Ok(n));
+                }
+            }
+        }
+    }
+
+    fn poll_flush(
+        self: core::pin::Pin<&mut Self>,
+        _: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<io::Result<()>> {
+        core::task::Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(
+        self: core::pin::Pin<&mut Self>,
+        _: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<io::Result<()>> {
+        self.shutdown();
+        core::task::Poll::Ready(Ok(()))
+    }
+}
+
+impl<const N: usize> Drop for PipeEnd<'_, N> {
+    fn drop(&mut self) {
+        // Discarding the unread bytes wakes a peer blocked writing, which then sees GONE.
+        self.inbound.signal.fetch_or(GONE, Release);
+        self.rx.clear();
+        self.shutdown();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn duplex() {
+        let pipe = Pipe::<16>::default();
+        let mut server = pipe.server().unwrap();
+        let mut client = pipe.client().unwrap();
+        assert!(pipe.client().is_none());
+
+        let request: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut received = Vec::new();
+                server.read_to_end(&mut received).unwrap();
+                server.write_all(&received[..10]).unwrap();
+                drop(server);
+            });
+            client.write_all(&request).unwrap();
+            client.shutdown();
+            let mut reply = Vec::new();
+            client.read_to_end(&mut reply).unwrap();
+            assert_eq!(reply, request[..10]);
+        });
+    }
+
+    #[test]
+    fn broken_pipe() {
+        let pipe = Pipe::<4>::default();
+        let mut server = pipe.server().unwrap();
+        let client = pipe.client().unwrap();
+
+        thread::scope(|s| {
+            s.spawn(move || {
+                thread::sleep(std::time::Duration::from_millis(20));
+                drop(client);
+            });
+            // Blocks once the ring is full, until the client hangs up.
+            let e = server.write_all(&[0; 100]).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+        });
+
+        // A client reconnecting starts from an empty ring.
+        let _client = pipe.client().unwrap();
+        assert_eq!(server.write(&[0; 8]).unwrap(), 4);
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn duplex_async() {
+        use {core::pin::Pin, tokio::io::ReadBuf};
+
+        let pipe = Pipe::<4>::default();
+        let mut server = pipe.server().unwrap();
+        let mut client = pipe.client().unwrap();
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        thread::scope(|s| {
+            s.spawn(move || {
+                server.write_all(b"hello world").unwrap();
+            });
+            runtime.block_on(async {
+                let mut received = Vec::new();
+                loop {
+                    let mut buf = [0; 3];
+                    let mut buf = ReadBuf::new(&mut buf);
+                    core::future::poll_fn(|cx| {
+                        tokio::io::AsyncRead::poll_read(Pin::new(&mut client), cx, &mut buf)
+                    })
+                    .await
+                    .unwrap();
+                    match buf.filled() {
+                        [] => break,
+                        bytes => received.extend_from_slice(bytes),
+                    }
+                }
+                assert_eq!(received, b"hello world");
+            });
+        });
+    }
+}
@@ -0,0 +1,243 @@
+use {
+    crate::{
+        mutex::MutexGuard,
+        ordering::{Acquire, Release},
+        rwlock::{ReadGuard, WriteGuard},
+        sync::const_fn,
+        Mutex, RwLock, Shareable,
+    },
+    core::{
+        fmt,
+        ops::{Deref, DerefMut},
+        sync::atomic::AtomicU32,
+    },
+};
+
+/// Returned when locking a lock which was poisoned by a guard dropped during a panic, in any
+/// process. The guard is still acquired and can be recovered with [`Self::into_inner`].
+pub struct PoisonError<G> {
+    guard: G,
+}
+
+impl<G> PoisonError<G> {
+    pub fn into_inner(self) -> G {
+        self.guard
+    }
+
+    pub fn get_ref(&self) -> &G {
+        &self.guard
+    }
+}
+
+impl<G> fmt::Debug for PoisonError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
+impl<G> fmt::Display for PoisonError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "lock poisoned by a panic while it was held")
+    }
+}
+
+impl<G> std::error::Error for PoisonError<G> {}
+
+pub type LockResult<G> = Result<G, PoisonError<G>>;
+
+/// A poison flag set when a guard is dropped by a panicking thread.
+#[derive(Default)]
+struct Flag(AtomicU32);
+
+impl Flag {
+    fn guard<G>(&self, guard: G) -> LockResult<G> {
+        match self.0.load(Acquire) {
+            0 => Ok(guard),
+            _ => Err(PoisonError { guard }),
+        }
+    }
+
+    /// Poisons if the thread started panicking while holding the guard.
+    fn done(&self, panicking: bool) {
+        if !panicking && std::thread::panicking() {
+            self.0.store(1, Release);
+        }
+    }
+}
+
+/// A [`Mutex`] with poisoning: if a guard is dropped during a panic the protected data may be
+/// inconsistent, so subsequent lockers (in any process) receive a [`PoisonError`].
+///
+/// A process which dies without unwinding (ex: killed, or built with `panic = "abort"`) doesn't
+/// poison the lock (see [`crate::RobustMutex`]).
+#[derive(Default)]
+pub struct PoisonMutex<T> {
+    poison: Flag,
+    mutex: Mutex<T>,
+}
+
+unsafe impl<T: Shareable + Send> Shareable for PoisonMutex<T> {}
+
+impl<T> PoisonMutex<T> {
+    const_fn! {
+        pub fn new(value: T) -> Self {
+            Self {
+                poison: Flag(AtomicU32::new(0)),
+                mutex: Mutex::new(value),
+            }
+        }
+    }
+
+    pub fn lock(&self) -> LockResult<PoisonGuard<'_, T>> {
+        self.poison.guard(PoisonGuard::new(self, self.mutex.lock()))
+    }
+
+    pub fn try_lock(&self) -> Option<LockResult<PoisonGuard<'_, T>>> {
+        let guard = self.mutex.try_lock()?;
+        Some(self.poison.guard(PoisonGuard::new(self, guard)))
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.0.load(Acquire) != 0
+    }
+
+    /// Clears the poison once the data has been made consistent.
+    pub fn clear_poison(&self) {
+        self.poison.0.store(0, Release);
+    }
+}
+
+pub struct PoisonGuard<'a, T> {
+    lock: &'a PoisonMutex<T>,
+    guard: MutexGuard<'a, T>,
+    panicking: bool,
+}
+
+impl<'a, T> PoisonGuard<'a, T> {
+    fn new(lock: &'a PoisonMutex<T>, guard: MutexGuard<'a, T>) -> Self {
+        Self {
+            lock,
+            guard,
+            panicking: std::thread::panicking(),
+        }
+    }
+}
+
+impl<T> Deref for PoisonGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for PoisonGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for PoisonGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.poison.done(self.panicking);
+    }
+}
+
+/// A [`RwLock`] with poisoning: a write guard dropped during a panic poisons the lock, after
+/// which both reads and writes receive a [`PoisonError`].
+#[derive(Default)]
+pub struct PoisonRwLock<T> {
+    poison: Flag,
+    rwlock: RwLock<T>,
+}
+
+unsafe impl<T: Shareable + Send> Shareable for PoisonRwLock<T> {}
+
+impl<T> PoisonRwLock<T> {
+    const_fn! {
+        pub fn new(value: T) -> Self {
+            Self {
+                poison: Flag(AtomicU32::new(0)),
+                rwlock: RwLock::new(value),
+            }
+        }
+    }
+
+    pub fn read(&self) -> LockResult<ReadGuard<'_, T>> {
+        self.poison.guard(self.rwlock.read())
+    }
+
+    pub fn write(&self) -> LockResult<PoisonWriteGuard<'_, T>> {
+        let guard = PoisonWriteGuard {
+            lock: self,
+            guard: self.rwlock.write(),
+            panicking: std::thread::panicking(),
+        };
+        self.poison.guard(guard)
+    }
+
+    pub fn is_poisoned(&self) -> bool {
+        self.poison.0.load(Acquire) != 0
+    }
+
+    /// Clears the poison once the data has been made consistent.
+    pub fn clear_poison(&self) {
+        self.poison.0.store(0, Release);
+    }
+}
+
+pub struct PoisonWriteGuard<'a, T> {
+    lock: &'a PoisonRwLock<T>,
+    guard: WriteGuard<'a, T>,
+    panicking: bool,
+}
+
+impl<T> Deref for PoisonWriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.guard
+    }
+}
+
+impl<T> DerefMut for PoisonWriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.guard
+    }
+}
+
+impl<T> Drop for PoisonWriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.lock.poison.done(self.panicking);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::panic};
+
+    #[test]
+    fn poisoning() {
+        let mutex = PoisonMutex::new(0);
+        let rwlock = PoisonRwLock::new(0);
+        let _ = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+            let _guard = mutex.lock().unwrap();
+            let _write = rwlock.write().unwrap();
+            panic!("mid-update");
+        }));
+
+        assert!(mutex.is_poisoned());
+        let Err(poisoned) = mutex.lock() else {
+            panic!("not poisoned");
+        };
+        let guard = poisoned.into_inner();
+        assert_eq!(*guard, 0);
+        drop(guard);
+        mutex.clear_poison();
+        assert!(mutex.try_lock().unwrap().is_ok());
+
+        assert!(rwlock.read().is_err());
+        rwlock.clear_poison();
+        assert!(rwlock.read().is_ok());
+    }
+}
@@ -0,0 +1,104 @@
+/// Implemented by structs declared with [`crate::projection!`], whose projection borrows their
+/// fields individually (ex: so each subsystem is handed only the fields it uses rather than the
+/// whole struct).
+pub trait Project {
+    type Projection<'a>
+    where
+        Self: 'a;
+
+    fn project(&self) -> Self::Projection<'_>;
+}
+
+/// Declares a struct along with its projection, a Copy type named after the `=>` whose methods
+/// borrow each field for the lifetime of the projected reference (ex: a [`crate::Shared`]).
+///
+/// Methods have the visibility of their field. Generic structs aren't supported.
+///
+/// # Examples
+///
+/// ```
+/// # use {shm::*, std::sync::atomic::*};
+/// # let shm_name = std::ffi::CString::new("/projection_doc").unwrap();
+/// shm::projection! {
+///     #[derive(Default)]
+///     pub struct Server => ServerFields {
+///         pub requests: AtomicU64,
+///         pub config: SeqLock<u64>,
+///     }
+/// }
+/// unsafe impl Shareable for Server {}
+///
+/// let server = unsafe { Shared::<Server>::create(&shm_name) }.unwrap();
+/// let requests: &AtomicU64 = server.project().requests();
+/// requests.fetch_add(1, Ordering::Relaxed);
+/// ```
+#[macro_export]
+macro_rules! projection {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident => $projection:ident {
+            $($(#[$field_meta:meta])* $field_vis:vis $field:ident: $ty:ty),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $($(#[$field_meta])* $field_vis $field: $ty,)*
+        }
+
+        #[doc = concat!("Borrows the fields of a [`", stringify!($name), "`] individually.")]
+        #[derive(Clone, Copy)]
+        $vis struct $projection<'a>(&'a $name);
+
+        impl<'a> $projection<'a> {
+            $(
+                #[allow(dead_code)]
+                $field_vis fn $field(self) -> &'a $ty {
+                    &self.0.$field
+                }
+            )*
+        }
+
+        impl $crate::Project for $name {
+            type Projection<'a> = $projection<'a>;
+
+            fn project(&self) -> $projection<'_> {
+                $projection(self)
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        crate::{Mutex, Shareable, Shared},
+        std::sync::atomic::{AtomicU32, Ordering::Relaxed},
+    };
+
+    crate::projection! {
+        #[derive(Default)]
+        struct State => StateFields {
+            counter: AtomicU32,
+            log: Mutex<[u8; 4]>,
+        }
+    }
+
+    unsafe impl Shareable for State {}
+
+    /// Only needs the counter, so it's only handed the counter
+    fn count(counter: &AtomicU32) {
+        counter.fetch_add(1, Relaxed);
+    }
+
+    #[test]
+    fn fields() {
+        let shared = Shared::<State>::create_anon().unwrap();
+        let fields = shared.project();
+        count(fields.counter());
+        count(fields.counter());
+        fields.log().lock()[0] = 1;
+
+        assert_eq!(shared.counter.load(Relaxed), 2);
+        assert_eq!(shared.log.lock()[0], 1);
+    }
+}
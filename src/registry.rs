@@ -0,0 +1,104 @@
+use {
+    crate::{Result, Shareable, Shared},
+    std::{
+        any::Any,
+        ffi::{CStr, CString},
+        marker::PhantomData,
+    },
+};
+
+/// Describes a region to be created by [`Registry::create_all`].
+pub trait RegionSpec {
+    fn name(&self) -> &CStr;
+
+    /// # Safety
+    ///
+    /// See [`Shared::create`].
+    unsafe fn create(&self) -> Result<Box<dyn Any + Send + Sync>>;
+}
+
+/// A region holding a T.
+pub struct Region<'a, T> {
+    name: &'a CStr,
+    _type: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Region<'a, T> {
+    pub fn new(name: &'a CStr) -> Self {
+        Self {
+            name,
+            _type: PhantomData,
+        }
+    }
+}
+
+impl<T: Shareable + 'static> RegionSpec for Region<'_, T> {
+    fn name(&self) -> &CStr {
+        self.name
+    }
+
+    unsafe fn create(&self) -> Result<Box<dyn Any + Send + Sync>> {
+        unsafe { Shared::<T>::create(self.name) }.map(|s| Box::new(s) as _)
+    }
+}
+
+/// A set of regions created together, which are unlinked together when dropped.
+pub struct Registry {
+    regions: Vec<(CString, Box<dyn Any + Send + Sync>)>,
+}
+
+impl Registry {
+    /// Creates every region, or none of them: if any creation fails the regions already created
+    /// are unlinked before the error is returned.
+    ///
+    /// # Safety
+    ///
+    /// See [`Shared::create`], which applies to every region name.
+    pub unsafe fn create_all(specs: &[&dyn RegionSpec]) -> Result<Self> {
+        let mut regions = Vec::with_capacity(specs.len());
+        for spec in specs {
+            // Returning early drops (unlinks) the regions created so far.
+            let region = unsafe { spec.create() }?;
+            regions.push((CString::from(spec.name()), region));
+        }
+        Ok(Self { regions })
+    }
+
+    /// Returns the region of the given name if it holds a T.
+    pub fn get<T: Shareable + 'static>(&self, name: &CStr) -> Option<&Shared<T>> {
+        self.regions
+            .iter()
+            .find(|(n, _)| n.as_c_str() == name)
+            .and_then(|(_, region)| region.downcast_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{AtomicF64, Error, Event},
+    };
+
+    #[test]
+    fn rollback() {
+        let (a, b) = (c"/registry_a", c"/registry_b");
+        let registry = unsafe {
+            Registry::create_all(&[&Region::<AtomicF64>::new(a), &Region::<Event>::new(b)])
+        }
+        .unwrap();
+        assert!(registry.get::<Event>(b).is_some());
+        assert!(registry.get::<Event>(a).is_none());
+
+        // The second region already exists
+        let c = c"/registry_c";
+        assert!(matches!(
+            unsafe { Registry::create_all(&[&Region::<Event>::new(c), &Region::<Event>::new(b)]) },
+            Err(Error::Open(_))
+        ));
+        assert!(matches!(
+            unsafe { Shared::<Event>::open(c) },
+            Err(Error::Open(_))
+        ));
+    }
+}
@@ -0,0 +1,328 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        ClientSlot, ClientSlots, Shareable,
+    },
+    core::{
+        ops::Deref,
+        sync::atomic::{AtomicU32, AtomicU64},
+        time::Duration,
+    },
+    std::{
+        sync::{Mutex, PoisonError},
+        time::Instant,
+    },
+};
+
+/// How often a blocked checkout rechecks for leases held by dead processes.
+const RECLAIM_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Serializes this process's lookups of its accounts, so its threads share one per pool.
+static ACCOUNTS: Mutex<()> = Mutex::new(());
+
+/// A bounded set of shared objects which processes check out and back in.
+///
+/// Leases held by processes which have exited are reclaimed, so objects should be reinitialized
+/// by the process checking them out.
+///
+/// Each leasing process has an account of its reservations, so a quota (see
+/// [`Self::set_quota`]) stops one process from exhausting the pool. A checkout reserves an object
+/// before claiming it, so concurrent checkouts can't overshoot the quota.
+pub struct ResourcePool<T, const N: usize> {
+    items: ClientSlots<T, N>,
+    /// Incremented on every checkin to wake blocked checkouts.
+    checkins: AtomicU32,
+    /// The most objects a single process may lease at once, or 0 if unlimited
+    quota: AtomicU32,
+    /// At most N processes hold leases at once
+    accounts: [Account; N],
+    #[cfg(feature = "metrics")]
+    metrics: crate::metrics::PoolCounters,
+}
+
+#[derive(Default)]
+struct Account {
+    /// The id (see [`crate::owner`]) of the process in the high half (0 if never used), and the
+    /// objects it has leased or is checking out in the low half
+    reserved: AtomicU64,
+    #[cfg(feature = "metrics")]
+    high_water: AtomicU32,
+}
+
+impl<T: Default, const N: usize> Default for ResourcePool<T, N> {
+    fn default() -> Self {
+        Self {
+            items: ClientSlots::default(),
+            checkins: AtomicU32::new(0),
+            quota: AtomicU32::new(0),
+            accounts: core::array::from_fn(|_| Account::default()),
+            #[cfg(feature = "metrics")]
+            metrics: Default::default(),
+        }
+    }
+}
+
+unsafe impl<T: Shareable, const N: usize> Shareable for ResourcePool<T, N> {}
+
+impl<T, const N: usize> ResourcePool<T, N> {
+    /// Limits the number of objects each process may lease at once (0 removes the limit).
+    pub fn set_quota(&self, per_process: u32) {
+        self.quota.store(per_process, Relaxed);
+    }
+
+    pub fn try_checkout(&self) -> Option<Lease<'_, T, N>> {
+        let account = self.reserve()?;
+        match self.items.claim() {
+            Some(item) => {
+                #[cfg(feature = "metrics")]
+                self.metrics.leased(self.leased().count() as u32);
+                Some(Lease {
+                    pool: self,
+                    account,
+                    item: Some(item),
+                })
+            }
+            None => {
+                account.reserved.fetch_sub(1, Release);
+                None
+            }
+        }
+    }
+
+    /// Reserves an object in this process's account, unless the process is at its quota.
+    fn reserve(&self) -> Option<&Account> {
+        let quota = self.quota.load(Relaxed);
+        let id = crate::owner::id(self);
+        loop {
+            let account = self.account(id)?;
+            let old = account.reserved.fetch_add(1, Acquire);
+            let (owner, reserved) = ((old >> 32) as u32, old as u32);
+            if owner == id && (quota == 0 || reserved < quota) {
+                #[cfg(feature = "metrics")]
+                account.high_water.fetch_max(reserved + 1, Relaxed);
+                return Some(account);
+            }
+            account.reserved.fetch_sub(1, Release);
+            if owner == id {
+                #[cfg(feature = "metrics")]
+                self.metrics.rejected();
+                return None;
+            }
+            // The account was taken over by another process while idle.
+        }
+    }
+
+    /// The account of the process `id`, opening one if it has none. None if every account is held
+    /// by a live process with leases.
+    fn account(&self, id: u32) -> Option<&Account> {
+        let _lookup = ACCOUNTS.lock().unwrap_or_else(PoisonError::into_inner);
+        let owner = |account: &Account| (account.reserved.load(Relaxed) >> 32) as u32;
+        if let Some(account) = self.accounts.iter().find(|&a| owner(a) == id) {
+            return Some(account);
+        }
+        let open = |account: &Account, current: u64| {
+            account
+                .reserved
+                .compare_exchange(current, u64::from(id) << 32, Acquire, Relaxed)
+                .is_ok()
+        };
+        // An unused or dead process's account (whose reservations are void), else an idle one
+        let account = self
+            .accounts
+            .iter()
+            .find(|&a| {
+                let current = a.reserved.load(Relaxed);
+                let owner = (current >> 32) as u32;
+                (owner == 0 || !crate::owner::alive(self, owner)) && open(a, current)
+            })
+            .or_else(|| {
+                self.accounts.iter().find(|&a| {
+                    let current = a.reserved.load(Relaxed);
+                    current as u32 == 0 && open(a, current)
+                })
+            })?;
+        #[cfg(feature = "metrics")]
+        account.high_water.store(0, Relaxed);
+        Some(account)
+    }
+
+    /// Blocks until an object is available.
+    pub fn checkout(&self) -> Lease<'_, T, N> {
+        loop {
+            if let Some(lease) = self.checkout_timeout(Duration::MAX) {
+                return lease;
+            }
+        }
+    }
+
+    /// Blocks until an object is available or the timeout expires.
+    pub fn checkout_timeout(&self, dur: Duration) -> Option<Lease<'_, T, N>> {
+        let deadline = Instant::now().checked_add(dur);
+        loop {
+            let checkins = self.checkins.load(Acquire);
+            if let Some(lease) = self.try_checkout() {
+                return Some(lease);
+            }
+
+            let remaining = deadline.map_or(Duration::MAX, |d| {
+                d.saturating_duration_since(Instant::now())
+            });
+            if remaining.is_zero() {
+                return None;
+            }
+            crate::futex::wait_timeout(
+                &self.checkins,
+                checkins,
+                Some(remaining.min(RECLAIM_INTERVAL)),
+            );
+        }
+    }
+
+    /// Iterates over the leased objects as (index, owner, object), where the owner is an id the
+    /// leasing process took in the region (not its pid).
+    pub fn leased(&self) -> impl Iterator<Item = (usize, u32, &T)> {
+        self.items.iter()
+    }
+
+    /// The number of objects leased by the process with id `owner` (see [`Self::leased`]).
+    pub fn leased_by(&self, owner: u32) -> usize {
+        self.leased().filter(|&(_, o, _)| o == owner).count()
+    }
+
+    /// The pool's counters, shared by every process.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> crate::metrics::PoolStats {
+        self.metrics.stats(self.leased().count() as u32)
+    }
+
+    /// Iterates over the use of the pool by each process which has an account.
+    #[cfg(feature = "metrics")]
+    pub fn usage(&self) -> impl Iterator<Item = crate::metrics::ProcessUsage> + '_ {
+        self.accounts.iter().filter_map(|a| {
+            let reserved = a.reserved.load(Relaxed);
+            (reserved >> 32 != 0).then(|| crate::metrics::ProcessUsage {
+                owner: (reserved >> 32) as u32,
+                leased: reserved as u32,
+                high_water: a.high_water.load(Relaxed),
+            })
+        })
+    }
+}
+
+/// An object checked out of a [`ResourcePool`], checked back in when dropped.
+#[must_use = "if unused the object will immediately be checked back in"]
+pub struct Lease<'a, T, const N: usize> {
+    pool: &'a ResourcePool<T, N>,
+    account: &'a Account,
+    item: Option<ClientSlot<'a, T>>,
+}
+
+impl<T, const N: usize> Lease<'_, T, N> {
+    pub fn index(&self) -> usize {
+        self.item.as_ref().unwrap().index()
+    }
+}
+
+impl<T, const N: usize> Deref for Lease<'_, T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.item.as_ref().unwrap()
+    }
+}
+
+impl<T, const N: usize> Drop for Lease<'_, T, N> {
+    fn drop(&mut self) {
+        drop(self.item.take());
+        self.account.reserved.fetch_sub(1, Release);
+        self.pool.checkins.fetch_add(1, Release);
+        crate::futex::wake_one(&self.pool.checkins);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{sync::atomic::AtomicU64, thread},
+    };
+
+    #[test]
+    fn checkout_checkin() {
+        let pool = ResourcePool::<AtomicU64, 1>::default();
+
+        let lease = pool.checkout();
+        assert!(pool.try_checkout().is_none());
+        assert!(pool.checkout_timeout(Duration::from_millis(10)).is_none());
+        assert_eq!(pool.leased().count(), 1);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                drop(lease);
+            });
+            let lease = pool.checkout_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(lease.index(), 0);
+        });
+        assert_eq!(pool.leased().count(), 0);
+    }
+
+    #[test]
+    fn quota() {
+        let pool = ResourcePool::<AtomicU64, 3>::default();
+        pool.set_quota(2);
+
+        let leases = [pool.try_checkout().unwrap(), pool.try_checkout().unwrap()];
+        assert!(pool.try_checkout().is_none());
+        assert_eq!(pool.leased_by(crate::owner::id(&pool)), 2);
+        drop(leases);
+        assert!(pool.try_checkout().is_some());
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn stats() {
+        let pool = ResourcePool::<AtomicU64, 3>::default();
+        pool.set_quota(2);
+
+        let leases = [pool.try_checkout().unwrap(), pool.try_checkout().unwrap()];
+        assert!(pool.try_checkout().is_none());
+        drop(leases);
+
+        let stats = pool.stats();
+        assert_eq!(
+            (stats.leased, stats.high_water, stats.quota_rejections),
+            (0, 2, 1)
+        );
+        let usage = pool.usage().collect::<Vec<_>>();
+        assert_eq!(usage.len(), 1);
+        assert_eq!(
+            (usage[0].owner, usage[0].leased, usage[0].high_water),
+            (crate::owner::id(&pool), 0, 2)
+        );
+    }
+
+    #[test]
+    fn checkout_blocks_at_quota() {
+        let pool = ResourcePool::<AtomicU64, 3>::default();
+        pool.set_quota(1);
+
+        let lease = pool.checkout();
+        assert!(pool.checkout_timeout(Duration::from_millis(50)).is_none());
+        // The blocked checkout waited for checkins instead of retrying in a loop.
+        #[cfg(feature = "metrics")]
+        assert!(pool.stats().quota_rejections < 5);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                drop(lease);
+            });
+            let lease = pool.checkout_timeout(Duration::from_secs(1)).unwrap();
+            assert_eq!(pool.leased_by(crate::owner::id(&pool)), 1);
+            drop(lease);
+        });
+        #[cfg(feature = "metrics")]
+        assert!(pool.stats().quota_rejections < 10);
+    }
+}
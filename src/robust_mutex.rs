@@ -0,0 +1,182 @@
+use {
+    crate::ordering::{Acquire, Relaxed, Release},
+    core::{
+        cell::UnsafeCell,
+        ops::{Deref, DerefMut},
+        sync::atomic::AtomicU32,
+        time::Duration,
+    },
+};
+
+/// Set in the state while processes are waiting
+const WAITERS: u32 = 1 << 31;
+
+/// How often waiters check whether the owner has died (a futex isn't woken by process exit)
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// A mutex which is recovered, rather than deadlocking every other process, when the process
+/// holding it dies.
+///
+/// The lock records the owning process's id in the region, which is backed by a lock the process
+/// holds on the region's descriptor, so a waiter which finds the owner has exited takes over the
+/// lock and is told so by [`RobustMutexGuard::owner_died`]. The protected data may then be
+/// inconsistent and should be repaired. Unlike a pid, the id is valid across pid namespaces,
+/// though a dead owner whose id has already been taken by another process isn't detected.
+pub struct RobustMutex<T> {
+    /// 0 if unlocked, otherwise the owner's id, with WAITERS set if contended
+    state: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+unsafe impl<T> Sync for RobustMutex<T> where T: Send {}
+
+impl<T: Default> Default for RobustMutex<T> {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+impl<T> RobustMutex<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            state: AtomicU32::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn try_lock(&self) -> Option<RobustMutexGuard<'_, T>> {
+        let id = crate::owner::id(self);
+        match self.state.compare_exchange(0, id, Acquire, Relaxed) {
+            Ok(_) => Some(self.guard(false)),
+            Err(s) => self.reclaim(s, id).then(|| self.guard(true)),
+        }
+    }
+
+    pub fn lock(&self) -> RobustMutexGuard<'_, T> {
+        let id = crate::owner::id(self);
+        let mut s = match self.state.compare_exchange(0, id, Acquire, Relaxed) {
+            Ok(_) => return self.guard(false),
+            Err(s) => s,
+        };
+        loop {
+            if s == 0 {
+                // Others may still be waiting.
+                match self
+                    .state
+                    .compare_exchange(0, id | WAITERS, Acquire, Relaxed)
+                {
+                    Ok(_) => return self.guard(false),
+                    Err(actual) => s = actual,
+                }
+                continue;
+            }
+            if self.reclaim(s, id) {
+                return self.guard(true);
+            }
+            if s & WAITERS == 0 {
+                if let Err(actual) = self
+                    .state
+                    .compare_exchange(s, s | WAITERS, Relaxed, Relaxed)
+                {
+                    s = actual;
+                    continue;
+                }
+            }
+            crate::futex::wait_timeout(&self.state, s | WAITERS, Some(POLL_INTERVAL));
+            s = self.state.load(Relaxed);
+        }
+    }
+
+    /// Takes over the lock in state `s` if its owner has died.
+    fn reclaim(&self, s: u32, id: u32) -> bool {
+        !crate::owner::alive(self, s & !WAITERS)
+            && self
+                .state
+                .compare_exchange(s, id | WAITERS, Acquire, Relaxed)
+                .is_ok()
+    }
+
+    fn guard(&self, owner_died: bool) -> RobustMutexGuard<'_, T> {
+        crate::ordering::guard_fence();
+        #[cfg(feature = "trace")]
+        crate::trace::record(self, crate::trace::Op::Acquire);
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self, crate::audit::Access::Lock);
+        crate::usdt::probe!("lock_acquire", self as *const _);
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::acquired(self, crate::diagnostics::Kind::Exclusive);
+        RobustMutexGuard {
+            mutex: self,
+            owner_died,
+        }
+    }
+}
+
+#[must_use = "if unused the RobustMutex will immediately unlock"]
+pub struct RobustMutexGuard<'a, T> {
+    mutex: &'a RobustMutex<T>,
+    owner_died: bool,
+}
+
+impl<T> RobustMutexGuard<'_, T> {
+    /// True if the lock was taken over from a process which died holding it.
+    pub fn owner_died(&self) -> bool {
+        self.owner_died
+    }
+}
+
+impl<T> Deref for RobustMutexGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // Safety: The very existence of this Guard guarantees we've exclusively acquired the lock.
+        unsafe { &*self.mutex.data.get() }
+    }
+}
+
+impl<T> DerefMut for RobustMutexGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // Safety: The very existence of this Guard guarantees we've exclusively acquired the lock.
+        unsafe { &mut *self.mutex.data.get() }
+    }
+}
+
+impl<T> Drop for RobustMutexGuard<'_, T> {
+    fn drop(&mut self) {
+        #[cfg(feature = "trace")]
+        crate::trace::record(self.mutex, crate::trace::Op::Release);
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self.mutex, crate::audit::Access::Unlock);
+        crate::usdt::probe!("lock_release", self.mutex as *const _);
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::released(self.mutex);
+        crate::ordering::guard_fence();
+        if self.mutex.state.swap(0, Release) & WAITERS != 0 {
+            crate::futex::wake_one(&self.mutex.state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn owner_died() {
+        let mutex = RobustMutex::new(0);
+        let mut guard = mutex.lock();
+        assert!(!guard.owner_died());
+        assert!(mutex.try_lock().is_none());
+        *guard += 1;
+        drop(guard);
+
+        // A process which exited while holding the lock
+        mutex.state.store(i32::MAX as u32 | WAITERS, Relaxed);
+
+        let guard = mutex.lock();
+        assert!(guard.owner_died());
+        assert_eq!(*guard, 1);
+        drop(guard);
+        assert!(!mutex.lock().owner_died());
+    }
+}
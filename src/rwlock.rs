@@ -1,18 +1,24 @@
 // This code derives from Rust Atomics and Locks by Mara Bos (O’Reilly).
 // Copyright 2023 Mara Bos, 978-1-098-11944-7."
 
-use core::{
-    cell::UnsafeCell,
-    ops::{Deref, DerefMut},
-    sync::atomic::{
-        AtomicU32,
-        Ordering::{Acquire, Relaxed, Release},
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        sync::{const_fn, AtomicU32},
     },
+    core::{
+        cell::UnsafeCell,
+        ops::{Deref, DerefMut},
+        time::Duration,
+    },
+    std::time::Instant,
 };
 
+/// The layout is C compatible (see `c/shm_sync.h`): two 32-bit futex words followed by the data.
+#[repr(C)]
 pub struct RwLock<T> {
-    /// The number of read locks (x2), plus one if there's a writer waiting.
-    /// u32::MAX if write locked.
+    /// The number of read locks (x2), plus one if there's a writer waiting, plus UPGRADABLE if
+    /// one of the read locks is upgradable. u32::MAX if write locked.
     ///
     /// This means that readers may acquire the lock when the state is even,
     /// but need to block when odd.
@@ -20,8 +26,24 @@ pub struct RwLock<T> {
     /// Incremented to wake up writers.
     writer_wake_counter: AtomicU32,
     value: UnsafeCell<T>,
+    #[cfg(feature = "metrics")]
+    metrics: crate::metrics::Counters,
 }
 
+/// Set in the state while an upgradable read lock is held
+const UPGRADABLE: u32 = 1 << 31;
+
+/// The read lock count (x2) is limited to the bits below UPGRADABLE.
+const MAX_READERS: u32 = UPGRADABLE - 2;
+
+// C11 ABI: `_Atomic uint32_t state; _Atomic uint32_t writer_wake_counter;`
+#[cfg(not(loom))]
+const _: () = assert!(core::mem::offset_of!(RwLock<u8>, state) == 0);
+#[cfg(not(loom))]
+const _: () = assert!(core::mem::offset_of!(RwLock<u8>, writer_wake_counter) == 4);
+#[cfg(not(loom))]
+const _: () = assert!(core::mem::offset_of!(RwLock<u64>, value) == 8);
+
 unsafe impl<T> Sync for RwLock<T> where T: Send + Sync {}
 
 impl<T: Default> Default for RwLock<T> {
@@ -59,50 +81,147 @@ where
 }
 
 impl<T> RwLock<T> {
-    pub const fn new(value: T) -> Self {
-        Self {
-            state: AtomicU32::new(0),
-            writer_wake_counter: AtomicU32::new(0),
-            value: UnsafeCell::new(value),
+    const_fn! {
+        pub fn new(value: T) -> Self {
+            Self {
+                state: AtomicU32::new(0),
+                writer_wake_counter: AtomicU32::new(0),
+                value: UnsafeCell::new(value),
+                #[cfg(feature = "metrics")]
+                metrics: crate::metrics::Counters::new(),
+            }
         }
     }
 
+    /// Initializes an unlocked RwLock in memory not managed by this crate.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and aligned for `Self`, and no other thread or process may
+    /// access the memory until this method returns.
+    pub unsafe fn init_in_place(ptr: *mut Self, value: T) {
+        unsafe { ptr.write(Self::new(value)) }
+    }
+
+    /// Borrows an initialized RwLock from memory not managed by this crate.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned and point to an initialized RwLock which remains mapped for `'a`.
+    pub unsafe fn from_raw<'a>(ptr: *const Self) -> &'a Self {
+        unsafe { &*ptr }
+    }
+
     pub fn try_read(&self) -> Option<ReadGuard<T>> {
         let s = self.state.load(Relaxed);
-        if (s % 2 == 0) && (s < u32::MAX - 2) {
+        if s.is_multiple_of(2) && (s & !UPGRADABLE < MAX_READERS) {
             self.state
                 .compare_exchange_weak(s, s + 2, Acquire, Relaxed)
                 .ok()
-                .map(|_| ReadGuard { rwlock: self })
+                .map(|_| self.read_guard())
         } else {
             None
         }
     }
 
     pub fn read(&self) -> ReadGuard<T> {
+        self.read_until(None).unwrap()
+    }
+
+    /// Like [`Self::read`], but gives up after `timeout` (ex: to detect a wedged writer).
+    pub fn try_read_for(&self, timeout: Duration) -> Option<ReadGuard<'_, T>> {
+        self.read_until(Some(Instant::now() + timeout))
+    }
+
+    /// Like [`Self::read`], but gives up once `deadline` has passed.
+    pub fn try_read_until(&self, deadline: Instant) -> Option<ReadGuard<'_, T>> {
+        self.read_until(Some(deadline))
+    }
+
+    fn read_until(&self, deadline: Option<Instant>) -> Option<ReadGuard<'_, T>> {
         let mut s = self.state.load(Relaxed);
+        #[cfg(feature = "metrics")]
+        if s % 2 == 1 {
+            self.metrics.contended();
+        }
+        loop {
+            if s.is_multiple_of(2) {
+                assert!(s & !UPGRADABLE < MAX_READERS, "too many readers");
+                match self.state.compare_exchange_weak(s, s + 2, Acquire, Relaxed) {
+                    Ok(_) => return Some(self.read_guard()),
+                    Err(e) => s = e,
+                }
+            }
+            if s % 2 == 1 {
+                if expired(deadline) {
+                    return None;
+                }
+                #[cfg(feature = "diagnostics")]
+                crate::diagnostics::read_blocked(self);
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("rwlock_wait", lock = ?(self as *const RwLock<T>))
+                    .entered();
+                #[cfg(feature = "metrics")]
+                self.metrics.sleeping(&self.state, s);
+                crate::futex::wait_until(&self.state, s, deadline);
+                s = self.state.load(Relaxed);
+            }
+        }
+    }
+
+    /// Like [`Self::read`], but waits on tokio's blocking pool rather than blocking the runtime's
+    /// thread, as [`crate::Mutex::lock_async`].
+    #[cfg(all(feature = "async", not(loom)))]
+    pub async fn read_async(&self) -> ReadGuard<'_, T> {
+        let mut s = self.state.load(Relaxed);
+        #[cfg(feature = "metrics")]
+        if s % 2 == 1 {
+            self.metrics.contended();
+        }
         loop {
-            if s % 2 == 0 {
-                assert!(s < u32::MAX - 2, "too many readers");
+            if s.is_multiple_of(2) {
+                assert!(s & !UPGRADABLE < MAX_READERS, "too many readers");
                 match self.state.compare_exchange_weak(s, s + 2, Acquire, Relaxed) {
-                    Ok(_) => return ReadGuard { rwlock: self },
+                    Ok(_) => return self.read_guard(),
                     Err(e) => s = e,
                 }
             }
             if s % 2 == 1 {
-                crate::futex::wait(&self.state, s);
+                #[cfg(feature = "diagnostics")]
+                crate::diagnostics::read_blocked(self);
+                #[cfg(feature = "metrics")]
+                self.metrics.sleeping(&self.state, s);
+                crate::futex::wait_async(&self.state, s).await;
                 s = self.state.load(Relaxed);
             }
         }
     }
 
     pub fn write(&self) -> WriteGuard<T> {
+        self.write_until(None).unwrap()
+    }
+
+    /// Like [`Self::write`], but gives up after `timeout`.
+    pub fn try_write_for(&self, timeout: Duration) -> Option<WriteGuard<'_, T>> {
+        self.write_until(Some(Instant::now() + timeout))
+    }
+
+    /// Like [`Self::write`], but gives up once `deadline` has passed.
+    pub fn try_write_until(&self, deadline: Instant) -> Option<WriteGuard<'_, T>> {
+        self.write_until(Some(deadline))
+    }
+
+    fn write_until(&self, deadline: Option<Instant>) -> Option<WriteGuard<'_, T>> {
         let mut s = self.state.load(Relaxed);
+        #[cfg(feature = "metrics")]
+        if s > 1 {
+            self.metrics.contended();
+        }
         loop {
             // Try to lock if unlocked.
             if s <= 1 {
                 match self.state.compare_exchange(s, u32::MAX, Acquire, Relaxed) {
-                    Ok(_) => return WriteGuard { rwlock: self },
+                    Ok(_) => return Some(self.write_guard()),
                     Err(e) => {
                         s = e;
                         continue;
@@ -110,7 +229,7 @@ impl<T> RwLock<T> {
                 }
             }
             // Block new readers by making sure the state is odd.
-            if s % 2 == 0 {
+            if s.is_multiple_of(2) {
                 match self.state.compare_exchange(s, s + 1, Relaxed, Relaxed) {
                     Ok(_) => {}
                     Err(e) => {
@@ -123,11 +242,197 @@ impl<T> RwLock<T> {
             let w = self.writer_wake_counter.load(Acquire);
             s = self.state.load(Relaxed);
             if s >= 2 {
-                crate::futex::wait(&self.writer_wake_counter, w);
+                if expired(deadline) {
+                    #[cfg(feature = "diagnostics")]
+                    crate::diagnostics::gave_up_writing(self);
+                    self.abandon_write();
+                    return None;
+                }
+                #[cfg(feature = "diagnostics")]
+                crate::diagnostics::waiting_to_write(self);
+                #[cfg(feature = "tracing")]
+                let _span = tracing::debug_span!("rwlock_wait", lock = ?(self as *const RwLock<T>))
+                    .entered();
+                #[cfg(feature = "metrics")]
+                self.metrics.sleeping(&self.writer_wake_counter, w);
+                crate::futex::wait_until(&self.writer_wake_counter, w, deadline);
                 s = self.state.load(Relaxed);
             }
         }
     }
+
+    /// Withdraws a timed out writer's block on new readers. Other waiting writers are woken to
+    /// restore it.
+    fn abandon_write(&self) {
+        let mut s = self.state.load(Relaxed);
+        while s % 2 == 1 && s != u32::MAX {
+            match self.state.compare_exchange(s, s - 1, Relaxed, Relaxed) {
+                Ok(_) => {
+                    crate::futex::wake_all(&self.state);
+                    self.writer_wake_counter.fetch_add(1, Release);
+                    crate::futex::wake_one(&self.writer_wake_counter);
+                    return;
+                }
+                Err(e) => s = e,
+            }
+        }
+    }
+
+    /// Acquires a read lock which can later be upgraded to a write lock without releasing it (ex:
+    /// to check then modify without another writer slipping in between). Ordinary readers may
+    /// share the lock, but only one upgradable read lock is held at a time.
+    pub fn upgradable_read(&self) -> UpgradableReadGuard<'_, T> {
+        let mut s = self.state.load(Relaxed);
+        #[cfg(feature = "metrics")]
+        if s % 2 == 1 || s & UPGRADABLE != 0 {
+            self.metrics.contended();
+        }
+        loop {
+            if s.is_multiple_of(2) && s & UPGRADABLE == 0 {
+                assert!(s < MAX_READERS, "too many readers");
+                match self
+                    .state
+                    .compare_exchange_weak(s, (s + 2) | UPGRADABLE, Acquire, Relaxed)
+                {
+                    Ok(_) => {
+                        self.acquired(false);
+                        return UpgradableReadGuard { rwlock: self };
+                    }
+                    Err(e) => s = e,
+                }
+                continue;
+            }
+            #[cfg(feature = "tracing")]
+            let _span =
+                tracing::debug_span!("rwlock_wait", lock = ?(self as *const RwLock<T>)).entered();
+            #[cfg(feature = "metrics")]
+            self.metrics.sleeping(&self.state, s);
+            crate::futex::wait(&self.state, s);
+            s = self.state.load(Relaxed);
+        }
+    }
+
+    /// Quiesces the lock (ex: to migrate or snapshot the protected data): marks a writer pending,
+    /// so new readers and upgradable readers wait, then waits until the current readers and
+    /// writers have exited and takes the lock exclusively. This is [`Self::write`] without a
+    /// deadline.
+    ///
+    /// The lock stays drained until the returned guard is dropped. Queues have their own drains
+    /// (ex: [`crate::mpsc::Channel::drain`]).
+    pub fn drain(&self) -> WriteGuard<'_, T> {
+        self.write()
+    }
+
+    /// The contention counters, shared by every process.
+    #[cfg(feature = "metrics")]
+    pub fn stats(&self) -> crate::metrics::LockStats {
+        self.metrics.stats()
+    }
+
+    #[inline]
+    fn read_guard(&self) -> ReadGuard<'_, T> {
+        self.acquired(false);
+        ReadGuard { rwlock: self }
+    }
+
+    #[inline]
+    fn write_guard(&self) -> WriteGuard<'_, T> {
+        self.acquired(true);
+        WriteGuard { rwlock: self }
+    }
+
+    #[inline]
+    #[cfg_attr(
+        not(any(
+            feature = "audit",
+            feature = "diagnostics",
+            feature = "trace",
+            feature = "tracing"
+        )),
+        allow(unused_variables)
+    )]
+    fn acquired(&self, exclusive: bool) {
+        crate::ordering::guard_fence();
+        #[cfg(feature = "metrics")]
+        self.metrics.acquired();
+        #[cfg(feature = "tracing")]
+        tracing::trace!(lock = ?(self as *const Self), exclusive, "rwlock acquired");
+        #[cfg(feature = "trace")]
+        crate::trace::record(
+            self,
+            match exclusive {
+                true => crate::trace::Op::Acquire,
+                false => crate::trace::Op::AcquireShared,
+            },
+        );
+        #[cfg(feature = "audit")]
+        crate::audit::lock(
+            self,
+            match exclusive {
+                true => crate::audit::Access::Lock,
+                false => crate::audit::Access::LockShared,
+            },
+        );
+        crate::usdt::probe!("lock_acquire", self as *const _);
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::acquired(
+            self,
+            match exclusive {
+                true => crate::diagnostics::Kind::Exclusive,
+                false => crate::diagnostics::Kind::Shared,
+            },
+        );
+    }
+
+    #[inline]
+    #[cfg_attr(
+        not(any(feature = "trace", feature = "tracing")),
+        allow(unused_variables)
+    )]
+    fn released(&self, exclusive: bool) {
+        #[cfg(feature = "trace")]
+        crate::trace::record(
+            self,
+            match exclusive {
+                true => crate::trace::Op::Release,
+                false => crate::trace::Op::ReleaseShared,
+            },
+        );
+        #[cfg(feature = "audit")]
+        crate::audit::lock(self, crate::audit::Access::Unlock);
+        crate::usdt::probe!("lock_release", self as *const _);
+        #[cfg(feature = "tracing")]
+        tracing::trace!(lock = ?(self as *const Self), exclusive, "rwlock released");
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::released(self);
+        crate::ordering::guard_fence();
+    }
+
+    /// Removes one read lock, or the upgradable read lock too if `upgradable`.
+    fn unlock_read(&self, upgradable: bool) {
+        let sub = match upgradable {
+            true => UPGRADABLE + 2,
+            false => 2,
+        };
+        let s = self.state.fetch_sub(sub, Release) - sub;
+        if s == 1 {
+            // The RwLock is now unlocked and there is a waiting writer, which we wake up.
+            self.writer_wake_counter.fetch_add(1, Release);
+            crate::futex::wake_one(&self.writer_wake_counter);
+        } else if s & !1 == UPGRADABLE + 2 {
+            // Only the upgradable read lock remains, whose holder may be waiting to upgrade.
+            self.writer_wake_counter.fetch_add(1, Release);
+            crate::futex::wake_all(&self.writer_wake_counter);
+        }
+        if upgradable {
+            // Wakes other processes waiting for the upgradable read lock.
+            crate::futex::wake_all(&self.state);
+        }
+    }
+}
+
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|d| Instant::now() >= d)
 }
 
 pub struct ReadGuard<'a, T> {
@@ -144,20 +449,95 @@ impl<T> Deref for ReadGuard<'_, T> {
 
 impl<T> Drop for ReadGuard<'_, T> {
     fn drop(&mut self) {
-        // Decrement the state by 2 to remove one read-lock.
-        if self.rwlock.state.fetch_sub(2, Release) == 3 {
-            // If we decremented from 3 to 1, that means the RwLock is now unlocked
-            // and there is a waiting writer, which we wake up.
-            self.rwlock.writer_wake_counter.fetch_add(1, Release);
-            crate::futex::wake_one(&self.rwlock.writer_wake_counter);
+        self.rwlock.released(false);
+        self.rwlock.unlock_read(false);
+    }
+}
+
+#[must_use = "if unused the RwLock will immediately unlock"]
+pub struct UpgradableReadGuard<'a, T> {
+    rwlock: &'a RwLock<T>,
+}
+
+impl<'a, T> UpgradableReadGuard<'a, T> {
+    /// Atomically upgrades to a write lock once the other readers have left. New readers are
+    /// blocked meanwhile.
+    pub fn upgrade(self) -> WriteGuard<'a, T> {
+        let rwlock = self.rwlock;
+        core::mem::forget(self);
+        let mut s = rwlock.state.load(Relaxed);
+        loop {
+            // Lock if only this read lock remains.
+            if s & !1 == UPGRADABLE + 2 {
+                match rwlock.state.compare_exchange(s, u32::MAX, Acquire, Relaxed) {
+                    Ok(_) => {
+                        rwlock.released(false);
+                        return rwlock.write_guard();
+                    }
+                    Err(e) => {
+                        s = e;
+                        continue;
+                    }
+                }
+            }
+            // Block new readers by making sure the state is odd.
+            if s.is_multiple_of(2) {
+                if let Err(e) = rwlock.state.compare_exchange(s, s + 1, Relaxed, Relaxed) {
+                    s = e;
+                    continue;
+                }
+            }
+            let w = rwlock.writer_wake_counter.load(Acquire);
+            s = rwlock.state.load(Relaxed);
+            if s & !1 != UPGRADABLE + 2 {
+                #[cfg(feature = "tracing")]
+                let _span =
+                    tracing::debug_span!("rwlock_wait", lock = ?(rwlock as *const RwLock<T>))
+                        .entered();
+                #[cfg(feature = "metrics")]
+                rwlock.metrics.sleeping(&rwlock.writer_wake_counter, w);
+                crate::futex::wait(&rwlock.writer_wake_counter, w);
+                s = rwlock.state.load(Relaxed);
+            }
         }
     }
 }
 
+impl<T> Deref for UpgradableReadGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { &*self.rwlock.value.get() }
+    }
+}
+
+impl<T> Drop for UpgradableReadGuard<'_, T> {
+    fn drop(&mut self) {
+        self.rwlock.released(false);
+        self.rwlock.unlock_read(true);
+    }
+}
+
 pub struct WriteGuard<'a, T> {
     rwlock: &'a RwLock<T>,
 }
 
+impl<'a, T> WriteGuard<'a, T> {
+    /// Atomically converts to a read lock, so the writer can keep reading what it published
+    /// without another writer slipping in between.
+    pub fn downgrade(self) -> ReadGuard<'a, T> {
+        let rwlock = self.rwlock;
+        core::mem::forget(self);
+        rwlock.released(true);
+        rwlock.state.store(2, Release);
+        // Waiting writers must mark themselves again to be woken when the read lock is released.
+        rwlock.writer_wake_counter.fetch_add(1, Release);
+        crate::futex::wake_one(&rwlock.writer_wake_counter);
+        crate::futex::wake_all(&rwlock.state);
+        rwlock.read_guard()
+    }
+}
+
 impl<T> Deref for WriteGuard<'_, T> {
     type Target = T;
 
@@ -174,9 +554,145 @@ impl<T> DerefMut for WriteGuard<'_, T> {
 
 impl<T> Drop for WriteGuard<'_, T> {
     fn drop(&mut self) {
+        self.rwlock.released(true);
         self.rwlock.state.store(0, Release);
         self.rwlock.writer_wake_counter.fetch_add(1, Release);
         crate::futex::wake_one(&self.rwlock.writer_wake_counter);
         crate::futex::wake_all(&self.rwlock.state);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{
+            sync::atomic::AtomicBool,
+            thread,
+            time::{Duration, Instant},
+        },
+    };
+
+    #[test]
+    fn drain() {
+        let rwlock = RwLock::new(0);
+        let released = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            let reader = rwlock.read();
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                released.store(true, Relaxed);
+                drop(reader);
+            });
+
+            let timer = Instant::now();
+            let drained = rwlock.drain();
+            assert!(released.load(Relaxed));
+            assert!(timer.elapsed() >= Duration::from_millis(50));
+            assert!(rwlock.try_read().is_none());
+            drop(drained);
+            assert!(rwlock.try_read().is_some());
+        });
+    }
+
+    #[test]
+    fn downgrade() {
+        let rwlock = RwLock::new(0);
+        let mut writer = rwlock.write();
+        *writer += 1;
+
+        thread::scope(|s| {
+            let waiting = s.spawn(|| *rwlock.write() += 1);
+            thread::sleep(Duration::from_millis(20));
+            let reader = writer.downgrade();
+            thread::sleep(Duration::from_millis(20));
+            assert_eq!(*reader, 1);
+            drop(reader);
+            waiting.join().unwrap();
+        });
+        assert_eq!(*rwlock.read(), 2);
+    }
+
+    #[test]
+    fn upgradable_read() {
+        let rwlock = RwLock::new(0);
+        let released = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            let upgradable = rwlock.upgradable_read();
+            let reader = rwlock.read();
+            assert!(rwlock.try_write_for(Duration::from_millis(10)).is_none());
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(50));
+                released.store(true, Relaxed);
+                drop(reader);
+            });
+
+            let mut writer = upgradable.upgrade();
+            assert!(released.load(Relaxed));
+            assert!(rwlock.try_read().is_none());
+            *writer += 1;
+        });
+
+        let upgradable = rwlock.upgradable_read();
+        assert_eq!(*rwlock.read(), 1);
+        drop(upgradable);
+        drop(rwlock.upgradable_read());
+        assert!(rwlock.try_write_for(Duration::ZERO).is_some());
+    }
+
+    #[test]
+    fn timeout() {
+        let rwlock = RwLock::new(0);
+        let timeout = Duration::from_millis(20);
+
+        let reader = rwlock.read();
+        assert!(rwlock.try_write_for(timeout).is_none());
+        // The abandoned writer no longer blocks readers.
+        assert!(rwlock.try_read_for(timeout).is_some());
+        drop(reader);
+
+        let writer = rwlock.try_write_until(Instant::now()).unwrap();
+        let timer = Instant::now();
+        assert!(rwlock.try_read_for(timeout).is_none());
+        assert!(timer.elapsed() >= timeout);
+        drop(writer);
+        assert!(rwlock.try_read_for(timeout).is_some());
+    }
+
+    #[cfg(all(feature = "async", not(loom)))]
+    #[test]
+    fn read_async() {
+        let rwlock = RwLock::new(0);
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        thread::scope(|s| {
+            let mut writer = rwlock.write();
+            s.spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                *writer += 1;
+            });
+            assert_eq!(runtime.block_on(async { *rwlock.read_async().await }), 1);
+        });
+    }
+
+    #[cfg(loom)]
+    #[test]
+    fn loom_rwlock() {
+        use loom::sync::Arc;
+
+        loom::model(|| {
+            let rwlock = Arc::new(RwLock::new(0));
+            let writer = loom::thread::spawn({
+                let rwlock = rwlock.clone();
+                move || *rwlock.write() += 1
+            });
+            let value = *rwlock.read();
+            assert!(value == 0 || value == 1);
+            writer.join().unwrap();
+            assert_eq!(*rwlock.read(), 1);
+        });
+    }
+}
@@ -0,0 +1,169 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::{
+        cell::UnsafeCell,
+        sync::atomic::{fence, AtomicU32},
+    },
+};
+
+/// A sequence lock: readers copy the value optimistically and retry if a write overlapped, so
+/// readers never block the writer (or each other).
+///
+/// Suited to small Copy values published at a high rate.
+///
+/// A writer which dies mid-update leaves the sequence odd, so [`read`](Self::read) would spin
+/// forever. [`try_read`](Self::try_read) reports the dead writer instead, and a supervisor may
+/// [`recover`](Self::recover) by publishing a known-good value.
+#[derive(Default)]
+pub struct SeqLock<T> {
+    /// Odd while a write is in progress
+    seq: AtomicU32,
+    /// The id (see [`crate::owner`]) of the process writing (0 if none), which serializes writers
+    writer: AtomicU32,
+    data: UnsafeCell<T>,
+}
+
+/// Returned by [`SeqLock::try_read`] when the writer died mid-update
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WriterDied {
+    /// The dead writer's id in the region, which was only unique among live processes
+    pub writer: u32,
+}
+
+unsafe impl<T: Copy + Send> Sync for SeqLock<T> {}
+
+unsafe impl<T: Shareable + Copy + Send> Shareable for SeqLock<T> {}
+
+impl<T: Copy> SeqLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            writer: AtomicU32::new(0),
+            data: UnsafeCell::new(value),
+        }
+    }
+
+    /// Returns a consistent copy of the value, retrying while writes overlap the read.
+    pub fn read(&self) -> T {
+        loop {
+            if let Some(value) = self.read_once() {
+                return value;
+            }
+        }
+    }
+
+    /// Like [`read`](Self::read), but fails rather than spinning forever once the writer holding
+    /// the sequence odd has died.
+    pub fn try_read(&self) -> Result<T, WriterDied> {
+        loop {
+            if let Some(value) = self.read_once() {
+                return Ok(value);
+            }
+            if let Some(writer) = self.dead_writer() {
+                // The writer may have completed its update before dying
+                return self.read_once().ok_or(WriterDied { writer });
+            }
+        }
+    }
+
+    fn read_once(&self) -> Option<T> {
+        let seq = self.seq.load(Acquire);
+        if seq & 1 == 1 {
+            crate::spin::relax(&self.seq, seq);
+            return None;
+        }
+        // [SAFETY]: A torn copy is possible but is discarded below when the sequence changed.
+        let value = unsafe { self.data.get().read_volatile() };
+        fence(Acquire);
+        (self.seq.load(Relaxed) == seq).then_some(value)
+    }
+
+    /// Publishes a new value. Concurrent writers are serialized.
+    pub fn write(&self, value: T) {
+        let id = crate::owner::id(self);
+        while let Err(writer) = self.writer.compare_exchange_weak(0, id, Acquire, Relaxed) {
+            crate::spin::relax(&self.writer, writer);
+        }
+        self.publish(value);
+    }
+
+    /// Publishes `value` in place of an update interrupted by the death of its writer, so
+    /// readers stop spinning. Returns false if the writer is alive (or there is none), or another
+    /// process recovered first.
+    pub fn recover(&self, value: T) -> bool {
+        let Some(dead) = self.dead_writer() else {
+            return false;
+        };
+        if self
+            .writer
+            .compare_exchange(dead, crate::owner::id(self), Acquire, Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        self.publish(value);
+        true
+    }
+
+    /// The id of the writer if it died without finishing
+    fn dead_writer(&self) -> Option<u32> {
+        let writer = self.writer.load(Relaxed);
+        (writer != 0 && !crate::owner::alive(self, writer)).then_some(writer)
+    }
+
+    /// Writes the value while holding the writer slot, then releases it.
+    fn publish(&self, value: T) {
+        // Odd already if a dead writer's update is being rolled forward
+        let seq = self.seq.load(Relaxed) | 1;
+        self.seq.store(seq, Relaxed);
+        fence(Release);
+        unsafe { self.data.get().write_volatile(value) };
+        self.seq.store(seq.wrapping_add(1), Release);
+        self.writer.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn consistent_reads() {
+        let lock = SeqLock::new([0u64; 8]);
+        thread::scope(|s| {
+            s.spawn(|| {
+                for i in 1..=10_000 {
+                    lock.write([i; 8]);
+                }
+            });
+            loop {
+                let value = lock.read();
+                assert!(value.iter().all(|&v| v == value[0]));
+                if value[0] == 10_000 {
+                    break;
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn writer_died() {
+        let lock = SeqLock::new(1u64);
+        assert!(!lock.recover(2));
+
+        // Simulates a writer which died mid-update
+        let writer = i32::MAX as u32;
+        lock.writer.store(writer, Relaxed);
+        lock.seq.store(1, Relaxed);
+        assert_eq!(lock.try_read(), Err(WriterDied { writer }));
+
+        assert!(lock.recover(3));
+        assert!(!lock.recover(4));
+        assert_eq!(lock.try_read(), Ok(3));
+        lock.write(5);
+        assert_eq!(lock.read(), 5);
+    }
+}
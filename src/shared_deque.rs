@@ -0,0 +1,147 @@
+use crate::{Mutex, Shareable};
+
+struct Deque<T, const N: usize> {
+    /// Index of the front element
+    head: usize,
+    len: usize,
+    /// Set once pushes are refused
+    closed: bool,
+    slots: [T; N],
+}
+
+/// A bounded double-ended queue (ex: a coordinator appends to the back while workers take from
+/// the front and return unfinished items to it).
+///
+/// A single lock guards both ends and the elements, which are moved while it's held, so
+/// operations on either end serialize. Elements should be cheap to move (ex: indices or small
+/// descriptors).
+///
+/// The deque may be closed to new elements, and drained of pushes in progress (see
+/// [`SharedDeque::drain`]).
+pub struct SharedDeque<T, const N: usize> {
+    deque: Mutex<Deque<T, N>>,
+}
+
+unsafe impl<T: Shareable + Send, const N: usize> Shareable for SharedDeque<T, N> {}
+
+impl<T: Default, const N: usize> Default for SharedDeque<T, N> {
+    fn default() -> Self {
+        Self {
+            deque: Mutex::new(Deque {
+                head: 0,
+                len: 0,
+                closed: false,
+                slots: core::array::from_fn(|_| T::default()),
+            }),
+        }
+    }
+}
+
+impl<T: Default, const N: usize> SharedDeque<T, N> {
+    /// Appends to the back, returning the value if the deque is full or closed.
+    pub fn push_back(&self, value: T) -> Result<(), T> {
+        self.push(value, |d| (d.head + d.len) % N)
+    }
+
+    /// Prepends to the front, returning the value if the deque is full or closed.
+    pub fn push_front(&self, value: T) -> Result<(), T> {
+        self.push(value, |d| {
+            d.head = (d.head + N - 1) % N;
+            d.head
+        })
+    }
+
+    pub fn pop_front(&self) -> Option<T> {
+        self.pop(|d| {
+            let index = d.head;
+            d.head = (d.head + 1) % N;
+            index
+        })
+    }
+
+    pub fn pop_back(&self) -> Option<T> {
+        self.pop(|d| (d.head + d.len - 1) % N)
+    }
+
+    pub fn len(&self) -> usize {
+        self.deque.lock().len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Closes the deque to new elements. Elements already pushed may still be popped.
+    pub fn close(&self) {
+        self.deque.lock().closed = true;
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.deque.lock().closed
+    }
+
+    /// Closes the deque, so only pops change it (ex: while the region is migrated). Elements are
+    /// moved under the deque's lock, so no push is left in progress once it's closed.
+    pub fn drain(&self) {
+        self.close();
+    }
+
+    /// `put` is only called on a deque with room and returns the index to fill.
+    fn push(&self, value: T, put: impl FnOnce(&mut Deque<T, N>) -> usize) -> Result<(), T> {
+        const { assert!(N > 0, "the deque must hold at least one element") };
+        let mut deque = self.deque.lock();
+        if deque.len == N || deque.closed {
+            return Err(value);
+        }
+        let index = put(&mut deque);
+        deque.slots[index] = value;
+        deque.len += 1;
+        Ok(())
+    }
+
+    /// `take` is only called on a non-empty deque and returns the index to pop.
+    fn pop(&self, take: impl FnOnce(&mut Deque<T, N>) -> usize) -> Option<T> {
+        let mut deque = self.deque.lock();
+        if deque.len == 0 {
+            return None;
+        }
+        let index = take(&mut deque);
+        deque.len -= 1;
+        Some(core::mem::take(&mut deque.slots[index]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn both_ends() {
+        let deque = SharedDeque::<u64, 3>::default();
+        assert_eq!(deque.pop_front(), None);
+
+        deque.push_back(2).unwrap();
+        deque.push_back(3).unwrap();
+        deque.push_front(1).unwrap();
+        assert_eq!(deque.push_back(4), Err(4));
+        assert_eq!(deque.len(), 3);
+
+        assert_eq!(deque.pop_front(), Some(1));
+        assert_eq!(deque.pop_back(), Some(3));
+        deque.push_front(0).unwrap();
+        assert_eq!(deque.pop_front(), Some(0));
+        assert_eq!(deque.pop_front(), Some(2));
+        assert!(deque.is_empty());
+    }
+
+    #[test]
+    fn drain() {
+        let deque = SharedDeque::<u64, 2>::default();
+        deque.push_back(1).unwrap();
+        deque.drain();
+        assert!(deque.is_closed());
+        assert_eq!(deque.push_back(2), Err(2));
+        assert_eq!(deque.push_front(0), Err(0));
+        assert_eq!(deque.pop_front(), Some(1));
+    }
+}
@@ -0,0 +1,182 @@
+use {
+    crate::ordering::{Acquire, Relaxed, Release},
+    core::{
+        cell::UnsafeCell,
+        ops::{Deref, DerefMut},
+        sync::atomic::AtomicU32,
+    },
+};
+
+/// A reader-writer lock built on a semaphore of N reader permits: readers take one permit and a
+/// writer takes all N, which bounds reader parallelism to N.
+///
+/// With writer preference (the default) a waiting writer claims permits as readers release them,
+/// so new readers queue behind it. Otherwise the writer waits until all permits are free at once.
+pub struct SharedLock<T, const N: u32> {
+    /// The number of free permits (also the futex waited on for permits)
+    permits: AtomicU32,
+    /// 1 while a writer is acquiring or holding the permits (serializes writers)
+    writer: AtomicU32,
+    prefer_writer: bool,
+    value: UnsafeCell<T>,
+}
+
+unsafe impl<T: Send + Sync, const N: u32> Sync for SharedLock<T, N> {}
+
+unsafe impl<T: crate::Shareable + Send, const N: u32> crate::Shareable for SharedLock<T, N> {}
+
+impl<T: Default, const N: u32> Default for SharedLock<T, N> {
+    fn default() -> Self {
+        Self::new(Default::default(), true)
+    }
+}
+
+impl<T, const N: u32> SharedLock<T, N> {
+    pub const fn new(value: T, prefer_writer: bool) -> Self {
+        assert!(N > 0, "at least one permit is required");
+        Self {
+            permits: AtomicU32::new(N),
+            writer: AtomicU32::new(0),
+            prefer_writer,
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn try_read(&self) -> Option<ReadGuard<'_, T, N>> {
+        self.permits
+            .fetch_update(Acquire, Relaxed, |p| p.checked_sub(1))
+            .ok()
+            .map(|_| ReadGuard { lock: self })
+    }
+
+    /// Takes a reader permit, blocking while all permits are taken.
+    pub fn read(&self) -> ReadGuard<'_, T, N> {
+        loop {
+            if let Some(guard) = self.try_read() {
+                return guard;
+            }
+            crate::futex::wait(&self.permits, 0);
+        }
+    }
+
+    pub fn try_write(&self) -> Option<WriteGuard<'_, T, N>> {
+        if self
+            .writer
+            .compare_exchange(0, 1, Acquire, Relaxed)
+            .is_err()
+        {
+            return None;
+        }
+        if self
+            .permits
+            .compare_exchange(N, 0, Acquire, Relaxed)
+            .is_err()
+        {
+            self.writer.store(0, Release);
+            crate::futex::wake_one(&self.writer);
+            return None;
+        }
+        Some(WriteGuard { lock: self })
+    }
+
+    /// Takes all permits, blocking until every reader has released its permit.
+    pub fn write(&self) -> WriteGuard<'_, T, N> {
+        while self.writer.swap(1, Acquire) != 0 {
+            crate::futex::wait(&self.writer, 1);
+        }
+
+        let mut taken = 0;
+        while taken < N {
+            let permits = self.permits.load(Relaxed);
+            let claim = match self.prefer_writer {
+                true => permits,
+                false if taken + permits == N => permits,
+                false => 0,
+            };
+            if claim == 0 {
+                crate::futex::wait(&self.permits, permits);
+            } else if self
+                .permits
+                .compare_exchange(permits, permits - claim, Acquire, Relaxed)
+                .is_ok()
+            {
+                taken += claim;
+            }
+        }
+        WriteGuard { lock: self }
+    }
+}
+
+pub struct ReadGuard<'a, T, const N: u32> {
+    lock: &'a SharedLock<T, N>,
+}
+
+impl<T, const N: u32> Deref for ReadGuard<'_, T, N> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T, const N: u32> Drop for ReadGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.lock.permits.fetch_add(1, Release);
+        // Wakes a blocked reader, or the writer waiting for the last permit
+        crate::futex::wake_all(&self.lock.permits);
+    }
+}
+
+pub struct WriteGuard<'a, T, const N: u32> {
+    lock: &'a SharedLock<T, N>,
+}
+
+impl<T, const N: u32> Deref for WriteGuard<'_, T, N> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        unsafe { &*self.lock.value.get() }
+    }
+}
+
+impl<T, const N: u32> DerefMut for WriteGuard<'_, T, N> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { &mut *self.lock.value.get() }
+    }
+}
+
+impl<T, const N: u32> Drop for WriteGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.lock.permits.store(N, Release);
+        crate::futex::wake_all(&self.lock.permits);
+        self.lock.writer.store(0, Release);
+        crate::futex::wake_one(&self.lock.writer);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn bounded_readers() {
+        let lock = SharedLock::<u64, 2>::new(0, true);
+        let (a, b) = (lock.read(), lock.read());
+        assert!(lock.try_read().is_none());
+        assert!(lock.try_write().is_none());
+        drop((a, b));
+
+        for prefer_writer in [true, false] {
+            let lock = SharedLock::<u64, 4>::new(0, prefer_writer);
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            *lock.write() += 1;
+                            assert!(*lock.read() > 0);
+                        }
+                    });
+                }
+            });
+            assert_eq!(*lock.read(), 4000);
+        }
+    }
+}
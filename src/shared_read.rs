@@ -0,0 +1,91 @@
+use {
+    crate::{
+        header::{self, Header},
+        unmap, Error, Result, Shareable, ShmFd,
+    },
+    std::{ffi::CStr, mem::align_of, num::NonZeroUsize, ops::Deref},
+};
+
+/// A read-only mapping of a region, for observers which must not be able to modify it.
+///
+/// The mapping is not writable, so only operations which load (ex: atomic loads, reading plain
+/// data) may be used. Locking a Mutex or RwLock, loading a [`crate::AtomicU128`] (which writes),
+/// or any other write, faults (SIGSEGV).
+pub struct SharedRead<T> {
+    /// Keeps the region alive (for the heap backend)
+    _fd: ShmFd,
+    /// The start of the mapping
+    base: *const u8,
+    ptr: *const T,
+    len: NonZeroUsize,
+}
+
+unsafe impl<T: Shareable> Send for SharedRead<T> {}
+unsafe impl<T: Shareable> Sync for SharedRead<T> {}
+
+impl<T> Deref for SharedRead<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // [SAFETY]: The pointer is aligned and valid for the access bounds, which is verified
+        // prior to constructing the SharedRead<T> instance.
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<T> Drop for SharedRead<T> {
+    fn drop(&mut self) {
+        unmap(self.base.cast_mut().cast(), self.len.get());
+    }
+}
+
+impl<T: Shareable> SharedRead<T> {
+    /// Opens the region with `O_RDONLY` and maps it with `PROT_READ`.
+    ///
+    /// # Safety
+    ///
+    /// See [`crate::Shared::open`].
+    pub unsafe fn open(name: &CStr) -> Result<Self> {
+        // [SAFETY]: The size of T is verified at compile-time to be non-zero.
+        #[allow(clippy::let_unit_value)]
+        let _ = crate::SizeIsNonZeroI64::<T>::OK;
+        let len = header::region_len::<T>();
+
+        let fd = ShmFd::open_read_only(name).map_err(Error::Open)?;
+        if Some(len.get()) != fd.len() {
+            return Err(Error::LengthMismatch);
+        }
+
+        let base = fd.map_read_only(len, align_of::<T>())?.cast::<u8>();
+        let shared = Self {
+            _fd: fd,
+            base,
+            // [SAFETY]: The payload offset is within the region and aligned for T.
+            ptr: unsafe { base.add(header::payload_offset::<T>()) }.cast(),
+            len,
+        };
+        let header = unsafe { &*base.cast::<Header>() };
+        header.wait_ready(&shared._fd)?;
+        header.validate::<T>(1)?;
+        Ok(shared)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{AtomicF64, Shared},
+        std::sync::atomic::Ordering::Relaxed,
+    };
+
+    #[test]
+    fn observer() {
+        let name = c"/shared_read";
+        let master = unsafe { Shared::<AtomicF64>::create(name).unwrap() };
+        let observer = unsafe { SharedRead::<AtomicF64>::open(name).unwrap() };
+
+        master.store(1.5, Relaxed);
+        assert_eq!(observer.load(Relaxed), 1.5);
+    }
+}
@@ -0,0 +1,185 @@
+use {
+    crate::{
+        header::{self, Header},
+        msync, unmap, Error, Lifecycle, Result, Shareable, ShmFd,
+    },
+    std::{
+        ffi::{c_void, CStr},
+        mem::{align_of, size_of},
+        num::NonZeroUsize,
+        ops::Deref,
+    },
+};
+
+/// A runtime-sized slice providing inter-process access via shared memory.
+///
+/// Like [`crate::Shared`], the region starts with a header, so `open` waits for the creator to
+/// initialize the elements and rejects regions holding another type or length.
+pub struct SharedSlice<T> {
+    /// The start of the mapping
+    base: *mut u8,
+    /// The first element, which follows the header
+    ptr: *mut T,
+    len: usize,
+    mapped: NonZeroUsize,
+    fd: ShmFd,
+}
+
+unsafe impl<T: Shareable> Send for SharedSlice<T> {}
+unsafe impl<T: Shareable> Sync for SharedSlice<T> {}
+
+impl<T> Deref for SharedSlice<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        // [SAFETY]: The pointer is aligned and valid for `len` initialized elements, which is
+        // verified prior to constructing the SharedSlice<T> instance.
+        unsafe { std::slice::from_raw_parts(self.ptr, self.len) }
+    }
+}
+
+impl<T> Drop for SharedSlice<T> {
+    fn drop(&mut self) {
+        let bytes = self.mapped.get();
+        #[cfg(feature = "diagnostics")]
+        crate::diagnostics::detaching(self.base.cast(), bytes);
+        #[cfg(feature = "audit")]
+        crate::audit::detaching(self.base.cast());
+        // [SAFETY]: The header is mapped until unmap below.
+        let header = unsafe { &*self.base.cast::<Header>() };
+        self.fd.detach(Some(header.orphaned()));
+        unmap(self.base as *mut c_void, bytes);
+    }
+}
+
+impl<T> SharedSlice<T> {
+    /// Sets what happens to the region's name when this SharedSlice is dropped.
+    pub fn set_lifecycle(&mut self, lifecycle: Lifecycle) {
+        self.fd.lifecycle = lifecycle;
+    }
+}
+
+impl<T: Shareable> SharedSlice<T> {
+    /// Creates a region holding `len` default-initialized elements.
+    ///
+    /// Returns [`Error::LengthMismatch`] if the slice would be empty or its size overflows.
+    ///
+    /// # Safety
+    ///
+    /// See [`crate::Shared::create`].
+    pub unsafe fn create(name: &CStr, len: usize) -> Result<Self> {
+        let bytes = region_len::<T>(len)?;
+
+        let fd = ShmFd::create(name).map_err(Error::Open)?;
+        // Held from before the region is sized until it's ready, so openers wait for it
+        fd.lock_init().map_err(Error::Open)?;
+        fd.resize(bytes)?;
+
+        let shared = Self::map(fd, len, bytes)?;
+        // [SAFETY]: Successful truncation (above) guarantees the allocation holds the header and
+        // `len` elements. Pointer validity and alignment are validated in the mmap call.
+        unsafe {
+            Header::initialize::<T>(shared.base.cast(), &shared.fd, len, || {
+                for i in 0..len {
+                    T::init_in_place(shared.ptr.add(i));
+                }
+            })
+        }?;
+        let _ = msync(shared.base.cast(), bytes.get());
+        #[cfg(feature = "audit")]
+        crate::audit::attached(shared.fd.name.as_deref(), shared.base, bytes.get(), true);
+        Ok(shared)
+    }
+
+    /// Opens a region created with [`Self::create`], which must hold exactly `len` elements.
+    ///
+    /// If the region is still being sized or initialized this waits for the creator to finish (see
+    /// [`crate::Shared::open`]).
+    ///
+    /// # Safety
+    ///
+    /// See [`crate::Shared::open`].
+    pub unsafe fn open(name: &CStr, len: usize) -> Result<Self> {
+        let bytes = region_len::<T>(len)?;
+
+        let fd = ShmFd::open(name).map_err(Error::Open)?;
+        // The length is only final once the creator releases its lock
+        let _ = fd.wait_initialized();
+        if Some(bytes.get()) != fd.len() {
+            return Err(Error::LengthMismatch);
+        }
+
+        let shared = Self::map(fd, len, bytes)?;
+        let header = unsafe { &*shared.base.cast::<Header>() };
+        header.wait_ready(&shared.fd)?;
+        header.validate::<T>(len)?;
+        #[cfg(feature = "audit")]
+        crate::audit::attached(shared.fd.name.as_deref(), shared.base, bytes.get(), false);
+        Ok(shared)
+    }
+
+    fn map(fd: ShmFd, len: usize, bytes: NonZeroUsize) -> Result<Self> {
+        let base = fd
+            .map(bytes, align_of::<T>().max(align_of::<Header>()))?
+            .cast::<u8>();
+        Ok(Self {
+            base,
+            // [SAFETY]: The payload offset is within the region and aligned for T.
+            ptr: unsafe { base.add(header::payload_offset::<T>()) }.cast(),
+            len,
+            mapped: bytes,
+            fd,
+        })
+    }
+}
+
+/// The length of a region holding the header and `len` T's.
+fn region_len<T>(len: usize) -> Result<NonZeroUsize> {
+    len.checked_mul(size_of::<T>())
+        .filter(|&bytes| bytes > 0)
+        .and_then(|bytes| bytes.checked_add(header::payload_offset::<T>()))
+        .filter(|&bytes| i64::try_from(bytes).is_ok())
+        .and_then(NonZeroUsize::new)
+        .ok_or(Error::LengthMismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::AtomicF64, std::sync::atomic::Ordering::Relaxed};
+
+    #[test]
+    fn runtime_len() {
+        let name = c"/shared_slice";
+        let master = unsafe { SharedSlice::<AtomicF64>::create(name, 1000).unwrap() };
+        master[999].store(7.0, Relaxed);
+
+        let client = unsafe { SharedSlice::<AtomicF64>::open(name, 1000).unwrap() };
+        assert_eq!(client.len(), 1000);
+        assert_eq!(client[999].load(Relaxed), 7.0);
+
+        assert!(matches!(
+            unsafe { SharedSlice::<AtomicF64>::open(name, 999) },
+            Err(Error::LengthMismatch)
+        ));
+        assert!(matches!(
+            unsafe { SharedSlice::<AtomicF64>::create(c"/shared_slice_empty", 0) },
+            Err(Error::LengthMismatch)
+        ));
+        assert_eq!(crate::inspect(name).unwrap().size, 8000);
+    }
+
+    #[test]
+    fn validates_header() {
+        use crate::AtomicF32;
+
+        let name = c"/shared_slice_header";
+        let _master = unsafe { SharedSlice::<AtomicF64>::create(name, 2).unwrap() };
+
+        // Same length in bytes, but another element type
+        assert!(matches!(
+            unsafe { SharedSlice::<AtomicF32>::open(name, 4) },
+            Err(Error::LayoutMismatch)
+        ));
+        assert!(unsafe { SharedSlice::<AtomicF64>::open(name, 2) }.is_ok());
+    }
+}
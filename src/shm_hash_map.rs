@@ -0,0 +1,154 @@
+use {
+    crate::{lock_table::Fnv1a, Mutex, Shareable},
+    core::hash::{Hash, Hasher},
+};
+
+#[derive(Default)]
+enum Slot<K, V> {
+    #[default]
+    Empty,
+    Occupied(K, V),
+    /// Removed, so lookups continue probing past it
+    Removed,
+}
+
+/// A fixed capacity hash map of up to N entries, stored inline using open addressing (linear
+/// probing), so processes can share a lookup table (ex: symbol to id).
+///
+/// Each slot has its own lock, so lookups don't block each other. Inserts and removals are
+/// serialized by a writer lock. Keys are hashed with FNV-1a rather than std's per-process seeded
+/// hashers; processes sharing a map should be built with the same toolchain, as std's Hash
+/// implementations may differ between releases.
+pub struct ShmHashMap<K, V, const N: usize> {
+    /// Serializes inserts and removals, protecting the number of entries
+    writer: Mutex<usize>,
+    slots: [Mutex<Slot<K, V>>; N],
+}
+
+unsafe impl<K: Shareable + Send, V: Shareable + Send, const N: usize> Shareable
+    for ShmHashMap<K, V, N>
+{
+}
+
+impl<K, V, const N: usize> Default for ShmHashMap<K, V, N> {
+    fn default() -> Self {
+        const { assert!(N > 0, "the map must hold at least one entry") };
+        Self {
+            writer: Mutex::default(),
+            slots: core::array::from_fn(|_| Mutex::default()),
+        }
+    }
+}
+
+impl<K: Hash + Eq, V, const N: usize> ShmHashMap<K, V, N> {
+    /// Inserts or replaces the value of `key`, returning the previous value, or the entry if the
+    /// map is full.
+    pub fn insert(&self, key: K, value: V) -> Result<Option<V>, (K, V)> {
+        let mut len = self.writer.lock();
+        let mut free = None;
+        for i in self.probe(&key) {
+            let mut slot = self.slots[i].lock();
+            match &mut *slot {
+                Slot::Occupied(k, v) if *k == key => return Ok(Some(core::mem::replace(v, value))),
+                Slot::Occupied(..) => {}
+                Slot::Removed => {
+                    free.get_or_insert(i);
+                }
+                Slot::Empty => {
+                    free.get_or_insert(i);
+                    break;
+                }
+            }
+        }
+        let Some(i) = free else {
+            return Err((key, value));
+        };
+        *self.slots[i].lock() = Slot::Occupied(key, value);
+        *len += 1;
+        Ok(None)
+    }
+
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.find(key, V::clone)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.find(key, |_| ()).is_some()
+    }
+
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let mut len = self.writer.lock();
+        for i in self.probe(key) {
+            let mut slot = self.slots[i].lock();
+            match &*slot {
+                Slot::Empty => return None,
+                Slot::Occupied(k, _) if k == key => {
+                    let Slot::Occupied(_, value) = core::mem::replace(&mut *slot, Slot::Removed)
+                    else {
+                        unreachable!()
+                    };
+                    *len -= 1;
+                    return Some(value);
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    pub fn len(&self) -> usize {
+        *self.writer.lock()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Calls `f` with the value of `key` if present.
+    fn find<R>(&self, key: &K, f: impl FnOnce(&V) -> R) -> Option<R> {
+        for i in self.probe(key) {
+            match &*self.slots[i].lock() {
+                Slot::Empty => return None,
+                Slot::Occupied(k, v) if k == key => return Some(f(v)),
+                _ => {}
+            }
+        }
+        None
+    }
+
+    /// The slots to search for `key`, in order.
+    fn probe(&self, key: &K) -> impl Iterator<Item = usize> {
+        let mut hasher = Fnv1a::default();
+        key.hash(&mut hasher);
+        let start = (hasher.finish() % N as u64) as usize;
+        (0..N).map(move |i| (start + i) % N)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_remove() {
+        let map = ShmHashMap::<u64, u32, 4>::default();
+        for key in 0..4 {
+            assert_eq!(map.insert(key, key as u32), Ok(None));
+        }
+        assert_eq!(map.insert(4, 4), Err((4, 4)));
+        assert_eq!(map.insert(2, 20), Ok(Some(2)));
+        assert_eq!(map.len(), 4);
+
+        // Removed slots are reused, without hiding the entries probed past them.
+        assert_eq!(map.remove(&1), Some(1));
+        assert_eq!(map.get(&1), None);
+        assert_eq!(map.insert(4, 4), Ok(None));
+        for (key, value) in [(0, 0), (2, 20), (3, 3), (4, 4)] {
+            assert_eq!(map.get(&key), Some(value));
+        }
+        assert!(!map.contains_key(&1));
+    }
+}
@@ -0,0 +1,143 @@
+use {
+    crate::{is_aligned, Shareable, Shared},
+    std::{
+        fmt,
+        marker::PhantomData,
+        mem::{align_of, size_of},
+    },
+};
+
+/// A pointer to a T within a region, stored as an offset from the start of the region so it's
+/// valid in every process (each maps the region at a different address). Enables linked
+/// structures (ex: lists, trees) in shared memory.
+///
+/// The pointer records the region's [`Shared::generation`], so it can't be dereferenced through
+/// another region (or a recreated one).
+#[repr(C)]
+pub struct ShmPtr<T> {
+    /// The offset from the start of the region, or 0 if null (the region starts with its header)
+    offset: u64,
+    generation: u64,
+    _type: PhantomData<fn() -> T>,
+}
+
+unsafe impl<T> Shareable for ShmPtr<T> {}
+
+impl<T> Clone for ShmPtr<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ShmPtr<T> {}
+
+impl<T> Default for ShmPtr<T> {
+    fn default() -> Self {
+        Self::null()
+    }
+}
+
+impl<T> PartialEq for ShmPtr<T> {
+    fn eq(&self, other: &Self) -> bool {
+        (self.offset, self.generation) == (other.offset, other.generation)
+    }
+}
+
+impl<T> Eq for ShmPtr<T> {}
+
+impl<T> fmt::Debug for ShmPtr<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ShmPtr")
+            .field("offset", &self.offset)
+            .field("generation", &self.generation)
+            .finish()
+    }
+}
+
+impl<T> ShmPtr<T> {
+    pub const fn null() -> Self {
+        Self {
+            offset: 0,
+            generation: 0,
+            _type: PhantomData,
+        }
+    }
+
+    /// Points to `target`, or returns None if it's not within `region`.
+    ///
+    /// # Safety
+    ///
+    /// Every process which dereferences the pointer (see [`Self::get`]) receives a shared
+    /// reference to `target`. It must remain a valid T for as long as the pointer is used, and
+    /// must not be mutated other than through interior mutability (ex: not through a
+    /// [`crate::Mutex`] guard).
+    pub unsafe fn new<R>(region: &Shared<R>, target: &T) -> Option<Self> {
+        let offset = (target as *const T as usize).checked_sub(region.as_ptr() as usize)?;
+        (offset > 0 && offset.checked_add(size_of::<T>())? <= region.0.len.get()).then(|| Self {
+            offset: offset as u64,
+            generation: region.generation().unwrap_or(0),
+            _type: PhantomData,
+        })
+    }
+
+    pub fn is_null(&self) -> bool {
+        self.offset == 0
+    }
+
+    /// The offset of the target from the start of the region, or 0 if null.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Dereferences the pointer within `region`, or returns None if it's null, was created in
+    /// another region, or doesn't fit within the region.
+    pub fn get<'a, R>(&self, region: &'a Shared<R>) -> Option<&'a T> {
+        if self.is_null() || self.generation != region.generation().unwrap_or(0) {
+            return None;
+        }
+        let offset = usize::try_from(self.offset).ok()?;
+        if offset.checked_add(size_of::<T>())? > region.0.len.get() {
+            return None;
+        }
+        let ptr = region.as_ptr().wrapping_add(offset);
+        // [SAFETY]: The target is within the region and aligned, and was a T when the pointer
+        // was created (see Self::new).
+        is_aligned(ptr as usize, align_of::<T>()).then(|| unsafe { &*ptr.cast::<T>() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{AtomicF64, Mutex},
+        std::sync::atomic::{AtomicU32, Ordering::Relaxed},
+    };
+
+    #[derive(Default)]
+    struct Nodes {
+        values: [AtomicU32; 4],
+        head: Mutex<ShmPtr<AtomicU32>>,
+    }
+
+    unsafe impl Shareable for Nodes {}
+
+    #[test]
+    fn shm_ptr() {
+        let name = c"/shm_ptr";
+        let master: Shared<Nodes> = unsafe { Shared::create(name).unwrap() };
+        let client: Shared<Nodes> = unsafe { Shared::open(name).unwrap() };
+
+        let ptr = unsafe { ShmPtr::new(&master, &master.values[2]) }.unwrap();
+        *master.head.lock() = ptr;
+
+        // The same target at a different address in the client's mapping
+        let head = *client.head.lock();
+        head.get(&client).unwrap().store(7, Relaxed);
+        assert_eq!(master.values[2].load(Relaxed), 7);
+
+        let other: Shared<AtomicF64> = Shared::create_anon().unwrap();
+        assert!(head.get(&other).is_none());
+        assert!(ShmPtr::<AtomicU32>::null().get(&client).is_none());
+    }
+}
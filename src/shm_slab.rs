@@ -0,0 +1,156 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::sync::atomic::{AtomicU32, AtomicU64},
+};
+
+/// Terminates the free list
+const NIL: u32 = u32::MAX;
+
+/// A pool of N slots (ex: connection states) shared by several processes. Slots are allocated and
+/// freed lock-free, and keep their index while allocated so it can be handed to other processes.
+///
+/// The slab doesn't reset a slot's value, which the allocating process should (re)initialize
+/// through interior mutability. Slots allocated by a process which exits are not reclaimed.
+pub struct ShmSlab<T, const N: usize> {
+    /// The first free slot (low half) and a tag incremented on every update, which prevents ABA
+    free: AtomicU64,
+    /// The free slot following each free slot
+    next: [AtomicU32; N],
+    /// 1 while the slot is allocated
+    occupied: [AtomicU32; N],
+    slots: [T; N],
+}
+
+unsafe impl<T: Shareable, const N: usize> Shareable for ShmSlab<T, N> {}
+
+impl<T: Default, const N: usize> Default for ShmSlab<T, N> {
+    fn default() -> Self {
+        const {
+            assert!(
+                N > 0 && N < NIL as usize,
+                "capacity must be between 1 and 2^32 - 2"
+            )
+        };
+        Self {
+            free: AtomicU64::new(0),
+            next: core::array::from_fn(|i| {
+                AtomicU32::new(if i + 1 < N { i as u32 + 1 } else { NIL })
+            }),
+            occupied: core::array::from_fn(|_| AtomicU32::new(0)),
+            slots: core::array::from_fn(|_| T::default()),
+        }
+    }
+}
+
+impl<T, const N: usize> ShmSlab<T, N> {
+    /// Allocates a slot, returning its index or None if every slot is allocated.
+    pub fn allocate(&self) -> Option<usize> {
+        let mut head = self.free.load(Acquire);
+        loop {
+            let index = head as u32;
+            if index == NIL {
+                return None;
+            }
+            let next = self.next[index as usize].load(Relaxed);
+            match self
+                .free
+                .compare_exchange_weak(head, tagged(head, next), Acquire, Acquire)
+            {
+                Ok(_) => {
+                    self.occupied[index as usize].store(1, Release);
+                    return Some(index as usize);
+                }
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// Frees the slot at `index`, returning false if it wasn't allocated.
+    pub fn free(&self, index: usize) -> bool {
+        if self
+            .occupied
+            .get(index)
+            .is_none_or(|o| o.compare_exchange(1, 0, Relaxed, Relaxed).is_err())
+        {
+            return false;
+        }
+        let mut head = self.free.load(Relaxed);
+        loop {
+            self.next[index].store(head as u32, Relaxed);
+            match self.free.compare_exchange_weak(
+                head,
+                tagged(head, index as u32),
+                Release,
+                Relaxed,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => head = actual,
+            }
+        }
+    }
+
+    /// The slot at `index` if it's allocated.
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let occupied = self.occupied.get(index)?.load(Acquire) == 1;
+        occupied.then(|| &self.slots[index])
+    }
+
+    /// The allocated slots and their indices.
+    pub fn iter(&self) -> impl Iterator<Item = (usize, &T)> {
+        (0..N).filter_map(|i| self.get(i).map(|slot| (i, slot)))
+    }
+
+    /// The number of allocated slots.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// The free list head `head` updated to point to `index`.
+fn tagged(head: u64, index: u32) -> u64 {
+    let tag = (head >> 32) as u32;
+    u64::from(tag.wrapping_add(1)) << 32 | u64::from(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn allocate_free() {
+        let slab = ShmSlab::<AtomicU32, 8>::default();
+        let indices: Vec<_> = (0..8).map(|_| slab.allocate().unwrap()).collect();
+        assert_eq!(slab.allocate(), None);
+        assert!(slab.free(indices[3]));
+        assert!(!slab.free(indices[3]));
+        assert_eq!(slab.allocate(), Some(indices[3]));
+
+        for &i in &indices {
+            slab.free(i);
+        }
+        assert!(slab.is_empty());
+
+        // No slot is allocated twice at once.
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..10000 {
+                        let Some(i) = slab.allocate() else { continue };
+                        let slot = slab.get(i).unwrap();
+                        assert_eq!(slot.swap(1, Relaxed), 0);
+                        slot.store(0, Relaxed);
+                        assert!(slab.free(i));
+                    }
+                });
+            }
+        });
+        assert_eq!(slab.iter().count(), 0);
+    }
+}
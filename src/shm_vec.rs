@@ -0,0 +1,143 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::{cell::UnsafeCell, sync::atomic::AtomicU32},
+};
+
+/// An append-only vector of capacity N. Producers push concurrently while consumers read the
+/// elements published so far, which are never modified (other than through interior mutability).
+///
+/// Elements are published in the order their slots were claimed, so a push waits for earlier
+/// pushes to publish (a producer which dies in between stalls later pushes).
+pub struct ShmVec<T, const N: usize> {
+    /// The number of slots claimed by producers
+    reserved: AtomicU32,
+    /// The number of published elements (also the futex waited on by producers and consumers)
+    len: AtomicU32,
+    slots: [UnsafeCell<T>; N],
+}
+
+unsafe impl<T: Send + Sync, const N: usize> Sync for ShmVec<T, N> {}
+
+unsafe impl<T: Shareable + Send, const N: usize> Shareable for ShmVec<T, N> {}
+
+impl<T: Default, const N: usize> Default for ShmVec<T, N> {
+    fn default() -> Self {
+        const {
+            assert!(
+                N > 0 && N < u32::MAX as usize,
+                "capacity must be between 1 and 2^32 - 2"
+            )
+        };
+        Self {
+            reserved: AtomicU32::new(0),
+            len: AtomicU32::new(0),
+            slots: core::array::from_fn(|_| UnsafeCell::default()),
+        }
+    }
+}
+
+impl<T, const N: usize> ShmVec<T, N> {
+    /// Appends `value`, returning its index or the value if the vector is full.
+    pub fn push(&self, value: T) -> Result<usize, T> {
+        let mut reserved = self.reserved.load(Relaxed);
+        let index = loop {
+            if reserved as usize == N {
+                return Err(value);
+            }
+            match self
+                .reserved
+                .compare_exchange_weak(reserved, reserved + 1, Relaxed, Relaxed)
+            {
+                Ok(_) => break reserved,
+                Err(actual) => reserved = actual,
+            }
+        };
+
+        // [SAFETY]: A slot is only accessed by the producer which claimed it until published.
+        unsafe { *self.slots[index as usize].get() = value };
+
+        loop {
+            match self.len.load(Relaxed) {
+                len if len == index => break,
+                len => crate::futex::wait(&self.len, len),
+            }
+        }
+        self.len.store(index + 1, Release);
+        crate::futex::wake_all(&self.len);
+        Ok(index as usize)
+    }
+
+    /// The number of published elements.
+    pub fn len(&self) -> usize {
+        self.len.load(Acquire) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Blocks until more than `len` elements are published (or the vector is full), returning
+    /// the new length.
+    pub fn wait_len(&self, len: usize) -> usize {
+        loop {
+            match self.len.load(Acquire) {
+                current if current as usize > len || current as usize == N => {
+                    return current as usize
+                }
+                current => crate::futex::wait(&self.len, current),
+            }
+        }
+    }
+
+    /// The elements published so far.
+    pub fn as_slice(&self) -> &[T] {
+        let len = self.len();
+        // [SAFETY]: Published elements are no longer written, and UnsafeCell<T> has the same
+        // layout as T.
+        unsafe { core::slice::from_raw_parts(self.slots.as_ptr().cast::<T>(), len) }
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.as_slice().get(index)
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, T> {
+        self.as_slice().iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn concurrent_push() {
+        let vec = ShmVec::<u64, 400>::default();
+        thread::scope(|s| {
+            for t in 0..4 {
+                let vec = &vec;
+                s.spawn(move || {
+                    for i in 0..100 {
+                        vec.push(t * 100 + i).unwrap();
+                    }
+                });
+            }
+            let mut seen = 0;
+            while seen < 400 {
+                seen = vec.wait_len(seen);
+            }
+        });
+        assert_eq!(vec.push(0), Err(0));
+
+        let mut values: Vec<_> = vec.iter().copied().collect();
+        values.sort();
+        assert!(values.into_iter().eq(0..400));
+    }
+}
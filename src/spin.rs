@@ -0,0 +1,67 @@
+use core::sync::atomic::AtomicU32;
+
+/// A single iteration of a spin phase waiting for `a` to change from `value`.
+#[inline]
+pub(crate) fn relax(a: &impl Spin, value: u32) {
+    a.relax(value);
+}
+
+/// A 32-bit word spun on: an AtomicU32, or under `cfg(loom)` the loom atomic of a lock state
+/// machine, which yields to loom's scheduler so the spin can make progress.
+pub(crate) trait Spin {
+    fn relax(&self, value: u32);
+}
+
+impl Spin for AtomicU32 {
+    /// On aarch64 the core sleeps (WFE) until the cache line is written or the kernel's event
+    /// stream fires, rather than burning cycles and interconnect bandwidth re-reading the line.
+    #[inline]
+    fn relax(&self, value: u32) {
+        #[cfg(target_arch = "aarch64")]
+        // [SAFETY]: The exclusive load arms the monitor so a store to the line generates the event
+        // which wakes WFE. Nothing is written.
+        unsafe {
+            core::arch::asm!(
+                "ldxr {current:w}, [{addr}]",
+                "cmp {current:w}, {value:w}",
+                "b.ne 2f",
+                "wfe",
+                "2:",
+                addr = in(reg) self.as_ptr(),
+                value = in(reg) value,
+                current = out(reg) _,
+                options(nostack),
+            )
+        }
+
+        #[cfg(not(target_arch = "aarch64"))]
+        {
+            let _ = value;
+            core::hint::spin_loop();
+        }
+    }
+}
+
+#[cfg(loom)]
+impl Spin for loom::sync::atomic::AtomicU32 {
+    fn relax(&self, _: u32) {
+        loom::thread::yield_now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, core::sync::atomic::Ordering::Relaxed, std::thread};
+
+    #[test]
+    fn relax_until_changed() {
+        let a = AtomicU32::new(1);
+        thread::scope(|s| {
+            s.spawn(|| a.store(0, Relaxed));
+            while a.load(Relaxed) == 1 {
+                relax(&a, 1);
+            }
+        });
+        relax(&a, 1);
+    }
+}
@@ -0,0 +1,352 @@
+//! A single-producer single-consumer byte ring buffer.
+
+use {
+    crate::{
+        gate::Gate,
+        ordering::{Acquire, Relaxed, Release},
+        wait::{Park, WaitStrategy},
+        Shareable,
+    },
+    core::{cell::UnsafeCell, sync::atomic::AtomicU32},
+};
+
+/// Lock-free byte ring buffer of capacity N, which must be a power of two.
+///
+/// The ring may be closed to new writes, and drained of a write in progress (see
+/// [`Ring::drain`]).
+pub struct Ring<const N: usize> {
+    /// Total bytes written (wrapping; also the futex waited on by the consumer)
+    head: AtomicU32,
+    /// Total bytes read (wrapping; also the futex waited on by the producer)
+    tail: AtomicU32,
+    /// The id (see [`crate::owner`]) of the process holding each end, or 0
+    producer: AtomicU32,
+    consumer: AtomicU32,
+    /// Counts the writes in progress
+    writers: Gate,
+    buf: UnsafeCell<[u8; N]>,
+}
+
+unsafe impl<const N: usize> Sync for Ring<N> {}
+
+unsafe impl<const N: usize> Shareable for Ring<N> {}
+
+impl<const N: usize> Default for Ring<N> {
+    fn default() -> Self {
+        const {
+            assert!(
+                N.is_power_of_two() && N <= 1 << 31,
+                "capacity must be a power of two <= 2^31"
+            )
+        };
+        Self {
+            head: AtomicU32::new(0),
+            tail: AtomicU32::new(0),
+            producer: AtomicU32::new(0),
+            consumer: AtomicU32::new(0),
+            writers: Gate::new(),
+            buf: UnsafeCell::new([0; N]),
+        }
+    }
+}
+
+impl<const N: usize> Ring<N> {
+    /// Claims the producing end, returning None if another live process holds it.
+    ///
+    /// The end parks in the kernel while blocked unless given another [`WaitStrategy`] (see
+    /// [`Producer::with_wait`]).
+    pub fn producer(&self) -> Option<Producer<'_, N>> {
+        claim(&self.producer).then(|| Producer {
+            ring: self,
+            wait: Park,
+        })
+    }
+
+    /// Claims the consuming end, returning None if another live process holds it.
+    ///
+    /// The end parks in the kernel while blocked unless given another [`WaitStrategy`] (see
+    /// [`Consumer::with_wait`]).
+    pub fn consumer(&self) -> Option<Consumer<'_, N>> {
+        claim(&self.consumer).then(|| Consumer {
+            ring: self,
+            wait: Park,
+        })
+    }
+
+    /// Bytes available to the consumer.
+    pub fn len(&self) -> usize {
+        self.head
+            .load(Acquire)
+            .wrapping_sub(self.tail.load(Acquire)) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Closes the ring to new writes. Bytes already written may still be read.
+    pub fn close(&self) {
+        self.writers.close();
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.writers.is_closed()
+    }
+
+    /// Closes the ring and waits until a write in progress has finished, so only the consumer
+    /// changes it (ex: while the region is migrated). A write blocked on a full ring finishes once
+    /// the consumer makes room.
+    pub fn drain(&self) {
+        self.writers.drain();
+    }
+
+    /// The futex waited on by a blocked producer, which advances as bytes are consumed.
+    pub(crate) fn tail(&self) -> &AtomicU32 {
+        &self.tail
+    }
+
+    /// Copies `len` bytes at ring position `pos` (wrapping) to or from `bytes`.
+    fn copy(&self, pos: u32, bytes: *mut u8, len: usize, write: bool) {
+        let start = pos as usize & (N - 1);
+        let first = len.min(N - start);
+        let buf = self.buf.get().cast::<u8>();
+        for (offset, at, n) in [(0, start, first), (first, 0, len - first)] {
+            // [SAFETY]: The producer and consumer only access disjoint ranges of the buffer.
+            unsafe {
+                let (src, dst) = match write {
+                    true => (bytes.add(offset), buf.add(at)),
+                    false => (buf.add(at), bytes.add(offset)),
+                };
+                core::ptr::copy_nonoverlapping(src, dst, n);
+            }
+        }
+    }
+}
+
+/// Records this process as the holder of a role, unless another live process holds it.
+pub(crate) fn claim(end: &AtomicU32) -> bool {
+    let id = crate::owner::id(end);
+    let owner = end.load(Relaxed);
+    (owner == 0 || !crate::owner::alive(end, owner))
+        && end.compare_exchange(owner, id, Acquire, Relaxed).is_ok()
+}
+
+/// The writing end of a [`Ring`], released when dropped.
+pub struct Producer<'a, const N: usize, W = Park> {
+    ring: &'a Ring<N>,
+    wait: W,
+}
+
+impl<'a, const N: usize> Producer<'a, N> {
+    /// Waits with `wait` rather than parking while the ring is full.
+    pub fn with_wait<W: WaitStrategy>(self, wait: W) -> Producer<'a, N, W> {
+        // The claim carries over to the returned end.
+        let this = core::mem::ManuallyDrop::new(self);
+        Producer {
+            ring: this.ring,
+            wait,
+        }
+    }
+}
+
+impl<const N: usize, W: WaitStrategy> Producer<'_, N, W> {
+    /// Writes as many bytes as fit without blocking, returning the number written (0 if the ring
+    /// is closed).
+    pub fn try_push(&mut self, bytes: &[u8]) -> usize {
+        let Some(_writing) = self.ring.writers.enter() else {
+            return 0;
+        };
+        self.push_entered(bytes)
+    }
+
+    /// Writes all of `bytes`, blocking while the ring is full. Returns false, writing nothing, if
+    /// the ring is closed.
+    pub fn push(&mut self, mut bytes: &[u8]) -> bool {
+        let Some(_writing) = self.ring.writers.enter() else {
+            return false;
+        };
+        while !bytes.is_empty() {
+            let tail = self.ring.tail.load(Acquire);
+            match self.push_entered(bytes) {
+                0 => {
+                    self.wait.wait(&self.ring.tail, tail, None);
+                }
+                n => bytes = &bytes[n..],
+            }
+        }
+        true
+    }
+
+    /// Writes as many bytes as fit without blocking once counted as in progress.
+    fn push_entered(&mut self, bytes: &[u8]) -> usize {
+        let head = self.ring.head.load(Relaxed);
+        let free = N - head.wrapping_sub(self.ring.tail.load(Acquire)) as usize;
+        let len = bytes.len().min(free);
+        if len > 0 {
+            self.ring.copy(head, bytes.as_ptr().cast_mut(), len, true);
+            self.ring.head.store(head.wrapping_add(len as u32), Release);
+            crate::futex::wake_one(&self.ring.head);
+            self.wait.woke();
+        }
+        len
+    }
+}
+
+impl<const N: usize, W> Drop for Producer<'_, N, W> {
+    fn drop(&mut self) {
+        self.ring.producer.store(0, Release);
+    }
+}
+
+/// The reading end of a [`Ring`], released when dropped.
+pub struct Consumer<'a, const N: usize, W = Park> {
+    ring: &'a Ring<N>,
+    wait: W,
+}
+
+impl<'a, const N: usize> Consumer<'a, N> {
+    /// Waits with `wait` rather than parking while the ring is empty.
+    pub fn with_wait<W: WaitStrategy>(self, wait: W) -> Consumer<'a, N, W> {
+        // The claim carries over to the returned end.
+        let this = core::mem::ManuallyDrop::new(self);
+        Consumer {
+            ring: this.ring,
+            wait,
+        }
+    }
+}
+
+impl<const N: usize, W: WaitStrategy> Consumer<'_, N, W> {
+    /// Reads as many bytes as are available without blocking, returning the number read.
+    pub fn try_pop(&mut self, bytes: &mut [u8]) -> usize {
+        let tail = self.ring.tail.load(Relaxed);
+        let available = self.ring.head.load(Acquire).wrapping_sub(tail) as usize;
+        let len = bytes.len().min(available);
+        if len > 0 {
+            self.ring.copy(tail, bytes.as_mut_ptr(), len, false);
+            self.ring.tail.store(tail.wrapping_add(len as u32), Release);
+            crate::futex::wake_one(&self.ring.tail);
+            self.wait.woke();
+        }
+        len
+    }
+
+    /// Discards the unread bytes, waking a producer blocked on a full ring.
+    pub(crate) fn clear(&mut self) {
+        self.ring.tail.store(self.ring.head.load(Acquire), Release);
+        crate::futex::wake_all(&self.ring.tail);
+        self.wait.woke();
+    }
+
+    /// Reads at least one byte, blocking while the ring is empty.
+    ///
+    /// Returns 0 only if `bytes` is empty.
+    pub fn pop(&mut self, bytes: &mut [u8]) -> usize {
+        loop {
+            let head = self.ring.head.load(Acquire);
+            match self.try_pop(bytes) {
+                0 if !bytes.is_empty() => {
+                    self.wait.wait(&self.ring.head, head, None);
+                }
+                n => return n,
+            }
+        }
+    }
+}
+
+impl<const N: usize, W> Drop for Consumer<'_, N, W> {
+    fn drop(&mut self) {
+        self.ring.consumer.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, core::time::Duration, std::thread};
+
+    #[test]
+    fn stream() {
+        let ring = Ring::<8>::default();
+        assert!(ring.producer().is_some());
+        let mut producer = ring.producer().unwrap();
+        let mut consumer = ring.consumer().unwrap();
+        assert!(ring.consumer().is_none());
+        assert!(ring.consumer().is_none());
+
+        assert_eq!(producer.try_push(&[0; 10]), 8);
+        assert_eq!(consumer.try_pop(&mut [0; 3]), 3);
+        assert_eq!(consumer.try_pop(&mut [0; 10]), 5);
+        assert!(ring.is_empty());
+
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        thread::scope(|s| {
+            s.spawn(|| producer.push(&data));
+
+            let mut received = Vec::new();
+            let mut buf = [0; 5];
+            while received.len() < data.len() {
+                let n = consumer.pop(&mut buf);
+                received.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(received, data);
+        });
+    }
+
+    #[test]
+    fn doorbell() {
+        let ring = Ring::<8>::default();
+        let doorbell = crate::Doorbell::new().unwrap();
+        // The consumer's event loop would poll the Doorbell.
+        let mut producer = ring
+            .producer()
+            .unwrap()
+            .with_wait(crate::wait::Ringing::new(&doorbell));
+        let mut consumer = ring.consumer().unwrap().with_wait(&doorbell);
+
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+        thread::scope(|s| {
+            s.spawn(|| producer.push(&data));
+
+            let mut received = Vec::new();
+            let mut buf = [0; 5];
+            while received.len() < data.len() {
+                let n = consumer.pop(&mut buf);
+                received.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(received, data);
+        });
+    }
+
+    #[test]
+    fn dead_end() {
+        let ring = crate::Shared::<Ring<8>>::create_anon().unwrap();
+        let _producer = ring.producer().unwrap();
+        assert!(ring.producer().is_none());
+        // A consumer which exited without releasing its end
+        ring.consumer.store(i32::MAX as u32, Relaxed);
+        assert!(ring.consumer().is_some());
+    }
+
+    #[test]
+    fn drain() {
+        let ring = Ring::<4>::default();
+        let mut producer = ring.producer().unwrap();
+        let mut consumer = ring.consumer().unwrap();
+
+        thread::scope(|s| {
+            // Blocked on the full ring when it's drained
+            let writing = s.spawn(|| producer.push(&[1; 6]));
+            thread::sleep(Duration::from_millis(20));
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(20));
+                assert_eq!(consumer.try_pop(&mut [0; 4]), 4);
+            });
+            ring.drain();
+            assert!(writing.join().unwrap());
+        });
+        assert!(ring.is_closed());
+        assert_eq!(producer.try_push(&[2]), 0);
+        assert!(!producer.push(&[2]));
+        assert_eq!(consumer.try_pop(&mut [0; 4]), 2);
+    }
+}
@@ -0,0 +1,149 @@
+use {
+    crate::{
+        ordering::{AcqRel, Acquire},
+        AtomicWatch, Shareable,
+    },
+    core::{fmt, marker::PhantomData, sync::atomic::AtomicU32},
+};
+
+/// A state machine cell whose states are encoded as u32 values.
+///
+/// Every change of state wakes all waiters.
+pub struct StateCell<S> {
+    state: AtomicU32,
+    _state: PhantomData<fn() -> S>,
+}
+
+unsafe impl<S: Default + Into<u32>> Shareable for StateCell<S> {}
+
+impl<S: Default + Into<u32>> Default for StateCell<S> {
+    fn default() -> Self {
+        Self::new(S::default())
+    }
+}
+
+impl<S: Into<u32> + TryFrom<u32> + fmt::Debug> fmt::Debug for StateCell<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut d = f.debug_struct("StateCell");
+        match self.get() {
+            Some(state) => d.field("state", &state),
+            None => d.field("state", &self.get_raw()),
+        };
+        d.finish()
+    }
+}
+
+impl<S: Into<u32>> StateCell<S> {
+    pub fn new(initial: S) -> Self {
+        Self {
+            state: AtomicU32::new(initial.into()),
+            _state: PhantomData,
+        }
+    }
+
+    /// Returns the current state, or None if it doesn't decode as an S.
+    pub fn get(&self) -> Option<S>
+    where
+        S: TryFrom<u32>,
+    {
+        S::try_from(self.get_raw()).ok()
+    }
+
+    pub fn get_raw(&self) -> u32 {
+        self.state.load(Acquire)
+    }
+
+    /// Unconditionally enters `state`.
+    pub fn set(&self, state: S) {
+        self.watch().store_and_wake(state.into());
+    }
+
+    /// Enters `to` if the current state is `from`, otherwise returns the current state.
+    ///
+    /// A transition synchronizes with the one which entered `from`, so writes made before
+    /// entering a state are visible to whoever transitions out of it.
+    pub fn transition(&self, from: S, to: S) -> Result<(), u32> {
+        self.state
+            .compare_exchange(from.into(), to.into(), AcqRel, Acquire)
+            .map(|_| {
+                crate::futex::wake_all(&self.state);
+            })
+    }
+
+    /// Blocks until the cell enters `state`.
+    pub fn wait_for(&self, state: S) {
+        let state = state.into();
+        self.watch().wait_until(|v| v == state);
+    }
+
+    /// Blocks until the cell enters any of `states`, returning the raw value of that state.
+    pub fn wait_for_any(&self, states: impl IntoIterator<Item = S>) -> u32 {
+        let states: Vec<u32> = states.into_iter().map(Into::into).collect();
+        self.watch().wait_until(|v| states.contains(&v))
+    }
+
+    fn watch(&self) -> AtomicWatch<'_> {
+        AtomicWatch::new(&self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{thread, time::Duration},
+    };
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    enum Phase {
+        #[default]
+        Starting,
+        Running,
+        Stopping,
+        Stopped,
+    }
+
+    impl From<Phase> for u32 {
+        fn from(p: Phase) -> u32 {
+            p as u32
+        }
+    }
+
+    impl TryFrom<u32> for Phase {
+        type Error = u32;
+
+        fn try_from(v: u32) -> Result<Self, u32> {
+            match v {
+                0 => Ok(Phase::Starting),
+                1 => Ok(Phase::Running),
+                2 => Ok(Phase::Stopping),
+                3 => Ok(Phase::Stopped),
+                v => Err(v),
+            }
+        }
+    }
+
+    #[test]
+    fn state_cell() {
+        let cell = StateCell::<Phase>::default();
+        assert_eq!(
+            cell.transition(Phase::Running, Phase::Stopping),
+            Err(Phase::Starting as u32)
+        );
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(10));
+                cell.transition(Phase::Starting, Phase::Running).unwrap();
+                cell.wait_for(Phase::Stopping);
+                cell.set(Phase::Stopped);
+            });
+
+            cell.wait_for(Phase::Running);
+            cell.set(Phase::Stopping);
+            let v = cell.wait_for_any([Phase::Stopped, Phase::Starting]);
+            assert_eq!(v, Phase::Stopped as u32);
+        });
+        assert_eq!(cell.get(), Some(Phase::Stopped));
+    }
+}
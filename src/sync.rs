@@ -0,0 +1,23 @@
+//! The atomics of the lock state machines (Mutex, Condvar and RwLock), which are loom's under
+//! `cfg(loom)` so their interleavings can be model checked (ex:
+//! `RUSTFLAGS="--cfg loom" cargo test --release loom`).
+
+#[cfg(not(loom))]
+pub(crate) use core::sync::atomic::{AtomicIsize, AtomicU32, AtomicUsize};
+#[cfg(loom)]
+pub(crate) use loom::sync::atomic::{AtomicIsize, AtomicU32, AtomicUsize};
+
+/// Declares a const fn, which isn't const under `cfg(loom)` as loom's atomics can't be created in
+/// const contexts.
+macro_rules! const_fn {
+    ($(#[$attr:meta])* $vis:vis fn $($rest:tt)*) => {
+        #[cfg(not(loom))]
+        $(#[$attr])*
+        $vis const fn $($rest)*
+
+        #[cfg(loom)]
+        $(#[$attr])*
+        $vis fn $($rest)*
+    };
+}
+pub(crate) use const_fn;
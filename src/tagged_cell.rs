@@ -0,0 +1,163 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::{
+        cell::UnsafeCell,
+        mem::MaybeUninit,
+        sync::atomic::{fence, AtomicU32},
+    },
+};
+
+/// A cell holding a tagged value (ex: a Copy enum, whose discriminant and payload are a tagged
+/// union) which is replaced atomically, so status richer than an integer can be published
+/// without a mutex.
+///
+/// Like a [`crate::SeqLock`], readers copy the value optimistically and retry if a write
+/// overlapped. Copies are only interpreted as an E once known to be consistent, so a torn copy
+/// never yields an invalid discriminant.
+pub struct TaggedCell<E> {
+    /// Odd while a write is in progress
+    seq: AtomicU32,
+    value: UnsafeCell<MaybeUninit<E>>,
+}
+
+unsafe impl<E: Copy + Send> Sync for TaggedCell<E> {}
+
+unsafe impl<E: Shareable + Copy + Send> Shareable for TaggedCell<E> {}
+
+impl<E: Copy + Default> Default for TaggedCell<E> {
+    fn default() -> Self {
+        Self::new(E::default())
+    }
+}
+
+impl<E: Copy> TaggedCell<E> {
+    pub const fn new(value: E) -> Self {
+        Self {
+            seq: AtomicU32::new(0),
+            value: UnsafeCell::new(MaybeUninit::new(value)),
+        }
+    }
+
+    pub fn load(&self) -> E {
+        loop {
+            let seq = self.seq.load(Acquire);
+            if seq & 1 == 1 {
+                crate::spin::relax(&self.seq, seq);
+                continue;
+            }
+            // [SAFETY]: A torn copy is possible but is discarded below when the sequence changed.
+            let value = unsafe { self.value.get().read_volatile() };
+            fence(Acquire);
+            if self.seq.load(Relaxed) == seq {
+                // [SAFETY]: No write overlapped the copy, so it's a valid E.
+                return unsafe { value.assume_init() };
+            }
+        }
+    }
+
+    pub fn store(&self, value: E) {
+        let _ = self.update(|_| Some(value));
+    }
+
+    /// Atomically transitions to the value returned by `f` given the current value, unless it
+    /// returns None. Returns the previous value, or the current value as an error if unchanged.
+    ///
+    /// Writers are serialized, so `f` sees the latest value and should be short.
+    pub fn update(&self, f: impl FnOnce(E) -> Option<E>) -> Result<E, E> {
+        let seq = self.begin_write();
+        // [SAFETY]: Writers are excluded, and the value is always initialized.
+        let current = unsafe { (*self.value.get()).assume_init() };
+        match f(current) {
+            Some(value) => {
+                unsafe { self.value.get().write_volatile(MaybeUninit::new(value)) };
+                self.seq.store(seq.wrapping_add(2), Release);
+                Ok(current)
+            }
+            None => {
+                // Readers which overlapped saw the unchanged value.
+                self.seq.store(seq, Release);
+                Err(current)
+            }
+        }
+    }
+
+    /// Replaces the value with `new` if `matches` accepts the current one (ex: to only leave a
+    /// particular variant).
+    pub fn transition(&self, matches: impl FnOnce(&E) -> bool, new: E) -> Result<E, E> {
+        self.update(|current| matches(&current).then_some(new))
+    }
+
+    /// Marks a write in progress, returning the prior (even) sequence.
+    fn begin_write(&self) -> u32 {
+        let mut seq = self.seq.load(Relaxed);
+        loop {
+            if seq & 1 == 1 {
+                crate::spin::relax(&self.seq, seq);
+                seq = self.seq.load(Relaxed);
+                continue;
+            }
+            match self
+                .seq
+                .compare_exchange_weak(seq, seq.wrapping_add(1), Acquire, Relaxed)
+            {
+                Ok(_) => break,
+                Err(s) => seq = s,
+            }
+        }
+        fence(Release);
+        seq
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    enum Status {
+        #[default]
+        Idle,
+        Running {
+            step: u64,
+            check: u64,
+        },
+        Failed(u32),
+    }
+
+    #[test]
+    fn transitions() {
+        let cell = TaggedCell::<Status>::default();
+        assert_eq!(
+            cell.transition(|s| matches!(s, Status::Running { .. }), Status::Failed(1)),
+            Err(Status::Idle)
+        );
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                for step in 1..=10_000 {
+                    let check = !step;
+                    cell.store(Status::Running { step, check });
+                }
+            });
+            loop {
+                match cell.load() {
+                    Status::Running { step, check } => {
+                        assert_eq!(check, !step);
+                        if step == 10_000 {
+                            break;
+                        }
+                    }
+                    status => assert_eq!(status, Status::Idle),
+                }
+            }
+        });
+
+        assert!(cell
+            .transition(|s| matches!(s, Status::Running { .. }), Status::Failed(2))
+            .is_ok());
+        assert_eq!(cell.load(), Status::Failed(2));
+    }
+}
@@ -0,0 +1,271 @@
+use {
+    crate::{
+        ordering::{Acquire, Relaxed, Release},
+        Shareable,
+    },
+    core::{
+        mem::MaybeUninit,
+        sync::atomic::{AtomicU32, AtomicU64},
+        time::Duration,
+    },
+};
+
+// The phase of a timer, in the low bits of its state
+const PHASE: u32 = 0b11;
+const FREE: u32 = 0;
+const INIT: u32 = 1;
+const ARMED: u32 = 2;
+const FIRED: u32 = 3;
+
+/// Added to the state's generation each time a timer is freed, so a ticker which read an earlier
+/// schedule's deadline can't fire the timer once it's rescheduled.
+const GENERATION: u32 = PHASE + 1;
+
+/// The longest a ticker sleeps before rechecking its stop condition.
+const TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Default)]
+struct Timer {
+    /// The generation and phase: FREE, INIT, ARMED or FIRED (also the futex waited on by the
+    /// scheduling process)
+    state: AtomicU32,
+    /// Nanoseconds on the CLOCK_MONOTONIC timeline shared by all processes
+    deadline: AtomicU64,
+}
+
+impl Timer {
+    /// Fires the timer unless it was freed (and maybe rescheduled) since its `armed` state was
+    /// read.
+    fn fire(&self, armed: u32) -> bool {
+        let fired = self
+            .state
+            .compare_exchange(armed, armed | FIRED, Release, Relaxed)
+            .is_ok();
+        if fired {
+            crate::futex::wake_all(&self.state);
+        }
+        fired
+    }
+}
+
+/// Shared storage for timers which one elected ticker process fires on behalf of all others.
+pub struct TimerQueue<const N: usize> {
+    /// The id (see [`crate::owner`]) of the elected ticker process, or 0
+    ticker: AtomicU32,
+    /// Incremented whenever a timer is scheduled (wakes the ticker)
+    scheduled: AtomicU32,
+    timers: [Timer; N],
+}
+
+unsafe impl<const N: usize> Shareable for TimerQueue<N> {}
+
+impl<const N: usize> Default for TimerQueue<N> {
+    fn default() -> Self {
+        Self {
+            ticker: AtomicU32::new(0),
+            scheduled: AtomicU32::new(0),
+            timers: core::array::from_fn(|_| Timer::default()),
+        }
+    }
+}
+
+/// The current time on the system-wide CLOCK_MONOTONIC timeline used for TimerQueue deadlines.
+pub fn monotonic_now() -> Duration {
+    let mut ts = MaybeUninit::uninit();
+    assert_eq!(
+        unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, ts.as_mut_ptr()) },
+        0
+    );
+    let ts = unsafe { ts.assume_init() };
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+fn as_nanos(time: Duration) -> u64 {
+    u64::try_from(time.as_nanos()).unwrap_or(u64::MAX)
+}
+
+impl<const N: usize> TimerQueue<N> {
+    /// Schedules a wakeup at an absolute deadline (see [`monotonic_now`]).
+    ///
+    /// Returns None if all timers are in use.
+    pub fn schedule_at(&self, deadline: Duration) -> Option<TimerHandle<'_, N>> {
+        let (index, free) = self.timers.iter().enumerate().find_map(|(index, t)| {
+            let state = t.state.load(Relaxed);
+            (state & PHASE == FREE
+                && t.state
+                    .compare_exchange(state, state | INIT, Acquire, Relaxed)
+                    .is_ok())
+            .then_some((index, state))
+        })?;
+        let timer = &self.timers[index];
+        timer.deadline.store(as_nanos(deadline), Relaxed);
+        let armed = free | ARMED;
+        timer.state.store(armed, Release);
+
+        self.scheduled.fetch_add(1, Release);
+        crate::futex::wake_all(&self.scheduled);
+        Some(TimerHandle {
+            queue: self,
+            index,
+            armed,
+        })
+    }
+
+    /// Schedules a wakeup after `dur` has elapsed.
+    pub fn schedule_after(&self, dur: Duration) -> Option<TimerHandle<'_, N>> {
+        self.schedule_at(monotonic_now().saturating_add(dur))
+    }
+
+    /// Attempts to become the ticker, replacing a ticker process which has exited.
+    pub fn try_become_ticker(&self) -> bool {
+        let id = crate::owner::id(self);
+        let ticker = self.ticker.load(Relaxed);
+        ticker == id
+            || ((ticker == 0 || !crate::owner::alive(self, ticker))
+                && self
+                    .ticker
+                    .compare_exchange(ticker, id, Acquire, Relaxed)
+                    .is_ok())
+    }
+
+    /// Fires expired timers until `stop` returns true, provided this process is the ticker.
+    ///
+    /// Returns false immediately if another live process is the ticker.
+    pub fn run_ticker(&self, mut stop: impl FnMut() -> bool) -> bool {
+        if !self.try_become_ticker() {
+            return false;
+        }
+        while !stop() {
+            let scheduled = self.scheduled.load(Acquire);
+            let next = self.fire_expired();
+            let timeout = next
+                .map_or(TICK_INTERVAL, |next| next.saturating_sub(monotonic_now()))
+                .min(TICK_INTERVAL);
+            crate::futex::wait_timeout(&self.scheduled, scheduled, Some(timeout));
+        }
+        self.ticker.store(0, Release);
+        true
+    }
+
+    /// Fires every expired timer, returning the earliest pending deadline.
+    fn fire_expired(&self) -> Option<Duration> {
+        let now = as_nanos(monotonic_now());
+        self.timers
+            .iter()
+            .filter_map(|t| {
+                let state = t.state.load(Acquire);
+                if state & PHASE != ARMED {
+                    return None;
+                }
+                match t.deadline.load(Relaxed) {
+                    deadline if deadline <= now => {
+                        t.fire(state);
+                        None
+                    }
+                    deadline => Some(Duration::from_nanos(deadline)),
+                }
+            })
+            .min()
+    }
+}
+
+/// A scheduled wakeup, cancelled when dropped.
+pub struct TimerHandle<'a, const N: usize> {
+    queue: &'a TimerQueue<N>,
+    index: usize,
+    /// The state while armed, including the generation
+    armed: u32,
+}
+
+impl<const N: usize> TimerHandle<'_, N> {
+    pub fn has_fired(&self) -> bool {
+        self.timer().state.load(Acquire) == self.armed | FIRED
+    }
+
+    /// Blocks until the ticker fires the timer.
+    pub fn wait(&self) {
+        while !self.has_fired() {
+            crate::futex::wait(&self.timer().state, self.armed);
+        }
+    }
+
+    fn timer(&self) -> &Timer {
+        &self.queue.timers[self.index]
+    }
+}
+
+impl<const N: usize> Drop for TimerHandle<'_, N> {
+    fn drop(&mut self) {
+        let free = (self.armed & !PHASE).wrapping_add(GENERATION) | FREE;
+        self.timer().state.store(free, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        std::{sync::atomic::AtomicBool, thread, time::Instant},
+    };
+
+    #[test]
+    fn timer_queue() {
+        let queue = TimerQueue::<2>::default();
+        let done = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            s.spawn(|| assert!(queue.run_ticker(|| done.load(Relaxed))));
+
+            let timer = Instant::now();
+            let a = queue.schedule_after(Duration::from_millis(20)).unwrap();
+            let b = queue.schedule_after(Duration::from_millis(40)).unwrap();
+            assert!(queue.schedule_after(Duration::ZERO).is_none());
+
+            a.wait();
+            assert!(timer.elapsed() >= Duration::from_millis(20));
+            assert!(!b.has_fired());
+            b.wait();
+            assert!(timer.elapsed() >= Duration::from_millis(40));
+            done.store(true, Relaxed);
+        });
+    }
+
+    #[test]
+    fn reschedule_while_ticking() {
+        let queue = TimerQueue::<1>::default();
+        let done = AtomicBool::new(false);
+
+        thread::scope(|s| {
+            s.spawn(|| assert!(queue.run_ticker(|| done.load(Relaxed))));
+
+            for _ in 0..1000 {
+                // The ticker may read this expired deadline and then find the slot rescheduled.
+                drop(queue.schedule_at(Duration::ZERO).unwrap());
+                let later = queue.schedule_after(Duration::from_secs(60)).unwrap();
+                for _ in 0..10 {
+                    thread::yield_now();
+                }
+                assert!(!later.has_fired());
+            }
+            done.store(true, Relaxed);
+        });
+
+        // A ticker which read the expired deadline before the slot was rescheduled
+        let expired = queue.schedule_at(Duration::ZERO).unwrap();
+        let armed = queue.timers[0].state.load(Acquire);
+        drop(expired);
+        let later = queue.schedule_after(Duration::from_secs(60)).unwrap();
+        assert!(!queue.timers[0].fire(armed));
+        assert!(!later.has_fired());
+    }
+
+    #[test]
+    fn dead_ticker() {
+        let queue = crate::Shared::<TimerQueue<1>>::create_anon().unwrap();
+        assert!(queue.try_become_ticker());
+        assert!(queue.try_become_ticker());
+        // A ticker which exited without stepping down
+        queue.ticker.store(i32::MAX as u32, Relaxed);
+        assert!(queue.try_become_ticker());
+    }
+}
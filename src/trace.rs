@@ -0,0 +1,374 @@
+use {
+    crate::Shareable,
+    core::{
+        fmt,
+        ptr::null_mut,
+        sync::atomic::{
+            fence, AtomicI64, AtomicPtr, AtomicU32, AtomicU64,
+            Ordering::{Acquire, Relaxed, Release},
+        },
+    },
+    std::collections::BTreeMap,
+};
+
+/// Number of events retained by a TraceRing before the oldest are overwritten.
+pub const TRACE_CAPACITY: usize = 4096;
+
+static RING: AtomicPtr<TraceRing> = AtomicPtr::new(null_mut());
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    Acquire,
+    Release,
+    AcquireShared,
+    ReleaseShared,
+}
+
+impl Op {
+    fn from_u32(v: u32) -> Option<Self> {
+        match v {
+            0 => Some(Op::Acquire),
+            1 => Some(Op::Release),
+            2 => Some(Op::AcquireShared),
+            3 => Some(Op::ReleaseShared),
+            _ => None,
+        }
+    }
+}
+
+/// A recorded lock operation.
+///
+/// `lock` identifies the lock by its byte offset from the TraceRing, which is the same in
+/// every process as long as the lock and the ring live in the same shared region.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    pub seq: u64,
+    pub lock: i64,
+    pub pid: u32,
+    pub tid: u32,
+    pub op: Op,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} lock {:+} {:?} pid {} tid {}",
+            self.seq, self.lock, self.op, self.pid, self.tid
+        )
+    }
+}
+
+#[derive(Default)]
+#[repr(C)]
+struct Slot {
+    /// 0 while empty or being written, otherwise the event sequence number.
+    seq: AtomicU64,
+    lock: AtomicI64,
+    pid: AtomicU32,
+    tid: AtomicU32,
+    op: AtomicU32,
+}
+
+/// A shared ring of lock acquisitions and releases, intended to be placed in a Shareable struct.
+///
+/// The ring is about 128 KiB, which [`Default`] builds on the stack. Regions created by this
+/// crate initialize it in place instead (see [`Shareable::init_in_place`]), which a struct
+/// embedding the ring should forward with [`TraceRing::init_in_place`].
+#[repr(C)]
+pub struct TraceRing {
+    next: AtomicU64,
+    slots: [Slot; TRACE_CAPACITY],
+}
+
+unsafe impl Shareable for TraceRing {
+    unsafe fn init_in_place(ptr: *mut Self) {
+        unsafe { TraceRing::init_in_place(ptr) }
+    }
+}
+
+impl Default for TraceRing {
+    fn default() -> Self {
+        Self {
+            next: AtomicU64::new(0),
+            slots: core::array::from_fn(|_| Slot::default()),
+        }
+    }
+}
+
+/// A critical section reconstructed from a lock's acquire and release events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Section {
+    pub lock: i64,
+    pub pid: u32,
+    pub tid: u32,
+    pub shared: bool,
+    /// The sequence number of the acquisition
+    pub acquired: u64,
+    /// The sequence number of the release, or None if it's still held (or the release was
+    /// overwritten)
+    pub released: Option<u64>,
+}
+
+impl Section {
+    fn overlaps(&self, other: &Self) -> bool {
+        self.lock == other.lock
+            && (!self.shared || !other.shared)
+            && self.acquired < other.released.unwrap_or(u64::MAX)
+            && other.acquired < self.released.unwrap_or(u64::MAX)
+    }
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = if self.shared { "shared" } else { "exclusive" };
+        write!(
+            f,
+            "lock {:+} {mode} pid {} tid {} #{}..",
+            self.lock, self.pid, self.tid, self.acquired
+        )?;
+        match self.released {
+            Some(seq) => write!(f, "#{seq}"),
+            None => write!(f, "(held)"),
+        }
+    }
+}
+
+impl TraceRing {
+    /// Returns the retained events ordered by sequence number.
+    pub fn events(&self) -> Vec<Event> {
+        let mut events: Vec<_> = self.slots.iter().filter_map(Slot::read).collect();
+        events.sort_unstable_by_key(|e| e.seq);
+        events
+    }
+
+    /// Reconstructs the interleaving of processes and threads for each lock.
+    pub fn by_lock(&self) -> BTreeMap<i64, Vec<Event>> {
+        let mut locks = BTreeMap::<_, Vec<_>>::new();
+        for e in self.events() {
+            locks.entry(e.lock).or_default().push(e);
+        }
+        locks
+    }
+
+    /// Replays the retained events into the critical sections they delimit, ordered by
+    /// acquisition. Each release closes the oldest open section of the same lock, mode and
+    /// thread.
+    pub fn sections(&self) -> Vec<Section> {
+        let mut sections = Vec::<Section>::new();
+        for e in self.events() {
+            let shared = matches!(e.op, Op::AcquireShared | Op::ReleaseShared);
+            match e.op {
+                Op::Acquire | Op::AcquireShared => sections.push(Section {
+                    lock: e.lock,
+                    pid: e.pid,
+                    tid: e.tid,
+                    shared,
+                    acquired: e.seq,
+                    released: None,
+                }),
+                Op::Release | Op::ReleaseShared => {
+                    // Releases whose acquisition was overwritten are skipped.
+                    if let Some(section) = sections.iter_mut().find(|s| {
+                        s.released.is_none()
+                            && (s.lock, s.pid, s.tid, s.shared) == (e.lock, e.pid, e.tid, shared)
+                    }) {
+                        section.released = Some(e.seq);
+                    }
+                }
+            }
+        }
+        sections
+    }
+
+    /// Pairs of sections which held the same lock at once, with at least one of them exclusive.
+    /// A correct lock never records these, so they point at the ordering bug being diagnosed (or
+    /// a lock released by another thread than acquired it).
+    pub fn overlaps(&self) -> Vec<(Section, Section)> {
+        let sections = self.sections();
+        let mut overlaps = Vec::new();
+        for (i, a) in sections.iter().enumerate() {
+            // Sections are ordered by acquisition, so later ones can't overlap once one starts
+            // after `a` is released.
+            for b in sections[i + 1..]
+                .iter()
+                .take_while(|b| b.acquired < a.released.unwrap_or(u64::MAX))
+            {
+                if a.overlaps(b) {
+                    overlaps.push((*a, *b));
+                }
+            }
+        }
+        overlaps
+    }
+
+    /// Initializes an empty ring without building it on the stack.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes and aligned for `Self`, and no other thread or process may
+    /// access the memory until this method returns.
+    pub unsafe fn init_in_place(ptr: *mut Self) {
+        // [SAFETY]: Zeroed atomics are valid, and an empty slot has sequence number 0.
+        unsafe { ptr.write_bytes(0, 1) }
+    }
+
+    /// Borrows a ring from memory not managed by this crate (ex: a snapshot read by a tool).
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be aligned and point to an initialized TraceRing which remains valid for `'a`.
+    pub unsafe fn from_raw<'a>(ptr: *const Self) -> &'a Self {
+        unsafe { &*ptr }
+    }
+
+    /// The number of events overwritten before they could be read.
+    pub fn dropped(&self) -> u64 {
+        self.next
+            .load(Relaxed)
+            .saturating_sub(TRACE_CAPACITY as u64)
+    }
+
+    fn record(&self, lock: i64, op: Op) {
+        let seq = self.next.fetch_add(1, Relaxed) + 1;
+        let slot = &self.slots[((seq - 1) % TRACE_CAPACITY as u64) as usize];
+
+        slot.seq.store(0, Relaxed);
+        fence(Release);
+        slot.lock.store(lock, Relaxed);
+        slot.pid.store(unsafe { libc::getpid() } as u32, Relaxed);
+        slot.tid.store(unsafe { libc::gettid() } as u32, Relaxed);
+        slot.op.store(op as u32, Relaxed);
+        slot.seq.store(seq, Release);
+    }
+}
+
+impl Slot {
+    fn read(&self) -> Option<Event> {
+        let seq = self.seq.load(Acquire);
+        let event = Event {
+            seq,
+            lock: self.lock.load(Relaxed),
+            pid: self.pid.load(Relaxed),
+            tid: self.tid.load(Relaxed),
+            op: Op::from_u32(self.op.load(Relaxed))?,
+        };
+        fence(Acquire);
+        (seq != 0 && self.seq.load(Relaxed) == seq).then_some(event)
+    }
+}
+
+/// Starts recording this process's lock operations into `ring`.
+///
+/// # Safety
+///
+/// The ring must remain mapped until [`uninstall`] has been called.
+pub unsafe fn install(ring: &TraceRing) {
+    RING.store(ring as *const _ as *mut _, Release);
+}
+
+/// Stops recording this process's lock operations.
+pub fn uninstall() {
+    RING.store(null_mut(), Release);
+}
+
+#[inline]
+pub(crate) fn record<T: ?Sized>(lock: *const T, op: Op) {
+    let ring = RING.load(Acquire);
+    if !ring.is_null() {
+        // [SAFETY]: The installer guarantees the ring outlives its installation.
+        let ring = unsafe { &*ring };
+        let offset = (lock as *const u8 as isize).wrapping_sub(ring as *const _ as isize);
+        ring.record(offset as i64, op);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        crate::{Mutex, Shared},
+        core::ptr::addr_of_mut,
+    };
+
+    #[derive(Default)]
+    struct S {
+        ring: TraceRing,
+        m: Mutex<u32>,
+    }
+
+    unsafe impl Shareable for S {
+        unsafe fn init_in_place(ptr: *mut Self) {
+            unsafe {
+                TraceRing::init_in_place(addr_of_mut!((*ptr).ring));
+                addr_of_mut!((*ptr).m).write(Mutex::default());
+            }
+        }
+    }
+
+    #[test]
+    fn trace_interleaving() {
+        let s: &'static Shared<S> = Box::leak(Box::new(Shared::create_anon().unwrap()));
+        unsafe { install(&s.ring) };
+
+        std::thread::scope(|scope| {
+            for _ in 0..2 {
+                scope.spawn(|| {
+                    for _ in 0..10 {
+                        *s.m.lock() += 1;
+                    }
+                });
+            }
+        });
+        uninstall();
+
+        let lock = (&s.m as *const _ as isize - &s.ring as *const _ as isize) as i64;
+        let events = s.ring.by_lock().remove(&lock).unwrap();
+        assert_eq!(events.len(), 40);
+        for pair in events.chunks(2) {
+            assert_eq!(pair[0].op, Op::Acquire);
+            assert_eq!(pair[1].op, Op::Release);
+            assert_eq!(pair[0].tid, pair[1].tid);
+        }
+
+        let sections = s.ring.sections();
+        assert_eq!(sections.len(), 20);
+        assert!(sections
+            .iter()
+            .all(|s| s.lock == lock && s.released.is_some()));
+        assert!(s.ring.overlaps().is_empty());
+    }
+
+    #[test]
+    fn overlaps() {
+        let s: Shared<S> = Shared::create_anon().unwrap();
+        // Two threads recorded as holding the lock at once, and a shared holder alongside
+        // another.
+        for (lock, op) in [
+            (8, Op::Acquire),
+            (8, Op::Acquire),
+            (8, Op::Release),
+            (8, Op::Release),
+            (16, Op::AcquireShared),
+            (16, Op::AcquireShared),
+            (16, Op::ReleaseShared),
+            (16, Op::ReleaseShared),
+        ] {
+            s.ring.record(lock, op);
+        }
+
+        let overlaps = s.ring.overlaps();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!((overlaps[0].0.acquired, overlaps[0].1.acquired), (1, 2));
+    }
+
+    #[test]
+    fn created_in_place() {
+        // Building the ring on this stack would overflow it.
+        let ring = std::thread::Builder::new()
+            .stack_size(64 * 1024)
+            .spawn(|| Shared::<TraceRing>::create_anon().unwrap().events().len())
+            .unwrap();
+        assert_eq!(ring.join().unwrap(), 0);
+    }
+}
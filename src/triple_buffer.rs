@@ -0,0 +1,142 @@
+use {
+    crate::{
+        ordering::{AcqRel, Relaxed, Release},
+        spsc::claim,
+        Shareable,
+    },
+    core::{cell::UnsafeCell, sync::atomic::AtomicU32},
+};
+
+/// Set in `back` when it holds a value the subscriber hasn't seen
+const FRESH: u32 = 4;
+const INDEX: u32 = 3;
+
+/// Wait-free exchange of the latest value between one publisher and one subscriber.
+///
+/// Of the three slots the publisher owns one to write into, the subscriber owns one to read from
+/// and the third (back) slot holds the most recently published value. Ownership is exchanged by
+/// swapping slot indices, so neither side ever waits for the other.
+pub struct TripleBuffer<T> {
+    /// Index of the back slot, with FRESH once a value is published into it
+    back: AtomicU32,
+    /// Index of the publisher's slot (only accessed by the publisher)
+    write: AtomicU32,
+    /// Index of the subscriber's slot (only accessed by the subscriber)
+    read: AtomicU32,
+    /// The id (see [`crate::owner`]) of the process holding each end, or 0
+    publisher: AtomicU32,
+    subscriber: AtomicU32,
+    slots: [UnsafeCell<T>; 3],
+}
+
+unsafe impl<T: Copy + Send> Sync for TripleBuffer<T> {}
+
+unsafe impl<T: Shareable + Copy + Send> Shareable for TripleBuffer<T> {}
+
+impl<T: Default> Default for TripleBuffer<T> {
+    fn default() -> Self {
+        Self {
+            back: AtomicU32::new(1),
+            write: AtomicU32::new(0),
+            read: AtomicU32::new(2),
+            publisher: AtomicU32::new(0),
+            subscriber: AtomicU32::new(0),
+            slots: Default::default(),
+        }
+    }
+}
+
+impl<T: Copy> TripleBuffer<T> {
+    /// Claims the publishing end, returning None if another live process holds it.
+    pub fn publisher(&self) -> Option<Publisher<'_, T>> {
+        claim(&self.publisher).then(|| Publisher { buffer: self })
+    }
+
+    /// Claims the subscribing end, returning None if another live process holds it.
+    pub fn subscriber(&self) -> Option<Subscriber<'_, T>> {
+        claim(&self.subscriber).then(|| Subscriber { buffer: self })
+    }
+}
+
+/// The writing end of a [`TripleBuffer`], released when dropped.
+pub struct Publisher<'a, T> {
+    buffer: &'a TripleBuffer<T>,
+}
+
+impl<T: Copy> Publisher<'_, T> {
+    /// Makes `value` the latest value.
+    pub fn publish(&mut self, value: T) {
+        let buffer = self.buffer;
+        let write = buffer.write.load(Relaxed);
+        // [SAFETY]: The write slot is exclusively owned by the publisher.
+        unsafe { *buffer.slots[write as usize].get() = value };
+        let back = buffer.back.swap(write | FRESH, AcqRel);
+        buffer.write.store(back & INDEX, Relaxed);
+    }
+}
+
+impl<T> Drop for Publisher<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.publisher.store(0, Release);
+    }
+}
+
+/// The reading end of a [`TripleBuffer`], released when dropped.
+pub struct Subscriber<'a, T> {
+    buffer: &'a TripleBuffer<T>,
+}
+
+impl<T: Copy> Subscriber<'_, T> {
+    /// Returns true if a value was published since the last call to [`Self::latest`].
+    pub fn has_update(&self) -> bool {
+        self.buffer.back.load(Relaxed) & FRESH != 0
+    }
+
+    /// Returns the most recently published value (or the default if none was published).
+    pub fn latest(&mut self) -> T {
+        let buffer = self.buffer;
+        let mut read = buffer.read.load(Relaxed);
+        if self.has_update() {
+            read = buffer.back.swap(read, AcqRel) & INDEX;
+            buffer.read.store(read, Relaxed);
+        }
+        // [SAFETY]: The read slot is exclusively owned by the subscriber.
+        unsafe { *buffer.slots[read as usize].get() }
+    }
+}
+
+impl<T> Drop for Subscriber<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.subscriber.store(0, Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn latest_value() {
+        let buffer = TripleBuffer::<[u64; 4]>::default();
+        let mut publisher = buffer.publisher().unwrap();
+        let mut subscriber = buffer.subscriber().unwrap();
+        assert!(buffer.subscriber().is_none());
+
+        assert_eq!(subscriber.latest(), [0; 4]);
+        publisher.publish([1; 4]);
+        publisher.publish([2; 4]);
+        assert!(subscriber.has_update());
+        assert_eq!(subscriber.latest(), [2; 4]);
+        assert!(!subscriber.has_update());
+
+        thread::scope(|s| {
+            s.spawn(|| (3..=10_000).for_each(|i| publisher.publish([i; 4])));
+            let mut last = 2;
+            while last < 10_000 {
+                let value = subscriber.latest();
+                assert!(value.iter().all(|&v| v == value[0]) && value[0] >= last);
+                last = value[0];
+            }
+        });
+    }
+}
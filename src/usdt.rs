@@ -0,0 +1,71 @@
+// USDT (SystemTap SDT) probes, attachable at runtime with bpftrace or perf, ex:
+//
+//   bpftrace -e 'usdt:./server:shm:lock_contend { @[arg0] = count(); }'
+//
+// With the `usdt` feature each probe site is a single nop plus an entry in the `.note.stapsdt`
+// ELF section describing where its arguments live. Without the feature probes compile to nothing.
+//
+// Probes (all arguments are u64):
+//   lock_acquire(addr), lock_contend(addr), lock_release(addr)
+//   futex_wait(addr, expected), futex_wake(addr, count)
+
+#[cfg(all(feature = "usdt", target_arch = "x86_64"))]
+macro_rules! probe {
+    ($name:literal, $a0:expr) => {
+        $crate::usdt::probe!(@emit $name, "8@{0}", $a0)
+    };
+    ($name:literal, $a0:expr, $a1:expr) => {
+        $crate::usdt::probe!(@emit $name, "8@{0} 8@{1}", $a0, $a1)
+    };
+    (@emit $name:literal, $args:literal, $($arg:expr),+) => {
+        unsafe {
+            core::arch::asm!(
+                "990: nop",
+                ".pushsection .note.stapsdt, \"\", \"note\"",
+                ".balign 4",
+                ".4byte 992f-991f, 994f-993f, 3",
+                "991: .asciz \"stapsdt\"",
+                "992: .balign 4",
+                "993: .8byte 990b",
+                ".8byte _.stapsdt.base",
+                ".8byte 0",
+                ".asciz \"shm\"",
+                concat!(".asciz \"", $name, "\""),
+                concat!(".asciz \"", $args, "\""),
+                "994: .balign 4",
+                ".popsection",
+                ".ifndef _.stapsdt.base",
+                ".pushsection .stapsdt.base, \"aGR\", \"progbits\", .stapsdt.base, comdat",
+                ".weak _.stapsdt.base",
+                ".hidden _.stapsdt.base",
+                "_.stapsdt.base: .space 1",
+                ".size _.stapsdt.base, 1",
+                ".popsection",
+                ".endif",
+                $(in(reg) $arg as u64,)+
+                options(att_syntax, nomem, nostack, preserves_flags),
+            )
+        }
+    };
+}
+
+#[cfg(not(all(feature = "usdt", target_arch = "x86_64")))]
+macro_rules! probe {
+    ($name:literal $(, $arg:expr)*) => {};
+}
+
+pub(crate) use probe;
+
+#[cfg(all(test, feature = "usdt", target_arch = "x86_64"))]
+mod tests {
+    #[test]
+    fn probes_are_described() {
+        drop(crate::Mutex::new(()).lock());
+
+        let exe = std::fs::read("/proc/self/exe").unwrap();
+        let find = |needle: &[u8]| exe.windows(needle.len()).any(|w| w == needle);
+        assert!(find(b"stapsdt\0"));
+        assert!(find(b"shm\0lock_acquire\0"));
+        assert!(find(b"shm\0futex_wake\0"));
+    }
+}
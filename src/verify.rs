@@ -0,0 +1,110 @@
+use std::{fmt, time::Duration};
+
+/// A failed invariant check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub name: &'static str,
+    /// The number of verification passes run, the last of which detected the violation.
+    pub passes: u64,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invariant '{}' violated (pass {})",
+            self.name, self.passes
+        )
+    }
+}
+
+type Check<'a, T> = Box<dyn Fn(&T) -> bool + 'a>;
+
+/// Periodically checks invariants over a shared object.
+///
+/// Each check receives `&T` and is responsible for taking whatever locks (or snapshots) it
+/// needs to observe a consistent state.
+pub struct Verifier<'a, T> {
+    target: &'a T,
+    checks: Vec<(&'static str, Check<'a, T>)>,
+    passes: u64,
+}
+
+impl<'a, T> Verifier<'a, T> {
+    pub fn new(target: &'a T) -> Self {
+        Self {
+            target,
+            checks: Vec::new(),
+            passes: 0,
+        }
+    }
+
+    /// Registers a check returning false when the invariant does not hold.
+    pub fn register(&mut self, name: &'static str, check: impl Fn(&T) -> bool + 'a) -> &mut Self {
+        self.checks.push((name, Box::new(check)));
+        self
+    }
+
+    /// The number of completed verification passes.
+    pub fn passes(&self) -> u64 {
+        self.passes
+    }
+
+    /// Runs every registered check once.
+    pub fn run(&mut self) -> Vec<Violation> {
+        self.passes += 1;
+        self.checks
+            .iter()
+            .filter(|(_, check)| !check(self.target))
+            .map(|(name, _)| Violation {
+                name,
+                passes: self.passes,
+            })
+            .collect()
+    }
+
+    /// Runs the checks every `period` until `stop` returns true, reporting each violation.
+    pub fn run_every(
+        &mut self,
+        period: Duration,
+        mut stop: impl FnMut() -> bool,
+        mut report: impl FnMut(Violation),
+    ) {
+        while !stop() {
+            self.run().into_iter().for_each(&mut report);
+            std::thread::sleep(period);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, crate::Mutex};
+
+    #[test]
+    fn verifier() {
+        #[derive(Default)]
+        struct S {
+            balance: Mutex<(u32, u32)>,
+        }
+
+        let s = S::default();
+        let mut v = Verifier::new(&s);
+        v.register("balanced", |s| {
+            let b = s.balance.lock();
+            b.0 == b.1
+        });
+
+        assert!(v.run().is_empty());
+        assert_eq!(v.passes(), 1);
+
+        s.balance.lock().0 = 1;
+        assert_eq!(
+            v.run(),
+            [Violation {
+                name: "balanced",
+                passes: 2
+            }]
+        );
+    }
+}
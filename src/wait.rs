@@ -0,0 +1,174 @@
+//! How a blocked channel endpoint (see [`crate::mpsc`] and [`crate::spsc`]) waits for its peer,
+//! selected per endpoint (ex: busy-polling on an isolated core while other endpoints sleep).
+
+use {
+    crate::{ordering::Acquire, Doorbell},
+    core::sync::atomic::AtomicU32,
+    std::time::Instant,
+};
+
+/// A way of waiting for a futex word to change.
+///
+/// Endpoints always wake their peer's futex, so endpoints using different strategies can share a
+/// channel.
+pub trait WaitStrategy {
+    /// Waits while `word` holds `expected`, returning false if `deadline` passes first. Like a
+    /// futex wait, it may return spuriously.
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool;
+
+    /// Called once the endpoint has changed a word its peer waits on and woken the futex, for
+    /// strategies which also signal the peer another way.
+    fn woke(&self) {}
+}
+
+impl<W: WaitStrategy + ?Sized> WaitStrategy for &W {
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+        (**self).wait(word, expected, deadline)
+    }
+
+    fn woke(&self) {
+        (**self).woke()
+    }
+}
+
+/// Sleeps in the kernel (a futex wait), the default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Park;
+
+impl WaitStrategy for Park {
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+        crate::futex::wait_until(word, expected, deadline)
+    }
+}
+
+/// Polls the word without ever sleeping, for the lowest wakeup latency at the cost of a core.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BusySpin;
+
+impl WaitStrategy for BusySpin {
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+        SpinThenYield { spins: u32::MAX }.wait(word, expected, deadline)
+    }
+}
+
+/// Polls the word `spins` times, then yields the core between polls.
+#[derive(Clone, Copy, Debug)]
+pub struct SpinThenYield {
+    pub spins: u32,
+}
+
+impl Default for SpinThenYield {
+    fn default() -> Self {
+        Self { spins: 100 }
+    }
+}
+
+impl WaitStrategy for SpinThenYield {
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+        let mut spins = 0;
+        while word.load(Acquire) == expected {
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return false;
+            }
+            if spins < self.spins {
+                crate::spin::relax(word, expected);
+                spins += 1;
+            } else {
+                std::thread::yield_now();
+            }
+        }
+        true
+    }
+}
+
+/// Waits for the eventfd to be rung, which its peers do through [`Ringing`] (ex: so the end's event
+/// loop polls the Doorbell instead). Only this end may wait on (and so drain) the Doorbell, or it
+/// could consume another waiter's ring.
+impl WaitStrategy for Doorbell {
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+        if word.load(Acquire) != expected {
+            return true;
+        }
+        let timeout = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        match Doorbell::wait(self, timeout) {
+            // Rings before the word was loaded return spuriously.
+            Ok(true) => {
+                let _ = self.drain();
+                true
+            }
+            // poll's timeout is in milliseconds, so it may end just short of the deadline.
+            Ok(false) => deadline.is_none_or(|deadline| Instant::now() < deadline),
+            // Treated as a spurious wakeup, so the caller rechecks.
+            Err(_) => true,
+        }
+    }
+}
+
+/// Waits with W, and also rings the Doorbell of a peer which waits on it whenever waking it (a
+/// duplicate of its descriptor, see [`Doorbell`]'s [`WaitStrategy`]).
+#[derive(Clone, Copy, Debug)]
+pub struct Ringing<'a, W = Park> {
+    pub doorbell: &'a Doorbell,
+    pub wait: W,
+}
+
+impl<'a> Ringing<'a> {
+    pub fn new(doorbell: &'a Doorbell) -> Self {
+        Self {
+            doorbell,
+            wait: Park,
+        }
+    }
+}
+
+impl<W: WaitStrategy> WaitStrategy for Ringing<'_, W> {
+    fn wait(&self, word: &AtomicU32, expected: u32, deadline: Option<Instant>) -> bool {
+        self.wait.wait(word, expected, deadline)
+    }
+
+    fn woke(&self) {
+        self.wait.woke();
+        let _ = self.doorbell.ring();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        core::{sync::atomic::Ordering::Relaxed, time::Duration},
+        std::thread,
+    };
+
+    #[test]
+    fn strategies() {
+        let doorbell = Doorbell::new().unwrap();
+        let ringing = Ringing::new(&doorbell);
+        // Each strategy with one its peer may use
+        let strategies: [(&(dyn WaitStrategy + Sync), &(dyn WaitStrategy + Sync)); 4] = [
+            (&Park, &Park),
+            (&BusySpin, &Park),
+            (&SpinThenYield { spins: 10 }, &Park),
+            (&doorbell, &ringing),
+        ];
+        for (strategy, peer) in strategies {
+            let word = AtomicU32::new(0);
+            let deadline = Instant::now() + Duration::from_millis(10);
+            while strategy.wait(&word, 0, Some(deadline)) {}
+            assert!(Instant::now() >= deadline);
+            assert!(strategy.wait(&word, 1, None));
+
+            thread::scope(|s| {
+                s.spawn(|| {
+                    thread::sleep(Duration::from_millis(10));
+                    word.store(1, Relaxed);
+                    crate::futex::wake_all(&word);
+                    peer.woke();
+                });
+                while word.load(Relaxed) == 0 {
+                    assert!(strategy.wait(&word, 0, None));
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use {
+    crate::{
+        ordering::{Acquire, Release},
+        rwlock::ReadGuard,
+        sync::const_fn,
+        RwLock, Shareable,
+    },
+    core::{sync::atomic::AtomicU32, time::Duration},
+    std::time::Instant,
+};
+
+/// The latest of a series of values (ex: a configuration), which processes can block on until it
+/// changes, mirroring tokio's `watch` channel.
+///
+/// Any process may send. Each reader tracks the version it last saw through a [`WatchReceiver`].
+#[derive(Default)]
+pub struct Watch<T> {
+    /// Incremented by every send (also the futex waited on by receivers)
+    version: AtomicU32,
+    value: RwLock<T>,
+}
+
+unsafe impl<T: Shareable + Send> Shareable for Watch<T> {}
+
+impl<T> Watch<T> {
+    const_fn! {
+        pub fn new(value: T) -> Self {
+            Self {
+                version: AtomicU32::new(0),
+                value: RwLock::new(value),
+            }
+        }
+    }
+
+    /// Replaces the value and wakes every receiver.
+    pub fn send(&self, value: T) {
+        self.send_modify(|v| *v = value);
+    }
+
+    /// Modifies the value in place and wakes every receiver.
+    pub fn send_modify(&self, modify: impl FnOnce(&mut T)) {
+        let mut value = self.value.write();
+        modify(&mut value);
+        self.version.fetch_add(1, Release);
+        drop(value);
+        crate::futex::wake_all(&self.version);
+    }
+
+    /// The current value, which blocks senders until the guard is dropped.
+    pub fn borrow(&self) -> ReadGuard<'_, T> {
+        self.value.read()
+    }
+
+    /// The number of values sent (wrapping).
+    pub fn version(&self) -> u32 {
+        self.version.load(Acquire)
+    }
+
+    /// A receiver which has seen the current value.
+    pub fn subscribe(&self) -> WatchReceiver<'_, T> {
+        WatchReceiver {
+            watch: self,
+            seen: self.version(),
+        }
+    }
+}
+
+/// A process local view of a [`Watch`], tracking the last version seen.
+#[derive(Clone)]
+pub struct WatchReceiver<'a, T> {
+    watch: &'a Watch<T>,
+    seen: u32,
+}
+
+impl<'a, T> WatchReceiver<'a, T> {
+    /// Whether a value was sent since the receiver last marked the value as seen.
+    pub fn has_changed(&self) -> bool {
+        self.watch.version() != self.seen
+    }
+
+    /// The current value, without marking it as seen.
+    pub fn borrow(&self) -> ReadGuard<'a, T> {
+        self.watch.borrow()
+    }
+
+    /// The current value, marking it as seen.
+    pub fn borrow_and_update(&mut self) -> ReadGuard<'a, T> {
+        let value = self.watch.borrow();
+        // Sends increment the version under the write lock, so it matches the value.
+        self.seen = self.watch.version();
+        value
+    }
+
+    /// Blocks until a value is sent which hasn't been seen, then marks it as seen.
+    pub fn changed(&mut self) {
+        self.changed_until(None);
+    }
+
+    /// Like [`Self::changed`], but returns false if `timeout` elapses first.
+    pub fn changed_timeout(&mut self, timeout: Duration) -> bool {
+        self.changed_until(Instant::now().checked_add(timeout))
+    }
+
+    /// Like [`Self::changed`], but waits on tokio's blocking pool rather than blocking the
+    /// runtime's thread.
+    #[cfg(feature = "async")]
+    pub async fn changed_async(&mut self) {
+        loop {
+            let version = self.watch.version();
+            if version != self.seen {
+                self.seen = version;
+                return;
+            }
+            crate::futex::wait_async(&self.watch.version, version).await;
+        }
+    }
+
+    fn changed_until(&mut self, deadline: Option<Instant>) -> bool {
+        loop {
+            let version = self.watch.version();
+            if version != self.seen {
+                self.seen = version;
+                return true;
+            }
+            if !crate::futex::wait_until(&self.watch.version, version, deadline) {
+                return false;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, std::thread};
+
+    #[test]
+    fn watch() {
+        let watch = Watch::new(0);
+        let mut rx = watch.subscribe();
+        assert!(!rx.has_changed());
+        assert!(!rx.changed_timeout(Duration::from_millis(10)));
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                for v in 1..=3 {
+                    thread::sleep(Duration::from_millis(10));
+                    watch.send(v);
+                }
+                watch.send_modify(|v| *v *= 10);
+            });
+            let mut last = 0;
+            while last != 30 {
+                rx.changed();
+                let value = *rx.borrow_and_update();
+                assert!(value > last);
+                last = value;
+            }
+        });
+        assert!(!rx.has_changed());
+        assert_eq!(watch.version(), 4);
+    }
+}